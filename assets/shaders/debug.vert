@@ -0,0 +1,15 @@
+#version 430 core
+
+layout (location = 0) in vec3 VertexPosition;
+layout (location = 1) in vec4 VertexColor;
+
+out vec4 LineColor;
+
+#include "include/camera.glsl"
+#include "include/quat.glsl"
+
+void main() {
+	LineColor = VertexColor;
+	vec3 EyePosition = quat_mul(quat_inv(cam.rot), VertexPosition - cam.pos);
+	gl_Position = perspective(cam.proj, vec3(EyePosition.xz, -EyePosition.y));
+}
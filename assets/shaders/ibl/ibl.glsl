@@ -0,0 +1,46 @@
+// Shared by the environment precomputation passes, which write one cubemap level with a work group layer per face
+
+#include "../include/cubemap.glsl"
+
+#define PI 3.14159265359
+
+// World direction through the center of texel Coord of face Face in a cubemap of Size
+vec3 texel_dir(ivec2 Coord, int Face, int Size) {
+	vec2 uv = (vec2(Coord) + 0.5) / float(Size) * 2.0 - 1.0;
+	vec3 dirs[6] = vec3[](
+		vec3(1.0, -uv.y, -uv.x),
+		vec3(-1.0, -uv.y, uv.x),
+		vec3(uv.x, 1.0, uv.y),
+		vec3(uv.x, -1.0, -uv.y),
+		vec3(uv.x, -uv.y, 1.0),
+		vec3(-uv.x, -uv.y, -1.0)
+	);
+	return normalize(cube_to_world(dirs[Face]));
+}
+
+// Low discrepancy point i of n in [0, 1)^2
+vec2 hammersley(uint i, uint n) {
+	return vec2(float(i) / float(n), float(bitfieldReverse(i)) * 2.3283064365386963e-10);
+}
+
+// Basis with z along N
+mat3 tangent_frame(vec3 N) {
+	vec3 up = abs(N.z) < 0.999 ? vec3(0.0, 0.0, 1.0) : vec3(1.0, 0.0, 0.0);
+	vec3 T = normalize(cross(up, N));
+	return mat3(T, cross(N, T), N);
+}
+
+// Half vector sampled around N in proportion to the GGX distribution
+vec3 importance_sample_ggx(vec2 Xi, vec3 N, float Roughness) {
+	float a = Roughness * Roughness;
+	float phi = 2.0 * PI * Xi.x;
+	float cos_theta = sqrt((1.0 - Xi.y) / (1.0 + (a * a - 1.0) * Xi.y));
+	float sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+	return tangent_frame(N) * vec3(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta);
+}
+
+float distribution_ggx(float NdotH, float Roughness) {
+	float a2 = Roughness * Roughness * Roughness * Roughness;
+	float d = NdotH * NdotH * (a2 - 1.0) + 1.0;
+	return a2 / (PI * d * d);
+}
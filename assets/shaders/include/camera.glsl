@@ -0,0 +1,9 @@
+layout (std140, binding = 0) uniform Camera {
+	vec4 proj;
+	vec4 rot;
+	vec3 pos;
+} cam;
+
+vec4 perspective(vec4 Projection, vec3 Position) {
+	return vec4(Position.xy * Projection.xy, Position.z * Projection.z + Projection.w, -Position.z);
+}
@@ -0,0 +1,9 @@
+// The world is Z-up while cubemaps are Y-up, with world +Y (forward) on the cubemap's -Z face
+
+vec3 world_to_cube(vec3 World) {
+	return vec3(World.x, World.z, -World.y);
+}
+
+vec3 cube_to_world(vec3 Cube) {
+	return vec3(Cube.x, -Cube.z, Cube.y);
+}
@@ -0,0 +1,23 @@
+// Image-based ambient light from the skybox, using the split-sum approximation
+
+#include "cubemap.glsl"
+
+uniform samplerCube irradiance_map;
+uniform samplerCube prefiltered_map;
+uniform sampler2D brdf_lut;
+
+vec3 fresnel_schlick_roughness(float Cos, vec3 F0, float Roughness) {
+	return F0 + (max(vec3(1.0 - Roughness), F0) - F0) * pow(clamp(1.0 - Cos, 0.0, 1.0), 5.0);
+}
+
+vec3 environment_light(vec3 Albedo, float Metallic, float Roughness, vec3 N, vec3 V, vec3 F0) {
+	float NdotV = max(dot(N, V), 1e-4);
+	vec3 F = fresnel_schlick_roughness(NdotV, F0, Roughness);
+	vec3 diffuse = texture(irradiance_map, world_to_cube(N)).rgb * Albedo * (1.0 - F) * (1.0 - Metallic);
+
+	vec3 R = reflect(-V, N);
+	float Lod = Roughness * float(textureQueryLevels(prefiltered_map) - 1);
+	vec3 prefiltered = textureLod(prefiltered_map, world_to_cube(R), Lod).rgb;
+	vec2 brdf = texture(brdf_lut, vec2(NdotV, Roughness)).rg;
+	return diffuse + prefiltered * (F * brdf.x + brdf.y);
+}
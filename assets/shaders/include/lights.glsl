@@ -0,0 +1,127 @@
+// MAX_LIGHTS is defined by the engine
+
+#include "camera.glsl"
+#include "quat.glsl"
+#include "shadows.glsl"
+#include "environment.glsl"
+
+#define LIGHT_DIRECTIONAL 0
+#define LIGHT_POINT 1
+#define LIGHT_SPOT 2
+
+struct Light {
+	vec4 pos;
+	vec4 dir;
+	vec4 color;
+	vec4 cone;
+};
+
+layout (std140, binding = 1) uniform Lights {
+	// w scales the environment's light, which replaces the flat rgb ambient when there is one
+	vec4 ambient;
+	ivec4 count;
+	Light lights[MAX_LIGHTS];
+} lights;
+
+// Direction towards the light and attenuation at Position
+vec4 light_incidence(Light light, vec3 Position) {
+	int kind = int(light.pos.w);
+	if (kind == LIGHT_DIRECTIONAL) {
+		return vec4(-light.dir.xyz, 1.0);
+	}
+
+	vec3 ToLight = light.pos.xyz - Position;
+	float dist = length(ToLight);
+	vec3 L = ToLight / dist;
+	float falloff = clamp(1.0 - pow(dist / light.dir.w, 4.0), 0.0, 1.0);
+	float atten = falloff * falloff / (dist * dist + 1.0);
+	if (kind == LIGHT_SPOT) {
+		atten *= smoothstep(light.cone.y, light.cone.x, dot(-L, light.dir.xyz));
+	}
+	return vec4(L, atten);
+}
+
+uniform sampler2DArrayShadow shadow_maps;
+
+// 3x3 PCF on top of the hardware's 2x2. Returns 1 when lit or outside the map.
+float shadow_pcf(int Layer, vec3 Position, vec3 N, vec3 L) {
+	vec2 Texel = 1.0 / vec2(textureSize(shadow_maps, 0).xy);
+	// Pushes the lookup off the surface to hide acne on surfaces at grazing angles
+	vec3 Offset = N * (1.0 - max(dot(N, L), 0.0)) * 0.05;
+	vec4 Clip = shadows.matrices[Layer] * vec4(Position + Offset, 1.0);
+	vec3 Coord = Clip.xyz / Clip.w * 0.5 + 0.5;
+	if (any(lessThan(Coord, vec3(0.0))) || any(greaterThan(Coord, vec3(1.0)))) {
+		return 1.0;
+	}
+
+	float Lit = 0.0;
+	for (int x = -1; x <= 1; x++) {
+		for (int y = -1; y <= 1; y++) {
+			Lit += texture(shadow_maps, vec4(Coord.xy + vec2(x, y) * Texel, Layer, Coord.z));
+		}
+	}
+	return Lit / 9.0;
+}
+
+float light_shadow(Light light, vec3 Position, vec3 N, vec3 L) {
+	int Layer = int(light.cone.z);
+	if (Layer < 0) {
+		return 1.0;
+	}
+	if (int(light.pos.w) == LIGHT_DIRECTIONAL) {
+		float ViewDepth = quat_mul(quat_inv(cam.rot), Position - cam.pos).y;
+		int Cascade = 0;
+		while (Cascade < CASCADES && ViewDepth > shadows.splits[Cascade]) {
+			Cascade++;
+		}
+		if (Cascade == CASCADES) {
+			return 1.0;
+		}
+		Layer += Cascade;
+	}
+	return shadow_pcf(Layer, Position, N, L);
+}
+
+#define PI 3.14159265359
+
+float distribution_ggx(float NdotH, float Roughness) {
+	float a2 = Roughness * Roughness * Roughness * Roughness;
+	float d = NdotH * NdotH * (a2 - 1.0) + 1.0;
+	return a2 / (PI * d * d);
+}
+
+float geometry_smith(float NdotV, float NdotL, float Roughness) {
+	float k = (Roughness + 1.0) * (Roughness + 1.0) / 8.0;
+	return NdotV / (NdotV * (1.0 - k) + k) * NdotL / (NdotL * (1.0 - k) + k);
+}
+
+vec3 fresnel_schlick(float Cos, vec3 F0) {
+	return F0 + (1.0 - F0) * pow(clamp(1.0 - Cos, 0.0, 1.0), 5.0);
+}
+
+// Cook-Torrance GGX for metallic-roughness materials
+vec3 pbr(vec3 Albedo, float Metallic, float Roughness, vec3 N, vec3 V, vec3 Position) {
+	vec3 F0 = mix(vec3(0.04), Albedo, Metallic);
+	float NdotV = max(dot(N, V), 1e-4);
+	vec3 color = lights.ambient.w > 0.0
+		? environment_light(Albedo, Metallic, Roughness, N, V, F0) * lights.ambient.w
+		: lights.ambient.rgb * Albedo;
+	for (int i = 0; i < min(lights.count.x, MAX_LIGHTS); i++) {
+		Light light = lights.lights[i];
+		vec4 incidence = light_incidence(light, Position);
+		vec3 L = incidence.xyz;
+		float NdotL = dot(N, L);
+		if (NdotL <= 0.0) {
+			continue;
+		}
+		vec3 H = normalize(L + V);
+		vec3 F = fresnel_schlick(max(dot(H, V), 0.0), F0);
+		float D = distribution_ggx(max(dot(N, H), 0.0), Roughness);
+		float G = geometry_smith(NdotV, NdotL, Roughness);
+		vec3 specular = D * G * F / (4.0 * NdotV * NdotL);
+		vec3 diffuse = (1.0 - F) * (1.0 - Metallic) * Albedo / PI;
+		float Shadow = light_shadow(light, Position, N, L);
+		color += light.color.rgb * incidence.w * Shadow * (diffuse + specular) * NdotL;
+	}
+	return color;
+}
@@ -0,0 +1,26 @@
+// Matches MaterialUniform in src/types/material.rs
+
+#define ALPHA_OPAQUE 0
+#define ALPHA_MASK 1
+#define ALPHA_BLEND 2
+
+struct Material {
+	vec4 base_color;
+	vec4 emissive;
+	float metallic;
+	float roughness;
+	float normal_scale;
+	float occlusion_strength;
+	float alpha_cutoff;
+	int alpha_mode;
+	int base_color_map;
+	int metallic_roughness_map;
+	int normal_map;
+	int emissive_map;
+	int occlusion_map;
+	int _pad;
+};
+
+layout (std430, binding = 2) readonly buffer Materials {
+	Material materials[];
+};
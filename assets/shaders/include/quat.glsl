@@ -0,0 +1,30 @@
+vec4 quat_inv(vec4 q) {
+	return vec4(q.xyz, -q.w);
+}
+
+vec3 quat_mul(vec4 quat, vec3 vec) {
+	return cross(quat.xyz, cross(quat.xyz, vec) + vec * quat.w) * 2.0 + vec;
+}
+
+vec4 quat_mul_quat(vec4 a, vec4 b) {
+	return vec4(a.w * b.xyz + b.w * a.xyz + cross(a.xyz, b.xyz), a.w * b.w - dot(a.xyz, b.xyz));
+}
+
+// Rotation part of a matrix without shear. Scale is normalized away, mirroring isn't supported.
+vec4 quat_from_mat3(mat3 m) {
+	m = mat3(normalize(m[0]), normalize(m[1]), normalize(m[2]));
+	float Trace = m[0][0] + m[1][1] + m[2][2];
+	if (Trace > 0.0) {
+		float s = sqrt(Trace + 1.0) * 2.0;
+		return vec4(m[1][2] - m[2][1], m[2][0] - m[0][2], m[0][1] - m[1][0], 0.25 * s * s) / s;
+	} else if (m[0][0] > m[1][1] && m[0][0] > m[2][2]) {
+		float s = sqrt(1.0 + m[0][0] - m[1][1] - m[2][2]) * 2.0;
+		return vec4(0.25 * s * s, m[1][0] + m[0][1], m[2][0] + m[0][2], m[1][2] - m[2][1]) / s;
+	} else if (m[1][1] > m[2][2]) {
+		float s = sqrt(1.0 + m[1][1] - m[0][0] - m[2][2]) * 2.0;
+		return vec4(m[1][0] + m[0][1], 0.25 * s * s, m[2][1] + m[1][2], m[2][0] - m[0][2]) / s;
+	} else {
+		float s = sqrt(1.0 + m[2][2] - m[0][0] - m[1][1]) * 2.0;
+		return vec4(m[2][0] + m[0][2], m[2][1] + m[1][2], 0.25 * s * s, m[0][1] - m[1][0]) / s;
+	}
+}
@@ -0,0 +1,6 @@
+// CASCADES and SHADOW_LAYERS are defined by the engine. The first CASCADES layers belong to the sun.
+
+layout (std140, binding = 3) uniform Shadows {
+	mat4 matrices[SHADOW_LAYERS];
+	vec4 splits;
+} shadows;
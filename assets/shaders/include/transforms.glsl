@@ -0,0 +1,5 @@
+// Matches RenderAllocs::transforms in src/systems/render/allocs.rs. Instances index it with their transform.
+
+layout (std430, binding = 5) readonly buffer Transforms {
+	mat4 transforms[];
+};
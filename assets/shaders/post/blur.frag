@@ -0,0 +1,21 @@
+#version 430 core
+
+#include "post.glsl"
+
+// 9 tap gaussian, sampled between texels so linear filtering does half the work
+const float Offsets[3] = float[](0.0, 1.3846153846, 3.2307692308);
+const float Weights[3] = float[](0.2270270270, 0.3162162162, 0.0702702703);
+
+void main() {
+#ifdef BLUR_HORIZONTAL
+	vec2 texel = vec2(1.0 / textureSize(Source, 0).x, 0.0);
+#else
+	vec2 texel = vec2(0.0, 1.0 / textureSize(Source, 0).y);
+#endif
+	vec3 color = texture(Source, UV).rgb * Weights[0];
+	for (int i = 1; i < 3; i++) {
+		color += texture(Source, UV + texel * Offsets[i]).rgb * Weights[i];
+		color += texture(Source, UV - texel * Offsets[i]).rgb * Weights[i];
+	}
+	Color = vec4(color, 1.0);
+}
@@ -0,0 +1,14 @@
+#version 430 core
+
+#include "post.glsl"
+
+// Keeps what's over the bloom threshold, with a soft knee so it doesn't pop in
+void main() {
+	vec3 color = texture(Source, UV).rgb;
+	float lum = luminance(color);
+	float knee = post.bloom_threshold * 0.5;
+	float soft = clamp(lum - post.bloom_threshold + knee, 0.0, 2.0 * knee);
+	soft = soft * soft / (4.0 * knee + 1e-4);
+	float weight = max(soft, lum - post.bloom_threshold) / max(lum, 1e-4);
+	Color = vec4(color * weight, 1.0);
+}
@@ -0,0 +1,10 @@
+#version 430 core
+
+#include "post.glsl"
+
+uniform sampler2D Bloom;
+
+void main() {
+	vec3 color = texture(Source, UV).rgb + texture(Bloom, UV).rgb * post.bloom_intensity;
+	Color = vec4(color, 1.0);
+}
@@ -0,0 +1,7 @@
+#version 430 core
+
+#include "post.glsl"
+
+void main() {
+	Color = vec4(texture(Source, UV).rgb * post.exposure, 1.0);
+}
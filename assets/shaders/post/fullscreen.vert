@@ -0,0 +1,9 @@
+#version 430 core
+
+out vec2 UV;
+
+// One triangle covering the screen, without any vertex buffers
+void main() {
+	UV = vec2((gl_VertexID << 1) & 2, gl_VertexID & 2);
+	gl_Position = vec4(UV * 2.0 - 1.0, 0.0, 1.0);
+}
@@ -0,0 +1,34 @@
+#version 430 core
+
+#include "post.glsl"
+
+const float EdgeMin = 1.0 / 16.0;
+const float EdgeScale = 1.0 / 8.0;
+const float SpanMax = 8.0;
+
+// FXAA 3.11 console version: blurs along the local edge direction. Reads luma from alpha.
+void main() {
+	vec2 texel = 1.0 / textureSize(Source, 0);
+	float nw = texture(Source, UV + vec2(-0.5, -0.5) * texel).a;
+	float ne = texture(Source, UV + vec2(0.5, -0.5) * texel).a;
+	float sw = texture(Source, UV + vec2(-0.5, 0.5) * texel).a;
+	float se = texture(Source, UV + vec2(0.5, 0.5) * texel).a;
+	vec4 center = texture(Source, UV);
+	float m = center.a;
+
+	float lo = min(m, min(min(nw, ne), min(sw, se)));
+	float hi = max(m, max(max(nw, ne), max(sw, se)));
+	if (hi - lo < max(EdgeMin, hi * EdgeScale)) {
+		Color = center;
+		return;
+	}
+
+	vec2 dir = vec2(-((nw + ne) - (sw + se)), (nw + sw) - (ne + se));
+	float reduce = max((nw + ne + sw + se) * 0.25 * EdgeScale, 1.0 / 128.0);
+	float scale = 1.0 / (min(abs(dir.x), abs(dir.y)) + reduce);
+	dir = clamp(dir * scale, -SpanMax, SpanMax) * texel;
+
+	vec4 a = (texture(Source, UV - dir / 6.0) + texture(Source, UV + dir / 6.0)) * 0.5;
+	vec4 b = a * 0.5 + (texture(Source, UV - dir * 0.5) + texture(Source, UV + dir * 0.5)) * 0.25;
+	Color = (b.a < lo || b.a > hi) ? a : b;
+}
@@ -0,0 +1,13 @@
+#version 430 core
+
+#include "post.glsl"
+
+uniform sampler3D Lut;
+
+void main() {
+	vec4 color = texture(Source, UV);
+	// Sample texel centers so the ends of the range map to the ends of the LUT
+	vec3 coord = color.rgb * ((post.lut_size - 1.0) / post.lut_size) + 0.5 / post.lut_size;
+	vec3 graded = texture(Lut, coord).rgb;
+	Color = vec4(graded, luminance(graded));
+}
@@ -0,0 +1,19 @@
+in vec2 UV;
+out vec4 Color;
+
+uniform sampler2D Source;
+
+layout (std140, binding = 4) uniform Post {
+	float exposure;
+	float bloom_threshold;
+	float bloom_intensity;
+	float lut_size;
+} post;
+
+float luminance(vec3 Color) {
+	return dot(Color, vec3(0.2126, 0.7152, 0.0722));
+}
+
+vec3 linear_to_srgb(vec3 Color) {
+	return mix(Color * 12.92, 1.055 * pow(Color, vec3(1.0 / 2.4)) - 0.055, step(0.0031308, Color));
+}
@@ -0,0 +1,28 @@
+#version 430 core
+
+#include "post.glsl"
+
+// Narkowicz's fit of the ACES filmic curve
+vec3 aces(vec3 x) {
+	return clamp((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14), 0.0, 1.0);
+}
+
+// Scales luminance so colors keep their hue
+vec3 reinhard(vec3 x) {
+	float lum = luminance(x);
+	return x / (1.0 + lum);
+}
+
+// Maps HDR to [0, 1] and encodes to sRGB, which ends the linear part of the chain. Grading LUTs and FXAA expect
+// encoded colors, and the window takes them as they are.
+void main() {
+	vec3 color = texture(Source, UV).rgb;
+#if defined(TONEMAP_ACES)
+	color = aces(color);
+#elif defined(TONEMAP_REINHARD)
+	color = reinhard(color);
+#endif
+	color = linear_to_srgb(clamp(color, 0.0, 1.0));
+	// Luma in alpha for FXAA
+	Color = vec4(color, luminance(color));
+}
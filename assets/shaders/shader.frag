@@ -0,0 +1,53 @@
+#version 430 core
+
+flat in int MaterialIndex;
+in vec3 WorldPosition;
+in vec4 WorldRotation;
+in vec4 UVMapping;
+
+out vec4 FragColor;
+
+uniform sampler2DArray tex;
+// Base color and emissive maps, decoded to linear by the sampler
+uniform sampler2DArray srgb_tex;
+
+#include "include/camera.glsl"
+#include "include/quat.glsl"
+#include "include/lights.glsl"
+#include "include/material.glsl"
+
+vec4 sample_map(int Layer, vec4 Default) {
+	return Layer < 0 ? Default : texture(tex, vec3(UVMapping.xy, Layer));
+}
+
+vec4 sample_color_map(int Layer, vec4 Default) {
+	return Layer < 0 ? Default : texture(srgb_tex, vec3(UVMapping.xy, Layer));
+}
+
+void main() {
+	Material mat = materials[MaterialIndex];
+
+	vec4 Albedo = mat.base_color * sample_color_map(mat.base_color_map, vec4(1.0));
+	if (mat.alpha_mode == ALPHA_MASK && Albedo.a < mat.alpha_cutoff) {
+		discard;
+	}
+
+	// glTF packs roughness in green and metalness in blue
+	vec4 MetallicRoughness = sample_map(mat.metallic_roughness_map, vec4(1.0));
+	float Metallic = clamp(mat.metallic * MetallicRoughness.b, 0.0, 1.0);
+	float Roughness = clamp(mat.roughness * MetallicRoughness.g, 0.04, 1.0);
+
+	vec3 TangentNormal = sample_map(mat.normal_map, vec4(0.5, 0.5, 1.0, 1.0)).xyz * 2.0 - 1.0;
+	TangentNormal.xy *= mat.normal_scale;
+	vec3 N = normalize(quat_mul(normalize(WorldRotation), TangentNormal));
+	if (!gl_FrontFacing) {
+		N = -N;
+	}
+	vec3 V = normalize(cam.pos - WorldPosition);
+
+	float Occlusion = mix(1.0, sample_map(mat.occlusion_map, vec4(1.0)).r, mat.occlusion_strength);
+	vec3 Emissive = mat.emissive.rgb * sample_color_map(mat.emissive_map, vec4(1.0)).rgb;
+
+	vec3 color = pbr(Albedo.rgb, Metallic, Roughness, N, V, WorldPosition) * Occlusion + Emissive;
+	FragColor = vec4(color, mat.alpha_mode == ALPHA_BLEND ? Albedo.a : 1.0);
+}
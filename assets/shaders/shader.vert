@@ -0,0 +1,28 @@
+#version 430 core
+
+layout (location = 0) in float VertexMaterialIndex;
+layout (location = 1) in float VertexTransformIndex;
+layout (location = 2) in vec3 VertexPosition;
+layout (location = 3) in vec4 VertexRotation;
+layout (location = 4) in vec4 VertexUVMapping;
+layout (location = 5) in vec4 VertexBoneIDs;
+layout (location = 6) in vec4 VertexBoneWeights;
+
+flat out int MaterialIndex;
+out vec3 WorldPosition;
+out vec4 WorldRotation;
+out vec4 UVMapping;
+
+#include "include/camera.glsl"
+#include "include/quat.glsl"
+#include "include/transforms.glsl"
+
+void main() {
+	mat4 Transform = transforms[int(VertexTransformIndex)];
+	MaterialIndex = int(VertexMaterialIndex);
+	WorldPosition = (Transform * vec4(VertexPosition, 1.0)).xyz;
+	WorldRotation = quat_mul_quat(quat_from_mat3(mat3(Transform)), VertexRotation);
+	UVMapping = VertexUVMapping;
+	vec3 EyePosition = quat_mul(quat_inv(cam.rot), WorldPosition - cam.pos);
+	gl_Position = perspective(cam.proj, vec3(EyePosition.xz, -EyePosition.y));
+}
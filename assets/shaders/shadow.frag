@@ -0,0 +1,24 @@
+#version 430 core
+
+flat in int MaterialIndex;
+in vec2 UV;
+
+// Base color maps, as in shader.frag
+uniform sampler2DArray srgb_tex;
+
+#include "include/material.glsl"
+
+// Cutouts cast the same holes they show. Only depth is written.
+void main() {
+	Material mat = materials[MaterialIndex];
+	if (mat.alpha_mode != ALPHA_MASK) {
+		return;
+	}
+	float Alpha = mat.base_color.a;
+	if (mat.base_color_map >= 0) {
+		Alpha *= texture(srgb_tex, vec3(UV, mat.base_color_map)).a;
+	}
+	if (Alpha < mat.alpha_cutoff) {
+		discard;
+	}
+}
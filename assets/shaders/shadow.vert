@@ -0,0 +1,21 @@
+#version 430 core
+
+layout (location = 0) in float VertexMaterialIndex;
+layout (location = 1) in float VertexTransformIndex;
+layout (location = 2) in vec3 VertexPosition;
+layout (location = 4) in vec4 VertexUVMapping;
+
+flat out int MaterialIndex;
+out vec2 UV;
+
+uniform int ShadowLayer;
+
+#include "include/shadows.glsl"
+#include "include/transforms.glsl"
+
+void main() {
+	mat4 Transform = transforms[int(VertexTransformIndex)];
+	MaterialIndex = int(VertexMaterialIndex);
+	UV = VertexUVMapping.xy;
+	gl_Position = shadows.matrices[ShadowLayer] * Transform * vec4(VertexPosition, 1.0);
+}
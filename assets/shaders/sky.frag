@@ -0,0 +1,22 @@
+#version 430 core
+
+in vec2 ScreenPosition;
+
+out vec4 FragColor;
+
+uniform samplerCube environment;
+
+// Only the ambient of include/lights.glsl, whose w is the sky's intensity
+layout (std140, binding = 1) uniform Lights {
+	vec4 ambient;
+} lights;
+
+#include "include/camera.glsl"
+#include "include/quat.glsl"
+#include "include/cubemap.glsl"
+
+// Only the camera's rotation matters, so the sky stays infinitely far away
+void main() {
+	vec3 dir = quat_mul(cam.rot, vec3(ScreenPosition.x / cam.proj.x, 1.0, ScreenPosition.y / cam.proj.y));
+	FragColor = vec4(textureLod(environment, world_to_cube(normalize(dir)), 0.0).rgb * lights.ambient.w, 1.0);
+}
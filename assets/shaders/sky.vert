@@ -0,0 +1,9 @@
+#version 430 core
+
+out vec2 ScreenPosition;
+
+// DEPTH is the far plane in NDC, 0 with reversed-Z and 1 otherwise
+void main() {
+	ScreenPosition = vec2((gl_VertexID << 1) & 2, gl_VertexID & 2) * 2.0 - 1.0;
+	gl_Position = vec4(ScreenPosition, DEPTH, 1.0);
+}
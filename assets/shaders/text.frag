@@ -0,0 +1,12 @@
+#version 430 core
+
+in vec3 GlyphUvw;
+in vec4 GlyphColor;
+
+out vec4 FragColor;
+
+uniform sampler2DArray tex;
+
+void main() {
+	FragColor = vec4(GlyphColor.rgb, GlyphColor.a * texture(tex, GlyphUvw).a);
+}
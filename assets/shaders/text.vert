@@ -0,0 +1,25 @@
+#version 430 core
+
+layout (location = 0) in vec3 VertexPosition;
+layout (location = 1) in vec3 VertexUvw;
+layout (location = 2) in vec4 VertexColor;
+
+out vec3 GlyphUvw;
+out vec4 GlyphColor;
+
+#ifdef WORLD
+#include "include/camera.glsl"
+#include "include/quat.glsl"
+#endif
+
+void main() {
+	GlyphUvw = VertexUvw;
+	GlyphColor = VertexColor;
+#ifdef WORLD
+	vec3 EyePosition = quat_mul(quat_inv(cam.rot), VertexPosition - cam.pos);
+	gl_Position = perspective(cam.proj, vec3(EyePosition.xz, -EyePosition.y));
+#else
+	// Already in clip space
+	gl_Position = vec4(VertexPosition, 1.0);
+#endif
+}
@@ -0,0 +1,11 @@
+#version 430 core
+
+layout (location = 0) in vec3 VertexPosition;
+layout (location = 1) in vec4 VertexColor;
+
+out vec4 LineColor;
+
+void main() {
+	LineColor = VertexColor;
+	gl_Position = vec4(VertexPosition, 1.0);
+}
@@ -101,6 +101,42 @@ impl Importer {
 		}
 	}
 
+	/// Load a scene from a memory buffer.
+	///
+	/// `hint` is the file extension of the data, such as `"dae"`, and is used to pick an importer.
+	///
+	/// If the call succeeds, return value is `Ok`, containing the loaded `Scene` structure.
+	/// If the call fails, return value is `Err`, containing the error string returned from
+	/// the Assimp library.
+	pub fn read_memory<'a>(&self, data: &[u8], hint: &str) -> Result<Scene<'a>, &str> {
+		let hint = CString::new(hint).unwrap();
+		let raw_scene = unsafe {
+			aiImportFileFromMemoryWithProperties(
+				data.as_ptr() as *const _,
+				data.len() as u32,
+				self.flags,
+				hint.as_ptr(),
+				self.property_store,
+			)
+		};
+		if !raw_scene.is_null() {
+			Ok(Scene::from_raw(raw_scene))
+		} else {
+			let error_str = unsafe { aiGetErrorString() };
+			if error_str.is_null() {
+				Err("Unknown error")
+			} else {
+				unsafe {
+					let cstr = CStr::from_ptr(error_str);
+					match str::from_utf8(cstr.to_bytes()) {
+						Ok(s) => Err(s),
+						Err(_) => Err("Unknown error"),
+					}
+				}
+			}
+		}
+	}
+
 	/// Apply post-processing to an already-imported scene.
 	///
 	/// This performs all enabled post-processing steps on an already imported scene. The main
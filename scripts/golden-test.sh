@@ -0,0 +1,30 @@
+#!/bin/sh
+# Renders the default scene headlessly and compares it with tests/golden/default.png, failing if more than a few
+# pixels differ by over $TOLERANCE in any channel. `--bless` saves the current render as the golden image instead.
+# The golden image isn't checked in since it depends on the GL driver, so the first run blesses it.
+#
+# Without a display this runs under xvfb-run with llvmpipe, so it works on machines without a GPU.
+set -e
+cd "$(dirname "$0")/.."
+
+GOLDEN=tests/golden/default.png
+TOLERANCE=${TOLERANCE:-8}
+
+if [ ! -f "$GOLDEN" ] && [ "$1" != "--bless" ]; then
+	echo "$GOLDEN doesn't exist, saving this render as the golden image" >&2
+	set -- --bless
+fi
+
+if [ "$1" = "--bless" ]; then
+	mkdir -p "$(dirname "$GOLDEN")"
+	set -- --capture "$GOLDEN"
+else
+	set -- --compare "$GOLDEN" --tolerance "$TOLERANCE"
+fi
+
+cargo build --release
+if [ -z "$DISPLAY" ] && [ -z "$WAYLAND_DISPLAY" ]; then
+	export LIBGL_ALWAYS_SOFTWARE=1
+	exec xvfb-run -a -s "-screen 0 1280x720x24" target/release/immoral-engine --headless "$@"
+fi
+exec target/release/immoral-engine --headless "$@"
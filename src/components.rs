@@ -1,2 +1,4 @@
+pub mod light;
 pub mod model;
 pub mod player_controller;
+pub mod skybox;
@@ -0,0 +1,91 @@
+use nalgebra::{Vector3, Vector4};
+
+/// Most lights the shaders handle, passed to them as a define. Extra lights are ignored.
+pub const MAX_LIGHTS: usize = 32;
+
+pub enum LightKind {
+	/// Lights everything from `Light::dir`
+	Directional,
+	/// Lights in all directions from `Light::pos`
+	Point,
+	/// Lights a cone around `Light::dir` from `Light::pos`. Angles are radians from the center, fading out between
+	/// `inner` and `outer`.
+	Spot { inner: f32, outer: f32 },
+}
+
+pub struct Light {
+	pub kind: LightKind,
+	pub color: Vector3<f32>,
+	pub intensity: f32,
+	pub pos: Vector3<f32>,
+	pub dir: Vector3<f32>,
+	/// Distance at which point and spot lights fade to nothing
+	pub range: f32,
+	/// Directional and spot lights only. Only the first directional light gets shadows.
+	pub shadows: bool,
+}
+impl Light {
+	pub fn directional(dir: Vector3<f32>, color: Vector3<f32>, intensity: f32) -> Self {
+		let dir = dir.normalize();
+		Self { kind: LightKind::Directional, color, intensity, pos: Vector3::zeros(), dir, range: 0.0, shadows: true }
+	}
+
+	pub fn point(pos: Vector3<f32>, color: Vector3<f32>, intensity: f32, range: f32) -> Self {
+		Self { kind: LightKind::Point, color, intensity, pos, dir: Vector3::z(), range, shadows: false }
+	}
+
+	pub fn spot(
+		pos: Vector3<f32>,
+		dir: Vector3<f32>,
+		inner: f32,
+		outer: f32,
+		color: Vector3<f32>,
+		intensity: f32,
+		range: f32,
+	) -> Self {
+		let kind = LightKind::Spot { inner, outer };
+		Self { kind, color, intensity, pos, dir: dir.normalize(), range, shadows: true }
+	}
+
+	/// `shadow` is the first shadow map layer assigned to the light.
+	pub fn uniform(&self, shadow: Option<usize>) -> LightUniform {
+		let (kind, cone) = match self.kind {
+			LightKind::Directional => (0.0, [1.0, 1.0]),
+			LightKind::Point => (1.0, [-1.0, -1.0]),
+			LightKind::Spot { inner, outer } => (2.0, [inner.cos(), outer.cos()]),
+		};
+		LightUniform {
+			pos: extend(&self.pos, kind),
+			dir: extend(&self.dir, self.range),
+			color: extend(&(self.color * self.intensity), 0.0),
+			cone: Vector4::new(cone[0], cone[1], shadow.map_or(-1.0, |layer| layer as f32), 0.0),
+		}
+	}
+}
+
+fn extend(v: &Vector3<f32>, w: f32) -> Vector4<f32> {
+	Vector4::new(v.x, v.y, v.z, w)
+}
+
+/// std140 layout of one light in `LightsUniform`
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+pub struct LightUniform {
+	/// w is 0 for directional, 1 for point and 2 for spot lights
+	pos: Vector4<f32>,
+	/// w is the range
+	dir: Vector4<f32>,
+	/// Premultiplied by intensity
+	color: Vector4<f32>,
+	/// Cosines of the inner and outer spot angles, then the shadow map layer or -1
+	cone: Vector4<f32>,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+pub struct LightsUniform {
+	pub ambient: Vector4<f32>,
+	/// Only x is used
+	pub count: Vector4<i32>,
+	pub lights: [LightUniform; MAX_LIGHTS],
+}
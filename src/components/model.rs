@@ -1,92 +1,349 @@
-use crate::systems::render::allocs::RenderAllocs;
-use assimp::{Importer, Mesh as AssimpMesh, Scene, Vector3D};
-use assimp_sys::{aiGetMaterialTexture, AiString, AiTextureType};
+use crate::{
+	systems::render::{
+		allocs::{DrawAllocation, MaterialAllocation, RenderAllocs, TransformAllocation, TEX_SIZE},
+		cull::DrawInput,
+	},
+	types::{
+		assets::Assets,
+		frustum::Bounds,
+		material::{AlphaMode, Material},
+		simplify::simplify,
+	},
+};
+use assimp::{Importer, Mesh as AssimpMesh, Node, Vector3D};
 use glrs::{
 	alloc::Allocation,
 	buffer::ImmutableBuffer,
 	gl::{self},
 	implement_vertex,
 };
-use nalgebra::{UnitQuaternion, Vector3, Vector4};
-use std::{iter::repeat, path::Path, ptr, rc::Rc, slice, str, sync::atomic::Ordering};
+use image::{imageops, imageops::FilterType, RgbaImage};
+use nalgebra::{Matrix3, Matrix4, Rotation3, UnitQuaternion, Vector3, Vector4};
+use std::{
+	f32::consts::PI,
+	iter::repeat,
+	mem::size_of,
+	path::{Path, PathBuf},
+	rc::Rc,
+};
+
+/// Screen height, as a fraction of the screen, below which the second LOD is used. Each LOD after that takes over at
+/// half the size of the one before.
+const LOD_SCREEN_SIZE: f32 = 0.5;
+/// LODs made for models that don't come with their own, including the original
+const GENERATED_LODS: usize = 4;
+/// How far past a threshold the screen size has to go before the LOD changes, so models at the boundary don't flicker
+/// between two LODs
+const LOD_HYSTERESIS: f32 = 0.1;
 
 pub struct Model {
+	/// Asset name
+	pub path: String,
+	/// Every LOD's meshes. Only the ones matching `lod` are drawn.
 	pub meshes: Vec<Mesh>,
+	pub materials: Vec<MaterialAllocation>,
+	/// false until the loader has uploaded the meshes. Renders as a placeholder until then.
+	pub loaded: bool,
+	/// Why the last load failed. A model that failed to reload keeps its old meshes, one that never loaded draws
+	/// nothing.
+	pub failed: Option<String>,
+	/// Files on disk for the model and every texture it uses
+	pub files: Vec<PathBuf>,
+	/// LOD drawn last frame
+	pub lod: usize,
+	/// Around the first LOD in model space, for picking LODs by screen size
+	pub bounds: Bounds,
+	/// Model to world. Set with `set_transform` so the meshes follow.
+	transform: Matrix4<f32>,
 }
 impl Model {
-	pub fn from_file(alloc: &Rc<RenderAllocs>, file: &str) -> Self {
+	pub fn pending(assets: &Assets, path: &str) -> Self {
+		let files = assets.path(path).into_iter().collect();
+		let mut model = Self::new(path, vec![], vec![], files);
+		model.loaded = false;
+		model
+	}
+
+	pub fn new(path: &str, meshes: Vec<Mesh>, materials: Vec<MaterialAllocation>, files: Vec<PathBuf>) -> Self {
+		let corners: Vec<_> = meshes
+			.iter()
+			.filter(|mesh| mesh.lod == 0)
+			.flat_map(|mesh| vec![mesh.bounds.min, mesh.bounds.max])
+			.collect();
+		let bounds = Bounds::from_points(corners.iter());
+		let transform = Matrix4::identity();
+		let model = Self {
+			path: path.to_owned(),
+			meshes,
+			materials,
+			loaded: true,
+			failed: None,
+			files,
+			lod: 0,
+			bounds,
+			transform,
+		};
+		model.enable_draws(true);
+		model
+	}
+
+	pub fn from_data(alloc: &Rc<RenderAllocs>, path: &str, data: ModelData) -> Self {
+		for tex in data.textures {
+			if let Err(err) = upload_texture(alloc, tex) {
+				eprintln!("failed to upload texture: {}", err);
+			}
+		}
+		let materials: Vec<_> = data.materials.iter().map(|mat| upload_material(alloc, mat)).collect();
+		let meshes = data.meshes.iter().map(|mesh| Mesh::upload(alloc, mesh, &materials)).collect();
+		Self::new(path, meshes, materials, data.files)
+	}
+
+	/// Waiting for its first load, so the placeholder is drawn in its place.
+	pub fn pending_load(&self) -> bool {
+		!self.loaded && self.failed.is_none()
+	}
+
+	pub fn lod_count(&self) -> usize {
+		self.meshes.iter().map(|mesh| mesh.lod + 1).max().unwrap_or(1)
+	}
+
+	/// Picks the LOD for a model covering `screen_size` of the screen height. Levels the model has no meshes for
+	/// fall back to the nearest one it has.
+	pub fn select_lod(&mut self, screen_size: f32) {
+		let lod = select_lod(self.lod, screen_size, self.lod_count());
+		let lod = nearest_lod(lod, self.meshes.iter().map(|mesh| mesh.lod));
+		if lod != self.lod {
+			self.lod = lod;
+			self.enable_draws(true);
+		}
+	}
+
+	/// Switches on the GPU culling draws of the meshes in the current LOD, or none of them if `visible` is false.
+	/// Blended meshes are left off, they're sorted and drawn from the CPU.
+	pub fn enable_draws(&self, visible: bool) {
+		for mesh in &self.meshes {
+			mesh.enable_draw(visible && mesh.lod == self.lod && !mesh.blend);
+		}
+	}
+
+	pub fn transform(&self) -> &Matrix4<f32> {
+		&self.transform
+	}
+
+	pub fn set_transform(&mut self, transform: Matrix4<f32>) {
+		self.transform = transform;
+		for mesh in &self.meshes {
+			mesh.transform.set(transform);
+		}
+	}
+
+	/// `bounds` in world space
+	pub fn world_bounds(&self) -> Bounds {
+		self.bounds.transformed(&self.transform)
+	}
+
+	/// Unit cube drawn in place of models that are still loading.
+	pub fn placeholder(alloc: &Rc<RenderAllocs>) -> Self {
+		let material = Material { base_color: Vector4::new(1.0, 0.1, 0.1, 1.0), ..Material::default() };
+		let data =
+			ModelData { meshes: vec![MeshData::cube(0.5)], materials: vec![material], textures: vec![], files: vec![] };
+		Self::from_data(alloc, "", data)
+	}
+}
+
+/// CPU side of a model, produced on a loader thread and uploaded to the GPU on the main thread.
+pub struct ModelData {
+	pub meshes: Vec<MeshData>,
+	pub materials: Vec<Material>,
+	/// Every texture the materials use
+	pub textures: Vec<TextureData>,
+	/// Files on disk the model was read from
+	pub files: Vec<PathBuf>,
+}
+impl ModelData {
+	pub fn load(assets: &Assets, name: &str) -> Result<Self, String> {
 		let mut importer = Importer::new();
 		importer.triangulate(true);
-		let scene = importer.read_file(file).unwrap();
-
-		let dir = Path::new(file).parent().unwrap();
-		let texidxs = get_textures(&dir, &scene, alloc);
-
-		let meshes = scene.mesh_iter().map(|mesh| Mesh::from_assimp(alloc, &mesh, &texidxs)).collect();
-
-		Self { meshes }
-	}
-}
-
-fn get_textures(file: &Path, scene: &Scene, alloc: &RenderAllocs) -> Vec<f32> {
-	scene
-		.material_iter()
-		.map(|m| unsafe {
-			let mut path = AiString::default();
-			aiGetMaterialTexture(
-				&*m,
-				AiTextureType::Diffuse,
-				0,
-				&mut path,
-				ptr::null(),
-				ptr::null_mut(),
-				ptr::null_mut(),
-				ptr::null_mut(),
-				ptr::null_mut(),
-				ptr::null_mut(),
-			);
-			let path = str::from_utf8_unchecked(slice::from_raw_parts(path.data.as_ptr(), path.length)).to_owned();
-
-			if path.len() > 0 {
-				let path = file.join(path);
-				let img = image::open(path).unwrap().to_rgba();
-				let (w, h) = img.dimensions();
-				let buf = ImmutableBuffer::from_slice(alloc.ctx(), &img.into_raw());
-				let idx = alloc.tex_free.fetch_add(1, Ordering::Relaxed);
-				alloc.tex.subimage_u8([0, 0, idx].into(), [w as _, h as _, 1].into(), gl::RGBA, &buf);
-				idx as f32
-			} else {
-				-1.0
+		// Flat normals for meshes that have none
+		importer.generate_normals(|args| args.enable = true);
+		importer.calc_tangent_space(|args| args.enable = true);
+		// Files on disk go through assimp's own IO so it finds the .mtl and .bin files next to them
+		let scene = match assets.path(name) {
+			Some(path) => importer.read_file(&path.to_string_lossy()),
+			None => {
+				let data = assets.read(name)?;
+				let ext = Path::new(name).extension().and_then(|ext| ext.to_str()).unwrap_or("");
+				importer.read_memory(&data, ext)
+			},
+		}
+		.map_err(|e| format!("{}: {}", name, e))?;
+
+		let mut materials: Vec<_> = scene.material_iter().map(|mat| Material::from_assimp(&mat, name)).collect();
+
+		let mut textures: Vec<TextureData> = vec![];
+		for (map, srgb) in materials.iter().flat_map(Material::maps) {
+			if textures.iter().any(|tex| &tex.name == map && tex.srgb == srgb) {
+				continue;
+			}
+			// Maps used both as color and data are uploaded to both arrays
+			let img = match textures.iter().find(|tex| &tex.name == map) {
+				Some(tex) => tex.img.clone(),
+				None => load_texture(assets, map)?,
+			};
+			textures.push(TextureData { name: map.clone(), img, srgb });
+		}
+		for mat in &mut materials {
+			let map = mat.base_color_map.as_ref().and_then(|map| textures.iter().find(|tex| &tex.name == map));
+			if let Some(tex) = map {
+				mat.detect_alpha(&tex.img);
 			}
-		})
-		.collect()
+		}
+
+		let mut lods = vec![0; scene.num_meshes() as usize];
+		node_lods(&scene.root_node(), 0, &mut lods);
+		let mut meshes: Vec<_> =
+			scene.mesh_iter().zip(lods).map(|(mesh, lod)| MeshData::from_assimp(&mesh, lod)).collect();
+		if meshes.iter().all(|mesh| mesh.lod == 0) {
+			meshes = generate_lods(meshes);
+		}
+		for mesh in &mut meshes {
+			mesh.blend = materials.get(mesh.material).map_or(false, |mat| mat.alpha_mode == AlphaMode::Blend);
+		}
+
+		let names = Some(name).into_iter().chain(textures.iter().map(|tex| tex.name.as_str()));
+		let files = names.filter_map(|name| assets.path(name)).collect();
+
+		Ok(Self { meshes, materials, textures, files })
+	}
 }
 
-pub struct Mesh {
-	pub buf: Allocation<Vertex>,
-	indices: Allocation<u16>,
-	pub instance: Allocation<Instance>,
+/// LOD for a model with `count` LODs covering `screen_size` of the screen height, given the one it used last frame.
+pub fn select_lod(current: usize, screen_size: f32, count: usize) -> usize {
+	// Threshold between `lod` and the one after it
+	let threshold = |lod: usize| LOD_SCREEN_SIZE * 0.5f32.powi(lod as i32);
+	let target = (0..count - 1).filter(|&lod| screen_size < threshold(lod)).count();
+	let hysteresis = |lod: usize| (threshold(lod) * (1.0 - LOD_HYSTERESIS), threshold(lod) * (1.0 + LOD_HYSTERESIS));
+	let lod = if target > current && screen_size > hysteresis(target - 1).0 {
+		target - 1
+	} else if target < current && screen_size < hysteresis(target).1 {
+		target + 1
+	} else {
+		target
+	};
+	lod.min(count - 1)
 }
-impl Mesh {
-	fn from_assimp(alloc: &Rc<RenderAllocs>, mesh: &AssimpMesh, texidxs: &[f32]) -> Self {
+
+/// The LOD in `lods` closest to `lod`, preferring the more detailed one on a tie.
+pub fn nearest_lod(lod: usize, lods: impl Iterator<Item = usize>) -> usize {
+	lods.min_by_key(|&other| (if other > lod { other - lod } else { lod - other }, other)).unwrap_or(lod)
+}
+
+/// Marks meshes under nodes named like `Body_LOD2` with that LOD. Children inherit their parent's LOD.
+fn node_lods(node: &Node, parent: usize, lods: &mut [usize]) {
+	let name = node.name().to_ascii_lowercase();
+	let lod = name.rfind("_lod").and_then(|i| name[i + 4..].parse().ok()).unwrap_or(parent);
+	for &mesh in node.meshes() {
+		lods[mesh as usize] = lod;
+	}
+	for child in node.child_iter() {
+		node_lods(&child, lod, lods);
+	}
+}
+
+/// Adds simplified copies of every mesh for each LOD after the first. Meshes too small to simplify further are
+/// copied as they are, so every LOD has the whole model.
+fn generate_lods(meshes: Vec<MeshData>) -> Vec<MeshData> {
+	let mut lods = vec![meshes];
+	for lod in 1..GENERATED_LODS {
+		let next = lods[lod - 1].iter().map(|mesh| mesh.simplified(0.5, lod)).collect();
+		lods.push(next);
+	}
+	lods.into_iter().flatten().collect()
+}
+
+/// Decodes the texture asset `name`, scaled down to fit in a texture array layer if it's bigger.
+fn load_texture(assets: &Assets, name: &str) -> Result<RgbaImage, String> {
+	let img = image::load_from_memory(&assets.read(name)?).map_err(|e| format!("{}: {}", name, e))?.to_rgba();
+	let (w, h) = img.dimensions();
+	if w <= TEX_SIZE && h <= TEX_SIZE {
+		return Ok(img);
+	}
+	eprintln!("{}: {}x{} is bigger than the {}x{} texture layers, scaling it down", name, w, h, TEX_SIZE, TEX_SIZE);
+	Ok(imageops::resize(&img, w.min(TEX_SIZE), h.min(TEX_SIZE), FilterType::Triangle))
+}
+
+/// A material's texture, decoded on a loader thread.
+pub struct TextureData {
+	/// Asset name
+	pub name: String,
+	pub img: RgbaImage,
+	/// Base color or emissive colors, uploaded to the sRGB array
+	pub srgb: bool,
+}
+impl TextureData {
+	/// Number of bytes this texture uploads.
+	pub fn size(&self) -> usize {
+		self.img.as_raw().len()
+	}
+}
+
+/// Uploads into the layer already used by the same file, if there is one, so reloaded textures replace the old ones.
+pub fn upload_texture(alloc: &RenderAllocs, TextureData { name, img, srgb }: TextureData) -> Result<(), String> {
+	if srgb {
+		alloc.srgb_tex.upload(alloc.srgb_layer(name)?, &img);
+		return Ok(());
+	}
+	let layer = alloc.data_layer(name)?;
+	let (w, h) = img.dimensions();
+	let buf = ImmutableBuffer::from_slice(alloc.ctx(), &img.into_raw());
+	alloc.tex.subimage_u8([0, 0, layer].into(), [w as _, h as _, 1].into(), gl::RGBA, &buf);
+	Ok(())
+}
+
+/// Textures must be uploaded first.
+pub fn upload_material(alloc: &Rc<RenderAllocs>, material: &Material) -> MaterialAllocation {
+	alloc.alloc_material(material.uniform(|name, srgb| alloc.tex_layer(name, srgb)))
+}
+
+pub struct MeshData {
+	vertices: Vec<Vertex>,
+	indices: Vec<u16>,
+	material: usize,
+	bounds: Bounds,
+	lod: usize,
+	/// Uses an alpha blended material
+	blend: bool,
+}
+impl MeshData {
+	fn from_assimp(mesh: &AssimpMesh, lod: usize) -> Self {
 		let texcoords = if mesh.get_num_uv_channels() > 1 {
 			Box::new(mesh.texture_coords_iter(1)) as Box<dyn Iterator<Item = Vector3D>>
 		} else {
 			Box::new(repeat(Vector3D::new(0.0, 0.0, 0.0)))
 		};
 
-		let vertices: Vec<_> = mesh
-			.vertex_iter()
-			.zip(mesh.normal_iter())
+		let has_tangents = !mesh.tangents.is_null();
+		// Only points and lines are left without normals after generating them
+		let has_normals = mesh.has_normals();
+		if !has_normals {
+			let name: &str = mesh.name.as_ref();
+			eprintln!("mesh {:?} has no normals, using +Z", name);
+		}
+		let vertices = (0..mesh.num_vertices())
 			.zip(mesh.texture_coords_iter(0))
 			.zip(texcoords)
-			.map(|(((v, n), u), l)| Vertex {
-				pos: [v.x, v.y, v.z].into(),
-				rot: UnitQuaternion::rotation_between(&[0.0, 0.0, 1.0].into(), &[n.x, n.y, n.z].into()).unwrap(),
-				uvw: [u.x, u.y, l.x, l.y].into(),
+			.map(|((i, u), l)| {
+				let v = mesh.get_vertex(i).unwrap();
+				let n = if has_normals { vector(mesh.get_normal(i).unwrap()) } else { Vector3::z() };
+				let rot = if has_tangents && has_normals {
+					tangent_rotation(&vector(mesh.get_tangent(i).unwrap()), &n)
+				} else {
+					normal_rotation(&n)
+				};
+				Vertex { pos: [v.x, v.y, v.z].into(), rot, uvw: [u.x, u.y, l.x, l.y].into() }
 			})
 			.collect();
-		let indices: Vec<_> = mesh
+		let indices = mesh
 			.face_iter()
 			.map(|f| {
 				assert_eq!(f.num_indices, 3);
@@ -95,26 +352,122 @@ impl Mesh {
 			.flatten()
 			.collect();
 
-		let buf = alloc.alloc_verts(&vertices);
-		let indices = alloc.alloc_indices(&indices);
-		let instance = alloc.alloc_instance(&Instance { tex: texidxs[mesh.material_index as usize] });
+		Self::new(vertices, indices, mesh.material_index as _, lod)
+	}
+
+	fn cube(half: f32) -> Self {
+		let mut vertices = vec![];
+		let mut indices = vec![];
+		for axis in 0..3 {
+			for &sign in &[1.0, -1.0] {
+				let mut n = Vector3::zeros();
+				n[axis] = sign;
+				let rot = normal_rotation(&n);
+				let base = vertices.len() as u16;
+				for &(u, v) in &[(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+					let pos = rot * Vector3::new(u * half, v * half, half);
+					vertices.push(Vertex { pos, rot, uvw: Vector4::new(u * 0.5 + 0.5, v * 0.5 + 0.5, 0.0, 0.0) });
+				}
+				indices.extend([0, 1, 2, 0, 2, 3].iter().map(|i| base + i));
+			}
+		}
+		Self::new(vertices, indices, 0, 0)
+	}
 
-		Self { buf, indices, instance }
+	fn new(vertices: Vec<Vertex>, indices: Vec<u16>, material: usize, lod: usize) -> Self {
+		let bounds = Bounds::from_points(vertices.iter().map(|v| &v.pos));
+		Self { vertices, indices, material, bounds, lod, blend: false }
+	}
+
+	/// Copy with about `ratio` of the triangles, for `lod`.
+	fn simplified(&self, ratio: f32, lod: usize) -> Self {
+		let positions: Vec<_> = self.vertices.iter().map(|v| v.pos).collect();
+		let simple = simplify(&positions, &self.indices, ratio);
+		let vertices = simple.vertices.iter().map(|&i| self.vertices[i]).collect();
+		Self::new(vertices, simple.indices, self.material, lod)
+	}
+
+	/// Number of bytes this mesh uploads.
+	pub fn size(&self) -> usize {
+		self.vertices.len() * size_of::<Vertex>() + self.indices.len() * size_of::<u16>()
+	}
+}
+
+fn vector(v: Vector3D) -> Vector3<f32> {
+	Vector3::new(v.x, v.y, v.z)
+}
+
+/// Rotation that takes +X to the tangent and +Z to the normal. The bitangent is rebuilt from the two, a rotation
+/// can't represent mirrored UVs.
+fn tangent_rotation(t: &Vector3<f32>, n: &Vector3<f32>) -> UnitQuaternion<f32> {
+	let n = n.normalize();
+	let t = match (t - n * n.dot(t)).try_normalize(1e-6) {
+		Some(t) => t,
+		None => return normal_rotation(&n),
+	};
+	let basis = Matrix3::from_columns(&[t, n.cross(&t), n]);
+	UnitQuaternion::from_rotation_matrix(&Rotation3::from_matrix_unchecked(basis))
+}
+
+/// Rotation that takes +Z to `n`. `rotation_between` has no answer for exactly opposite vectors.
+fn normal_rotation(n: &Vector3<f32>) -> UnitQuaternion<f32> {
+	UnitQuaternion::rotation_between(&Vector3::z(), n)
+		.unwrap_or_else(|| UnitQuaternion::from_axis_angle(&Vector3::x_axis(), PI))
+}
+
+pub struct Mesh {
+	pub buf: Allocation<Vertex>,
+	indices: Allocation<u16>,
+	pub instance: Allocation<Instance>,
+	/// Model space
+	pub bounds: Bounds,
+	pub lod: usize,
+	/// Drawn sorted in the transparent pass
+	pub blend: bool,
+	transform: TransformAllocation,
+	/// This mesh in the GPU culling pass's draw list
+	draw: DrawAllocation,
+}
+impl Mesh {
+	pub fn upload(alloc: &Rc<RenderAllocs>, mesh: &MeshData, materials: &[MaterialAllocation]) -> Self {
+		let buf = alloc.alloc_verts(&mesh.vertices);
+		let indices = alloc.alloc_indices(&mesh.indices);
+		let transform = alloc.alloc_transform(Matrix4::identity());
+		let material = materials[mesh.material].idx() as _;
+		let instance = alloc.alloc_instance(&Instance { material, transform: transform.idx() as _ });
+		let draw = alloc.alloc_draw(DrawInput::new(&buf, &indices, &instance, &mesh.bounds, transform.idx()));
+
+		Self { buf, indices, instance, bounds: mesh.bounds, lod: mesh.lod, blend: mesh.blend, transform, draw }
 	}
 
 	pub fn indices(&self) -> &Allocation<u16> {
 		&self.indices
 	}
+
+	/// `bounds` in world space
+	pub fn world_bounds(&self) -> Bounds {
+		self.bounds.transformed(&self.transform.get())
+	}
+
+	fn enable_draw(&self, enabled: bool) {
+		let mut draw = self.draw.get();
+		if draw.enabled() != enabled {
+			draw.set_enabled(enabled);
+			self.draw.set(draw);
+		}
+	}
 }
 
 #[allow(unused)]
 #[derive(Clone, Copy, Default)]
 #[repr(C)]
 pub struct Instance {
-	/// -1 if no texture
-	tex: f32,
+	/// Index into the materials storage buffer
+	material: f32,
+	/// Index into the transforms storage buffer
+	transform: f32,
 }
-implement_vertex!(Instance, tex);
+implement_vertex!(Instance, material, transform);
 
 #[allow(unused)]
 #[derive(Clone, Copy, Default)]
@@ -137,3 +490,47 @@ pub struct VertexRigged {
 	bone_wt: Vector4<u8>,
 }
 implement_vertex!(VertexRigged, pos, rot, uvw, bone_id, bone_wt);
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn lods_follow_screen_size() {
+		assert_eq!(select_lod(0, 1.0, 4), 0);
+		assert_eq!(select_lod(0, 0.3, 4), 1);
+		assert_eq!(select_lod(0, 0.01, 4), 3);
+		assert_eq!(select_lod(3, 1.0, 4), 0);
+		assert_eq!(select_lod(0, 0.01, 1), 0);
+	}
+
+	#[test]
+	fn lods_change_past_the_hysteresis() {
+		// Threshold between LOD 0 and 1 is 0.5
+		assert_eq!(select_lod(0, 0.48, 4), 0);
+		assert_eq!(select_lod(0, 0.44, 4), 1);
+		assert_eq!(select_lod(1, 0.52, 4), 1);
+		assert_eq!(select_lod(1, 0.56, 4), 0);
+	}
+
+	#[test]
+	fn every_lod_is_reachable() {
+		for count in 1..10 {
+			assert_eq!(select_lod(0, 0.0, count), count - 1);
+		}
+		// Fewer LODs than last frame, after a reload
+		assert_eq!(select_lod(5, 0.0, 2), 1);
+	}
+
+	#[test]
+	fn missing_lods_use_the_nearest() {
+		// Authored _lod0 and _lod3
+		let lods = || vec![0, 0, 3].into_iter();
+		assert_eq!(nearest_lod(0, lods()), 0);
+		assert_eq!(nearest_lod(1, lods()), 0);
+		assert_eq!(nearest_lod(2, lods()), 3);
+		assert_eq!(nearest_lod(3, lods()), 3);
+		assert_eq!(nearest_lod(1, vec![0, 2].into_iter()), 0);
+		assert_eq!(nearest_lod(2, vec![].into_iter()), 2);
+	}
+}
@@ -0,0 +1,59 @@
+use crate::types::assets::Assets;
+use image::hdr::HdrDecoder;
+
+/// Where a skybox's image comes from, as asset names. `.hdr` files keep their full range, anything else the `image`
+/// crate reads is decoded from sRGB to linear [0, 1].
+#[derive(Clone, Debug, PartialEq)]
+pub enum SkySource {
+	/// One image with longitude across and latitude down, +X in the middle
+	Equirect(String),
+	/// Square faces in +X, -X, +Y, -Y, +Z, -Z order. Cubemap +Y is world +Z, the usual up for cubemap images.
+	Cubemap([String; 6]),
+}
+
+/// Drawn behind the scene and lights it from all directions. Only the first skybox is used.
+pub struct Skybox {
+	pub source: SkySource,
+	/// Scales both the background and the light it gives
+	pub intensity: f32,
+}
+impl Skybox {
+	pub fn equirect(name: &str) -> Self {
+		Self { source: SkySource::Equirect(name.to_owned()), intensity: 1.0 }
+	}
+
+	pub fn cubemap(faces: [&str; 6]) -> Self {
+		let [px, nx, py, ny, pz, nz] = faces;
+		let faces = [px.to_owned(), nx.to_owned(), py.to_owned(), ny.to_owned(), pz.to_owned(), nz.to_owned()];
+		Self { source: SkySource::Cubemap(faces), intensity: 1.0 }
+	}
+}
+
+/// Linear RGB pixels, top row first
+pub struct SkyImage {
+	pub width: u32,
+	pub height: u32,
+	pub pixels: Vec<[f32; 3]>,
+}
+impl SkyImage {
+	pub fn load(assets: &Assets, name: &str) -> Result<Self, String> {
+		let data = assets.read(name)?;
+		let err = |e: image::ImageError| format!("{}: {}", name, e);
+		if name.ends_with(".hdr") {
+			let decoder = HdrDecoder::new(&data[..]).map_err(err)?;
+			let meta = decoder.metadata();
+			let pixels = decoder.read_image_hdr().map_err(err)?.into_iter().map(|p| p.0).collect();
+			Ok(Self { width: meta.width, height: meta.height, pixels })
+		} else {
+			let img = image::load_from_memory(&data).map_err(err)?.to_rgb();
+			let pixels = img.pixels().map(|p| [decode_srgb(p[0]), decode_srgb(p[1]), decode_srgb(p[2])]).collect();
+			Ok(Self { width: img.width(), height: img.height(), pixels })
+		}
+	}
+}
+
+/// sRGB encoded byte to linear [0, 1]
+fn decode_srgb(c: u8) -> f32 {
+	let c = c as f32 / 255.0;
+	if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+}
@@ -5,12 +5,20 @@ mod systems;
 mod types;
 
 use crate::{
-	components::{model::Model, player_controller::PlayerController},
+	components::{light::Light, model::Model, player_controller::PlayerController},
 	systems::{
-		gui::update_gui,
+		capture::{capture, Capture, Golden},
+		cursor::{update_cursor, Cursor},
+		gamepad::{update_gamepads, GamepadInput, Gamepads},
+		gui::{debug_ui, update_gui, Gui},
+		hot_reload::hot_reload,
+		input::{update_input, InputMap, BINDINGS},
+		loader::{upload_models, ModelLoader},
 		player::update_player,
-		render::{allocs::RenderAllocs, render, render_init},
+		render::{allocs::RenderAllocs, render, render_init, resize, update_lods},
+		replay::{play_frame, record_frame, Playback, Recorder},
 	},
+	types::{assets::Assets, debug_draw::DebugDraw, file_watcher::FileWatcher, text::TextDraw},
 };
 use glrs::{framebuffer::FramebufferAbstract, Ctx};
 use glutin::{
@@ -18,27 +26,88 @@ use glutin::{
 	event_loop::{ControlFlow, EventLoop},
 };
 use shipyard::{system, EntitiesViewMut, NonSendSync, UniqueView, UniqueViewMut, ViewMut, World};
-use std::time::{Duration, Instant};
+use std::{
+	env,
+	path::PathBuf,
+	process,
+	time::{Duration, Instant},
+};
 
 fn main() {
+	let assets = Assets::from_env();
+	// Rendering still needs a GL context, so headless runs create a hidden window. Run under Xvfb with
+	// LIBGL_ALWAYS_SOFTWARE=1 to use llvmpipe on machines without a GPU.
+	let headless = env::args().any(|arg| arg == "--headless");
+	let arg = |name: &str| env::args().skip_while(|arg| arg != name).nth(1);
+	let tolerance = arg("--tolerance").map_or(0, |tolerance| tolerance.parse().expect("tolerance must be 0 to 255"));
+	let golden = match (arg("--capture"), arg("--compare")) {
+		(Some(path), _) => Some(Golden::Save(path.into())),
+		(None, Some(path)) => Some(Golden::Compare { path: path.into(), tolerance }),
+		(None, None) => None,
+	};
+	// Replays play back recorded input instead of the window's, then quit
+	let record = arg("--record").map(PathBuf::from);
+	let replay = arg("--replay").map(PathBuf::from);
+	let (recording, replaying) = (record.is_some(), replay.is_some());
+
 	let event_loop = EventLoop::new();
 	let ctx = Ctx::new(&event_loop);
+	if headless {
+		ctx.window().window().set_visible(false);
+	}
 	let allocs = RenderAllocs::new(&ctx);
 
 	let world = World::new();
 	world.add_unique(Application::default());
+	world.add_unique(Capture { golden, ..Capture::default() });
+	world.add_unique(Cursor::new());
+	world.add_unique(Gamepads::new());
+	// Scripted runs shouldn't see whatever controllers are plugged in, and replays bring their own
+	let (fake_gamepads, fake_handle) = GamepadInput::fake();
+	world.add_unique_non_send_sync(if headless || replaying { fake_gamepads } else { GamepadInput::new() });
+	if let Some(path) = record {
+		world.add_unique(Recorder::create(&path).unwrap_or_else(|e| panic!("{}", e)));
+	}
+	if let Some(path) = replay {
+		world.add_unique_non_send_sync(Playback::load(&path, fake_handle).unwrap_or_else(|e| panic!("{}", e)));
+	}
+	world.add_unique(InputMap::load(&assets, BINDINGS).unwrap_or_else(|e| panic!("{}", e)));
 	world.add_unique(PlayerController::new());
-	world.run(|mut entities: EntitiesViewMut, mut models: NonSendSync<ViewMut<Model>>| {
-		entities.add_entity(&mut *models, Model::from_file(&allocs, "assets/baldman.dae"));
+	world.add_unique(FileWatcher::new());
+	world.add_unique(DebugDraw::new());
+	world.add_unique(TextDraw::new());
+	world.add_unique(Gui::new());
+	world.add_unique_non_send_sync(ModelLoader::new(&allocs, &assets));
+	world.run(
+		|mut entities: EntitiesViewMut,
+		 mut models: NonSendSync<ViewMut<Model>>,
+		 mut loader: NonSendSync<UniqueViewMut<ModelLoader>>| {
+			let path = "baldman.dae";
+			let id = entities.add_entity(&mut *models, Model::pending(&assets, path));
+			loader.load(id, path);
+		},
+	);
+	world.run(|mut entities: EntitiesViewMut, mut lights: ViewMut<Light>| {
+		entities.add_entity(&mut lights, Light::directional([0.3, 0.5, -1.0].into(), [1.0, 0.95, 0.9].into(), 1.0));
+		entities.add_entity(&mut lights, Light::point([2.0, -2.0, 2.0].into(), [0.4, 0.6, 1.0].into(), 10.0, 10.0));
 	});
 
-	render_init(&world, &allocs);
+	render_init(&world, &allocs, &assets);
 
 	world
 		.add_workload("")
+		.with_system(system!(resize))
+		.with_system(system!(update_input))
+		.with_system(system!(update_gamepads))
 		.with_system(system!(update_gui))
+		.with_system(system!(debug_ui))
+		.with_system(system!(update_cursor))
 		.with_system(system!(update_player))
+		.with_system(system!(hot_reload))
+		.with_system(system!(upload_models))
+		.with_system(system!(update_lods))
 		.with_system(system!(render))
+		.with_system(system!(capture))
 		.build();
 
 	let mut last_instant = Instant::now();
@@ -53,18 +122,33 @@ fn main() {
 		match event {
 			Event::WindowEvent { event, .. } => match event {
 				WindowEvent::CloseRequested => *control = ControlFlow::Exit,
-				WindowEvent::Resized(physical_size) => ctx.window().resize(physical_size),
+				WindowEvent::Resized(physical_size) => {
+					ctx.window().resize(physical_size);
+					world.run(|events| push_window_event(event, events));
+				},
+				_ if replaying => (),
 				_ => world.run(|events| push_window_event(event, events)),
 			},
-			Event::DeviceEvent { event, .. } => world.run(|events| push_device_event(event, events)),
+			Event::DeviceEvent { event, .. } if !replaying => world.run(|events| push_device_event(event, events)),
 			Event::MainEventsCleared => {
-				world.run(|mut delta: UniqueViewMut<Duration>| {
-					let now = Instant::now();
-					*delta = now - last_instant;
-					last_instant = now;
-				});
+				if replaying {
+					world.run(play_frame);
+				} else {
+					world.run(|mut delta: UniqueViewMut<Duration>| {
+						let now = Instant::now();
+						*delta = now - last_instant;
+						last_instant = now;
+					});
+				}
 				world.run_default();
+				if recording {
+					world.run(record_frame);
+				}
 				world.run(|app: UniqueView<Application>| {
+					// The event loop always exits successfully
+					if app.exit_code != 0 {
+						process::exit(app.exit_code);
+					}
 					if app.quit {
 						*control = ControlFlow::Exit;
 						return;
@@ -76,6 +160,7 @@ fn main() {
 				ctx.default_framebuffer().clear_color(0.1, 0.1, 0.1, 1.0);
 
 				world.run(clear_events);
+				world.run(clear_debug_draw);
 			},
 			_ => (),
 		};
@@ -85,11 +170,18 @@ fn main() {
 #[derive(Default)]
 pub struct Application {
 	pub quit: bool,
+	/// Exits with this right away if it's not 0
+	pub exit_code: i32,
 }
 impl Application {
 	pub fn quit(&mut self) {
 		self.quit = true;
 	}
+
+	/// Quits with an error, for scripted runs whose checks failed.
+	pub fn fail(&mut self) {
+		self.exit_code = 1;
+	}
 }
 
 fn clear_events(
@@ -100,6 +192,11 @@ fn clear_events(
 	device_events.clear();
 }
 
+fn clear_debug_draw(mut debug_draw: UniqueViewMut<DebugDraw>, mut text_draw: UniqueViewMut<TextDraw>) {
+	debug_draw.clear();
+	text_draw.clear();
+}
+
 fn push_device_event(event: DeviceEvent, mut device_events: UniqueViewMut<Vec<DeviceEvent>>) {
 	device_events.push(event);
 }
@@ -1,3 +1,10 @@
+pub mod capture;
+pub mod cursor;
+pub mod gamepad;
 pub mod gui;
+pub mod hot_reload;
+pub mod input;
+pub mod loader;
 pub mod player;
 pub mod render;
+pub mod replay;
@@ -0,0 +1,229 @@
+use crate::{
+	components::{model::Model, player_controller::PlayerController},
+	systems::{loader::ModelLoader, render::RenderState},
+	Application,
+};
+use image::{imageops, imageops::FilterType, Rgba, RgbaImage};
+use shipyard::{IntoIter, NonSendSync, UniqueView, UniqueViewMut, View};
+use std::{
+	fmt::Write,
+	fs,
+	path::{Path, PathBuf},
+	thread,
+	time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// What to do with the first frame rendered after every model has loaded, before quitting. Used for golden image tests.
+pub enum Golden {
+	/// Saves it as the new golden image
+	Save(PathBuf),
+	/// Compares it with the golden image, exiting with an error if it's further off than `tolerance`
+	Compare { path: PathBuf, tolerance: u8 },
+}
+
+pub struct Capture {
+	pub golden: Option<Golden>,
+	/// Directory screenshots are saved to
+	pub dir: PathBuf,
+	/// Screenshots render at this multiple of the window size and are scaled down
+	pub supersample: u32,
+	requested: bool,
+	/// The frame just rendered is a supersampled screenshot
+	supersampling: bool,
+}
+impl Capture {
+	/// Saves a screenshot of the next frame to `dir`, with a text file describing the engine state next to it.
+	pub fn screenshot(&mut self) {
+		self.requested = true;
+	}
+}
+impl Default for Capture {
+	fn default() -> Self {
+		Self { golden: None, dir: "screenshots".into(), supersample: 1, requested: false, supersampling: false }
+	}
+}
+
+pub fn capture(
+	mut capture: UniqueViewMut<Capture>,
+	mut app: UniqueViewMut<Application>,
+	mut state: NonSendSync<UniqueViewMut<RenderState>>,
+	loader: NonSendSync<UniqueView<ModelLoader>>,
+	player: UniqueView<PlayerController>,
+	delta: UniqueView<Duration>,
+	models: NonSendSync<View<Model>>,
+) {
+	if capture.golden.is_some() && loader.is_idle() {
+		let img = state.read_pixels();
+		match capture.golden.take().unwrap() {
+			Golden::Save(path) => {
+				if let Err(err) = img.save(&path) {
+					eprintln!("failed to save {}: {}", path.display(), err);
+				}
+			},
+			Golden::Compare { path, tolerance } => {
+				if let Err(err) = golden_test(&img, &path, tolerance) {
+					eprintln!("golden image test failed: {}", err);
+					app.fail();
+				}
+			},
+		}
+		app.quit();
+	}
+
+	let img = if capture.supersampling {
+		capture.supersampling = false;
+		let [width, height] = state.window_size();
+		let img = imageops::resize(&state.read_pixels(), width, height, FilterType::Triangle);
+		state.set_scale(1);
+		img
+	} else if capture.requested {
+		capture.requested = false;
+		if capture.supersample > 1 {
+			// Takes effect next frame
+			state.set_scale(capture.supersample);
+			capture.supersampling = true;
+			return;
+		}
+		state.read_screen()
+	} else {
+		return;
+	};
+
+	let info = engine_state(&player, &delta, &models);
+	save_screenshot(capture.dir.clone(), img, info);
+}
+
+/// Compares `img` with the image at `path`. On failure `img` and the difference are saved next to it.
+fn golden_test(img: &RgbaImage, path: &Path, tolerance: u8) -> Result<(), String> {
+	let golden = image::open(path).map_err(|e| format!("{}: {}", path.display(), e))?.to_rgba();
+	let diff = compare(img, &golden, tolerance)?;
+	println!("{}: max difference {}, {} pixels over {}", path.display(), diff.max, diff.over, tolerance);
+	if diff.passed() {
+		return Ok(());
+	}
+
+	let actual = path.with_extension("actual.png");
+	let diff_path = path.with_extension("diff.png");
+	for (img, path) in &[(img, &actual), (&diff.image, &diff_path)] {
+		if let Err(err) = img.save(path) {
+			eprintln!("failed to save {}: {}", path.display(), err);
+		}
+	}
+	Err(format!("{} pixels differ, see {} and {}", diff.over, actual.display(), diff_path.display()))
+}
+
+/// Difference between a frame and its golden image
+pub struct ImageDiff {
+	/// Largest difference in any channel
+	pub max: u8,
+	/// Pixels with a channel differing by more than the tolerance
+	pub over: usize,
+	/// White where a pixel is over the tolerance, and the golden image darkened elsewhere
+	pub image: RgbaImage,
+}
+impl ImageDiff {
+	/// Edges rasterize slightly differently between drivers, so a few pixels in a thousand may be off.
+	pub fn passed(&self) -> bool {
+		let pixels = self.image.width() as usize * self.image.height() as usize;
+		self.over * 1000 <= pixels
+	}
+}
+
+/// Compares two images of the same size channel by channel.
+pub fn compare(img: &RgbaImage, golden: &RgbaImage, tolerance: u8) -> Result<ImageDiff, String> {
+	if img.dimensions() != golden.dimensions() {
+		return Err(format!("size is {:?}, the golden image's is {:?}", img.dimensions(), golden.dimensions()));
+	}
+	let (mut max, mut over) = (0, 0);
+	let image = RgbaImage::from_fn(img.width(), img.height(), |x, y| {
+		let (a, b) = (img.get_pixel(x, y), golden.get_pixel(x, y));
+		let diff = a.0.iter().zip(&b.0).map(|(&a, &b)| (a as i16 - b as i16).abs() as u8).max().unwrap();
+		max = max.max(diff);
+		if diff > tolerance {
+			over += 1;
+			Rgba([255, 255, 255, 255])
+		} else {
+			Rgba([b[0] / 4, b[1] / 4, b[2] / 4, 255])
+		}
+	});
+	Ok(ImageDiff { max, over, image })
+}
+
+fn engine_state(player: &PlayerController, delta: &Duration, models: &View<Model>) -> String {
+	let cam = &player.cam;
+	let mut info = String::new();
+	writeln!(info, "camera pos: {:?}", cam.uniform.pos.as_slice()).unwrap();
+	writeln!(info, "camera yaw: {} pitch: {} fov: {}", cam.yaw, cam.pitch, cam.fov).unwrap();
+	writeln!(info, "frame time: {:?}", delta).unwrap();
+	for model in models.iter() {
+		let (path, loaded, meshes, lod) = (&model.path, model.loaded, model.meshes.len(), model.lod);
+		writeln!(info, "model: {} loaded: {} meshes: {} lod: {}", path, loaded, meshes, lod).unwrap();
+		if let Some(err) = &model.failed {
+			writeln!(info, "model: {} failed: {}", path, err).unwrap();
+		}
+	}
+	info
+}
+
+/// Encodes on another thread so the frame doesn't hitch.
+fn save_screenshot(dir: PathBuf, img: RgbaImage, info: String) {
+	let time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+	thread::spawn(move || {
+		let path = dir.join(format!("screenshot-{}.png", time));
+		let result = fs::create_dir_all(&dir)
+			.map_err(|e| e.to_string())
+			.and_then(|()| img.save(&path).map_err(|e| e.to_string()))
+			.and_then(|()| fs::write(path.with_extension("txt"), info).map_err(|e| e.to_string()));
+		match result {
+			Ok(()) => println!("saved {}", path.display()),
+			Err(err) => eprintln!("failed to save {}: {}", path.display(), err),
+		}
+	});
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn solid(width: u32, height: u32, value: u8) -> RgbaImage {
+		RgbaImage::from_pixel(width, height, Rgba([value, value, value, 255]))
+	}
+
+	#[test]
+	fn identical_images_pass() {
+		let diff = compare(&solid(4, 4, 100), &solid(4, 4, 100), 0).unwrap();
+		assert_eq!((diff.max, diff.over), (0, 0));
+		assert!(diff.passed());
+	}
+
+	#[test]
+	fn differences_within_tolerance_pass() {
+		let diff = compare(&solid(4, 4, 103), &solid(4, 4, 100), 3).unwrap();
+		assert_eq!((diff.max, diff.over), (3, 0));
+		assert!(diff.passed());
+	}
+
+	#[test]
+	fn differences_over_tolerance_fail() {
+		let mut img = solid(40, 40, 100);
+		img.put_pixel(3, 5, Rgba([100, 90, 100, 255]));
+		let diff = compare(&img, &solid(40, 40, 100), 3).unwrap();
+		assert_eq!((diff.max, diff.over), (10, 1));
+		// One pixel in 1600 is allowed
+		assert!(diff.passed());
+		assert_eq!(diff.image.get_pixel(3, 5), &Rgba([255, 255, 255, 255]));
+		assert_eq!(diff.image.get_pixel(0, 0), &Rgba([25, 25, 25, 255]));
+
+		img.put_pixel(4, 5, Rgba([0, 100, 100, 255]));
+		img.put_pixel(5, 5, Rgba([100, 100, 100, 0]));
+		let diff = compare(&img, &solid(40, 40, 100), 3).unwrap();
+		assert_eq!((diff.max, diff.over), (255, 3));
+		assert!(!diff.passed());
+	}
+
+	#[test]
+	fn size_mismatch_is_an_error() {
+		let err = compare(&solid(4, 4, 0), &solid(4, 2, 0), 0).err().unwrap();
+		assert_eq!(err, "size is (4, 4), the golden image's is (4, 2)");
+	}
+}
@@ -0,0 +1,64 @@
+use crate::systems::render::RenderState;
+use glutin::dpi::PhysicalPosition;
+use shipyard::{NonSendSync, UniqueView, UniqueViewMut};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CursorMode {
+	Free,
+	/// Free but invisible over the window
+	Hidden,
+	/// Kept in the window
+	Confined,
+	/// Hidden and kept in the center of the window, for mouse look
+	Locked,
+}
+
+pub struct Cursor {
+	/// Applied to the window at the end of the frame
+	pub mode: CursorMode,
+	/// Mode clicking in the window switches to
+	pub grab_mode: CursorMode,
+	/// Mode the window is in
+	applied: Option<CursorMode>,
+}
+impl Cursor {
+	pub fn new() -> Self {
+		Self { mode: CursorMode::Free, grab_mode: CursorMode::Locked, applied: None }
+	}
+
+	/// Whether the cursor is kept in the window, so mouse movement should turn the camera.
+	pub fn grabbed(&self) -> bool {
+		self.mode == CursorMode::Confined || self.mode == CursorMode::Locked
+	}
+
+	pub fn grab(&mut self) {
+		self.mode = self.grab_mode;
+	}
+
+	pub fn release(&mut self) {
+		self.mode = CursorMode::Free;
+	}
+}
+
+/// Applies `Cursor::mode` to the window.
+pub fn update_cursor(mut cursor: UniqueViewMut<Cursor>, state: NonSendSync<UniqueView<RenderState>>) {
+	let window = state.window();
+	if cursor.applied != Some(cursor.mode) {
+		if let Err(err) = window.set_cursor_grab(cursor.grabbed()) {
+			eprintln!("failed to grab cursor: {}", err);
+		}
+		window.set_cursor_visible(cursor.mode == CursorMode::Free || cursor.mode == CursorMode::Confined);
+		cursor.applied = Some(cursor.mode);
+	}
+
+	// Grabbing only confines the cursor on some platforms, so it's moved back every frame
+	if cursor.mode == CursorMode::Locked {
+		let [width, height] = state.window_size();
+		let center = PhysicalPosition::new(width / 2, height / 2);
+		if let Err(err) = window.set_cursor_position(center) {
+			eprintln!("failed to move cursor, confining it instead: {}", err);
+			cursor.grab_mode = CursorMode::Confined;
+			cursor.mode = CursorMode::Confined;
+		}
+	}
+}
@@ -0,0 +1,475 @@
+use crate::systems::input::{GamepadAxis, GamepadButton, Input, InputMap};
+use gilrs::{
+	ff::{BaseEffect, BaseEffectType, Effect, EffectBuilder, Replay, Ticks},
+	Axis, Button, EventType, Gilrs, GilrsBuilder,
+};
+use shipyard::{NonSendSync, UniqueViewMut};
+use std::{
+	cell::RefCell,
+	collections::{BTreeMap, HashMap, HashSet},
+	mem,
+	rc::Rc,
+	time::{Duration, Instant},
+};
+
+pub const BUTTONS: [GamepadButton; 14] = [
+	GamepadButton::South,
+	GamepadButton::East,
+	GamepadButton::North,
+	GamepadButton::West,
+	GamepadButton::LeftBumper,
+	GamepadButton::RightBumper,
+	GamepadButton::Select,
+	GamepadButton::Start,
+	GamepadButton::LeftStick,
+	GamepadButton::RightStick,
+	GamepadButton::DPadUp,
+	GamepadButton::DPadDown,
+	GamepadButton::DPadLeft,
+	GamepadButton::DPadRight,
+];
+
+pub const AXES: [GamepadAxis; 6] = [
+	GamepadAxis::LeftX,
+	GamepadAxis::LeftY,
+	GamepadAxis::RightX,
+	GamepadAxis::RightY,
+	GamepadAxis::LeftTrigger,
+	GamepadAxis::RightTrigger,
+];
+
+/// Input from a backend. Axis values are raw, before dead zones.
+#[derive(Clone, Debug, PartialEq)]
+pub enum GamepadEvent {
+	Connected { pad: usize, name: String },
+	Disconnected { pad: usize },
+	Button { pad: usize, button: GamepadButton, down: bool },
+	Axis { pad: usize, axis: GamepadAxis, value: f32 },
+}
+
+/// Rumble, with motor strengths from 0 to 1
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rumble {
+	pub pad: usize,
+	pub strong: f32,
+	pub weak: f32,
+	pub duration: Duration,
+}
+
+/// Where gamepad events come from
+pub trait GamepadBackend {
+	/// Events since the last poll.
+	fn poll(&mut self) -> Vec<GamepadEvent>;
+	fn rumble(&mut self, rumble: Rumble);
+}
+
+#[derive(Default)]
+pub struct GamepadState {
+	pub name: String,
+	pub buttons: HashSet<GamepadButton>,
+	/// After dead zones
+	pub axes: HashMap<GamepadAxis, f32>,
+	raw_axes: HashMap<GamepadAxis, f32>,
+}
+impl GamepadState {
+	pub fn axis(&self, axis: GamepadAxis) -> f32 {
+		self.axes.get(&axis).copied().unwrap_or(0.0)
+	}
+}
+
+/// Connected gamepads, updated from the backend every frame. Their buttons and axes also feed the `InputMap`.
+pub struct Gamepads {
+	pub pads: BTreeMap<usize, GamepadState>,
+	/// This frame's events, including hotplugging
+	pub events: Vec<GamepadEvent>,
+	/// Fraction of a stick's range around the center, or a trigger's from the bottom, that reads as 0
+	pub stick_dead_zone: f32,
+	pub trigger_dead_zone: f32,
+	rumbles: Vec<Rumble>,
+}
+impl Gamepads {
+	pub fn new() -> Self {
+		Self { pads: BTreeMap::new(), events: vec![], stick_dead_zone: 0.15, trigger_dead_zone: 0.05, rumbles: vec![] }
+	}
+
+	/// Rumbles `pad`, or every pad if it's `None`. Pads without force feedback ignore it.
+	pub fn rumble(&mut self, pad: Option<usize>, strong: f32, weak: f32, duration: Duration) {
+		let pads: Vec<_> = match pad {
+			Some(pad) => vec![pad],
+			None => self.pads.keys().copied().collect(),
+		};
+		for pad in pads {
+			self.rumbles.push(Rumble { pad, strong, weak, duration });
+		}
+	}
+
+	fn apply(&mut self, event: &GamepadEvent) {
+		match event {
+			GamepadEvent::Connected { pad, name } => {
+				println!("gamepad {} connected: {}", pad, name);
+				self.pads.insert(*pad, GamepadState { name: name.clone(), ..GamepadState::default() });
+			},
+			GamepadEvent::Disconnected { pad } => {
+				println!("gamepad {} disconnected", pad);
+				self.pads.remove(pad);
+			},
+			GamepadEvent::Button { pad, button, down } => {
+				let state = self.pads.entry(*pad).or_default();
+				if *down {
+					state.buttons.insert(*button);
+				} else {
+					state.buttons.remove(button);
+				}
+			},
+			GamepadEvent::Axis { pad, axis, value } => {
+				self.pads.entry(*pad).or_default().raw_axes.insert(*axis, *value);
+			},
+		}
+	}
+
+	fn apply_dead_zones(&mut self) {
+		let (stick_zone, trigger_zone) = (self.stick_dead_zone, self.trigger_dead_zone);
+		for state in self.pads.values_mut() {
+			let raw = |axis| state.raw_axes.get(&axis).copied().unwrap_or(0.0);
+			let (left_x, left_y) = dead_zone(raw(GamepadAxis::LeftX), raw(GamepadAxis::LeftY), stick_zone);
+			let (right_x, right_y) = dead_zone(raw(GamepadAxis::RightX), raw(GamepadAxis::RightY), stick_zone);
+			let left_trigger = dead_zone(raw(GamepadAxis::LeftTrigger), 0.0, trigger_zone).0;
+			let right_trigger = dead_zone(raw(GamepadAxis::RightTrigger), 0.0, trigger_zone).0;
+			let values = [left_x, left_y, right_x, right_y, left_trigger, right_trigger];
+			state.axes = AXES.iter().copied().zip(values.iter().copied()).collect();
+		}
+	}
+}
+
+/// Scales `(x, y)` so lengths from `zone` to 1 map to 0 to 1, keeping its direction. Works on the whole stick rather
+/// than each axis, so diagonals aren't snapped to the axes.
+fn dead_zone(x: f32, y: f32, zone: f32) -> (f32, f32) {
+	let len = (x * x + y * y).sqrt();
+	if len <= zone {
+		return (0.0, 0.0);
+	}
+	let scale = ((len - zone) / (1.0 - zone)).min(1.0) / len;
+	(x * scale, y * scale)
+}
+
+/// Reads gamepads through gilrs.
+pub struct GilrsBackend {
+	gilrs: Gilrs,
+	/// Pads connected before startup, which gilrs has no events for
+	connected: Vec<GamepadEvent>,
+	/// Effects stop when dropped, so they're kept until they finish
+	effects: Vec<(Effect, Instant)>,
+}
+impl GilrsBackend {
+	pub fn new() -> Result<Self, String> {
+		// Dead zones are applied by `Gamepads` instead, so they're configurable
+		let gilrs = GilrsBuilder::new().with_default_filters(false).build().map_err(|e| e.to_string())?;
+		let connected = gilrs
+			.gamepads()
+			.map(|(id, pad)| GamepadEvent::Connected { pad: usize::from(id), name: pad.name().to_owned() })
+			.collect();
+		Ok(Self { gilrs, connected, effects: vec![] })
+	}
+}
+impl GamepadBackend for GilrsBackend {
+	fn poll(&mut self) -> Vec<GamepadEvent> {
+		let mut events = mem::take(&mut self.connected);
+		while let Some(event) = self.gilrs.next_event() {
+			let pad = usize::from(event.id);
+			match event.event {
+				EventType::Connected => {
+					let name = self.gilrs.gamepad(event.id).name().to_owned();
+					events.push(GamepadEvent::Connected { pad, name });
+				},
+				EventType::Disconnected => events.push(GamepadEvent::Disconnected { pad }),
+				EventType::ButtonPressed(button, _) | EventType::ButtonReleased(button, _) => {
+					let down = matches!(event.event, EventType::ButtonPressed(..));
+					if let Some(button) = gilrs_button(button) {
+						events.push(GamepadEvent::Button { pad, button, down });
+					}
+				},
+				// Analog triggers report as buttons on most pads
+				EventType::ButtonChanged(Button::LeftTrigger2, value, _) => {
+					events.push(GamepadEvent::Axis { pad, axis: GamepadAxis::LeftTrigger, value });
+				},
+				EventType::ButtonChanged(Button::RightTrigger2, value, _) => {
+					events.push(GamepadEvent::Axis { pad, axis: GamepadAxis::RightTrigger, value });
+				},
+				EventType::AxisChanged(axis, value, _) => {
+					if let Some(axis) = gilrs_axis(axis) {
+						events.push(GamepadEvent::Axis { pad, axis, value });
+					}
+				},
+				_ => (),
+			}
+		}
+
+		let now = Instant::now();
+		self.effects.retain(|(_, end)| *end > now);
+		events
+	}
+
+	fn rumble(&mut self, rumble: Rumble) {
+		let id = match self.gilrs.gamepads().map(|(id, _)| id).find(|&id| usize::from(id) == rumble.pad) {
+			Some(id) => id,
+			None => return,
+		};
+		if !self.gilrs.gamepad(id).is_ff_supported() {
+			return;
+		}
+		let magnitude = |strength: f32| (strength.max(0.0).min(1.0) * u16::MAX as f32) as u16;
+		let scheduling = Replay { play_for: Ticks::from_ms(rumble.duration.as_millis() as _), ..Replay::default() };
+		let effect = EffectBuilder::new()
+			.add_effect(BaseEffect {
+				kind: BaseEffectType::Strong { magnitude: magnitude(rumble.strong) },
+				scheduling,
+				..BaseEffect::default()
+			})
+			.add_effect(BaseEffect {
+				kind: BaseEffectType::Weak { magnitude: magnitude(rumble.weak) },
+				scheduling,
+				..BaseEffect::default()
+			})
+			.gamepads(&[id])
+			.finish(&mut self.gilrs)
+			.and_then(|effect| effect.play().map(|_| effect));
+		match effect {
+			Ok(effect) => self.effects.push((effect, Instant::now() + rumble.duration)),
+			Err(err) => eprintln!("failed to rumble gamepad {}: {}", rumble.pad, err),
+		}
+	}
+}
+
+fn gilrs_button(button: Button) -> Option<GamepadButton> {
+	Some(match button {
+		Button::South => GamepadButton::South,
+		Button::East => GamepadButton::East,
+		Button::North => GamepadButton::North,
+		Button::West => GamepadButton::West,
+		Button::LeftTrigger => GamepadButton::LeftBumper,
+		Button::RightTrigger => GamepadButton::RightBumper,
+		Button::Select => GamepadButton::Select,
+		Button::Start => GamepadButton::Start,
+		Button::LeftThumb => GamepadButton::LeftStick,
+		Button::RightThumb => GamepadButton::RightStick,
+		Button::DPadUp => GamepadButton::DPadUp,
+		Button::DPadDown => GamepadButton::DPadDown,
+		Button::DPadLeft => GamepadButton::DPadLeft,
+		Button::DPadRight => GamepadButton::DPadRight,
+		_ => return None,
+	})
+}
+
+fn gilrs_axis(axis: Axis) -> Option<GamepadAxis> {
+	Some(match axis {
+		Axis::LeftStickX => GamepadAxis::LeftX,
+		Axis::LeftStickY => GamepadAxis::LeftY,
+		Axis::RightStickX => GamepadAxis::RightX,
+		Axis::RightStickY => GamepadAxis::RightY,
+		Axis::LeftZ => GamepadAxis::LeftTrigger,
+		Axis::RightZ => GamepadAxis::RightTrigger,
+		_ => return None,
+	})
+}
+
+/// Events pushed to `FakeGamepads` and rumbles sent to the backend
+#[derive(Default)]
+struct FakeState {
+	events: Vec<GamepadEvent>,
+	rumbles: Vec<Rumble>,
+}
+
+/// Backend without hardware. Events pushed through its `FakeGamepads` handle come out of the next poll.
+#[derive(Default)]
+pub struct FakeBackend {
+	state: Rc<RefCell<FakeState>>,
+}
+impl FakeBackend {
+	pub fn handle(&self) -> FakeGamepads {
+		FakeGamepads { state: self.state.clone() }
+	}
+}
+impl GamepadBackend for FakeBackend {
+	fn poll(&mut self) -> Vec<GamepadEvent> {
+		mem::take(&mut self.state.borrow_mut().events)
+	}
+
+	fn rumble(&mut self, rumble: Rumble) {
+		self.state.borrow_mut().rumbles.push(rumble);
+	}
+}
+
+/// Injects inputs into a `FakeBackend`.
+#[derive(Clone)]
+pub struct FakeGamepads {
+	state: Rc<RefCell<FakeState>>,
+}
+impl FakeGamepads {
+	pub fn push(&self, event: GamepadEvent) {
+		self.state.borrow_mut().events.push(event);
+	}
+
+	/// Rumbles requested so far, oldest first.
+	pub fn rumbles(&self) -> Vec<Rumble> {
+		self.state.borrow().rumbles.clone()
+	}
+}
+
+/// The backend, kept apart from `Gamepads` since gilrs can't move between threads.
+pub struct GamepadInput {
+	backend: Box<dyn GamepadBackend>,
+}
+impl GamepadInput {
+	/// Uses gilrs, or no gamepads at all if it's unavailable.
+	pub fn new() -> Self {
+		match GilrsBackend::new() {
+			Ok(backend) => Self::with_backend(backend),
+			Err(err) => {
+				eprintln!("gamepads unavailable: {}", err);
+				Self::with_backend(FakeBackend::default())
+			},
+		}
+	}
+
+	pub fn with_backend(backend: impl GamepadBackend + 'static) -> Self {
+		Self { backend: Box::new(backend) }
+	}
+
+	/// No real gamepads, with a handle to inject fake ones.
+	pub fn fake() -> (Self, FakeGamepads) {
+		let backend = FakeBackend::default();
+		let handle = backend.handle();
+		(Self::with_backend(backend), handle)
+	}
+}
+
+/// Polls the backend into `Gamepads` and the `InputMap`. Runs after `update_input`.
+pub fn update_gamepads(
+	mut gamepad_input: NonSendSync<UniqueViewMut<GamepadInput>>,
+	mut gamepads: UniqueViewMut<Gamepads>,
+	mut input: UniqueViewMut<InputMap>,
+) {
+	let events = gamepad_input.backend.poll();
+	for event in &events {
+		gamepads.apply(event);
+		// Lets the player know which pad was picked up
+		if let GamepadEvent::Connected { pad, .. } = event {
+			gamepads.rumble(Some(*pad), 0.5, 0.5, Duration::from_millis(200));
+		}
+	}
+	gamepads.events = events;
+	gamepads.apply_dead_zones();
+	for rumble in gamepads.rumbles.drain(..) {
+		gamepad_input.backend.rumble(rumble);
+	}
+
+	// Inputs from every pad are merged, with the axis pushed furthest winning
+	let buttons: HashSet<_> = gamepads.pads.values().flat_map(|state| state.buttons.iter().copied()).collect();
+	for button in BUTTONS.iter() {
+		input.set_held(Input::GamepadButton(*button), buttons.contains(button));
+	}
+	for &axis in AXES.iter() {
+		let values = gamepads.pads.values().map(|state| state.axis(axis));
+		let value = values.fold(0.0, |a: f32, b| if b.abs() > a.abs() { b } else { a });
+		input.set_value(Input::GamepadAxis(axis), value);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::systems::input::update_input;
+	use glutin::event::{DeviceEvent, WindowEvent};
+	use shipyard::{UniqueView, World};
+
+	fn close(a: f32, b: f32) -> bool {
+		(a - b).abs() < 1e-6
+	}
+
+	#[test]
+	fn dead_zone_rescales_outside_zone() {
+		assert_eq!(dead_zone(0.1, 0.1, 0.2), (0.0, 0.0));
+		assert_eq!(dead_zone(0.0, 0.2, 0.2), (0.0, 0.0));
+		let (x, y) = dead_zone(0.6, 0.0, 0.2);
+		assert!(close(x, 0.5) && y == 0.0, "{} {}", x, y);
+		let (x, y) = dead_zone(0.0, -1.0, 0.2);
+		assert!(x == 0.0 && close(y, -1.0), "{} {}", x, y);
+		// Diagonals keep their direction instead of snapping to an axis
+		let (x, y) = dead_zone(0.3, 0.4, 0.2);
+		assert!(close(x / y, 0.75) && close((x * x + y * y).sqrt(), 0.375), "{} {}", x, y);
+	}
+
+	fn world() -> (World, FakeGamepads) {
+		let bindings = "action jump = GamepadSouth\naxis move_x = GamepadLeftX\naxis throttle = GamepadRightTrigger";
+		let (gamepad_input, fake) = GamepadInput::fake();
+		let world = World::new();
+		world.add_unique(Gamepads::new());
+		world.add_unique(InputMap::from_bindings(bindings).unwrap());
+		world.add_unique_non_send_sync(gamepad_input);
+		world.add_unique(Vec::<WindowEvent>::new());
+		world.add_unique(Vec::<DeviceEvent>::new());
+		(world, fake)
+	}
+
+	/// Runs input handling in the same order as the workload.
+	fn frame(world: &World) {
+		world.run(update_input);
+		world.run(update_gamepads);
+	}
+
+	#[test]
+	fn fake_events_reach_gamepads_and_input_map() {
+		let (world, fake) = world();
+		fake.push(GamepadEvent::Connected { pad: 0, name: "Test pad".to_owned() });
+		fake.push(GamepadEvent::Axis { pad: 0, axis: GamepadAxis::LeftX, value: 0.6 });
+		fake.push(GamepadEvent::Axis { pad: 0, axis: GamepadAxis::RightTrigger, value: 0.03 });
+		fake.push(GamepadEvent::Button { pad: 0, button: GamepadButton::South, down: true });
+		frame(&world);
+
+		let stick = (0.6 - 0.15) / (1.0 - 0.15);
+		world.run(|gamepads: UniqueView<Gamepads>, input: UniqueView<InputMap>| {
+			assert_eq!(gamepads.events.len(), 4);
+			let pad = &gamepads.pads[&0];
+			assert_eq!(pad.name, "Test pad");
+			assert!(close(pad.axis(GamepadAxis::LeftX), stick));
+			// Inside the trigger dead zone
+			assert_eq!(pad.axis(GamepadAxis::RightTrigger), 0.0);
+			assert!(pad.buttons.contains(&GamepadButton::South));
+
+			assert!(close(input.axis("move_x"), stick));
+			assert_eq!(input.axis("throttle"), 0.0);
+			assert!(input.held("jump") && input.pressed("jump"));
+		});
+		let connect = Rumble { pad: 0, strong: 0.5, weak: 0.5, duration: Duration::from_millis(200) };
+		assert_eq!(fake.rumbles(), vec![connect]);
+
+		// Nothing new, so the button stays held without being pressed again
+		frame(&world);
+		world.run(|input: UniqueView<InputMap>| assert!(input.held("jump") && !input.pressed("jump")));
+	}
+
+	#[test]
+	fn hotplug_adds_and_removes_pads() {
+		let (world, fake) = world();
+		fake.push(GamepadEvent::Connected { pad: 0, name: "First".to_owned() });
+		fake.push(GamepadEvent::Connected { pad: 3, name: "Second".to_owned() });
+		fake.push(GamepadEvent::Button { pad: 3, button: GamepadButton::South, down: true });
+		fake.push(GamepadEvent::Axis { pad: 3, axis: GamepadAxis::LeftX, value: -1.0 });
+		frame(&world);
+		world.run(|gamepads: UniqueView<Gamepads>, input: UniqueView<InputMap>| {
+			assert_eq!(gamepads.pads.keys().copied().collect::<Vec<_>>(), vec![0, 3]);
+			assert!(input.held("jump"));
+			assert!(close(input.axis("move_x"), -1.0));
+		});
+		assert_eq!(fake.rumbles().iter().map(|rumble| rumble.pad).collect::<Vec<_>>(), vec![0, 3]);
+
+		fake.push(GamepadEvent::Disconnected { pad: 3 });
+		frame(&world);
+		world.run(|gamepads: UniqueView<Gamepads>, input: UniqueView<InputMap>| {
+			assert_eq!(gamepads.pads.keys().copied().collect::<Vec<_>>(), vec![0]);
+			// What the unplugged pad was holding is let go
+			assert!(!input.held("jump") && input.released("jump"));
+			assert_eq!(input.axis("move_x"), 0.0);
+		});
+	}
+}
@@ -1,30 +1,187 @@
-use crate::Application;
-use glutin::event::{ElementState, VirtualKeyCode, WindowEvent};
-use shipyard::{UniqueView, UniqueViewMut};
-
-pub fn update_gui(mut app: UniqueViewMut<Application>, events: UniqueView<Vec<WindowEvent>>) {
-	for event in events.iter() {
-		match event {
-			WindowEvent::KeyboardInput { input, .. } => match input.virtual_keycode {
-				Some(keycode) => match keycode {
-					VirtualKeyCode::Escape => {
-						// if ctx.grab.load(Ordering::Relaxed) {
-						// ctx.set_grab(false);
-						// } else {
-						app.quit();
-						// }
-					},
-					_ => (),
-				},
-				_ => (),
-			},
-			WindowEvent::MouseInput { state, .. } => match state {
-				ElementState::Pressed => {
-					// ctx.set_grab(true);
-				},
-				ElementState::Released => (),
-			},
-			_ => (),
+use crate::{
+	components::{
+		light::{Light, LightKind},
+		model::Model,
+		skybox::{SkySource, Skybox},
+	},
+	systems::{
+		capture::Capture,
+		cursor::{Cursor, CursorMode},
+		input::InputMap,
+		render::{post::PostSettings, WindowSize},
+	},
+	types::ui::Ui,
+	Application, PlayerController,
+};
+use glutin::event::WindowEvent;
+use shipyard::{EntityId, IntoIter, NonSendSync, UniqueView, UniqueViewMut, View, ViewMut};
+use std::{collections::VecDeque, time::Duration};
+
+/// Frames the timings panel averages over
+const FRAME_HISTORY: usize = 120;
+
+/// Debug overlay state. Toggled with F1.
+pub struct Gui {
+	pub ui: Ui,
+	pub visible: bool,
+	frame_times: VecDeque<Duration>,
+}
+impl Gui {
+	pub fn new() -> Self {
+		Self { ui: Ui::new(), visible: false, frame_times: VecDeque::with_capacity(FRAME_HISTORY) }
+	}
+
+	/// Whether the overlay is using the mouse, so the camera shouldn't.
+	pub fn wants_mouse(&self) -> bool {
+		self.visible && self.ui.wants_mouse()
+	}
+}
+
+pub fn update_gui(
+	mut app: UniqueViewMut<Application>,
+	mut capture: UniqueViewMut<Capture>,
+	mut cursor: UniqueViewMut<Cursor>,
+	mut gui: UniqueViewMut<Gui>,
+	input: UniqueView<InputMap>,
+	events: UniqueView<Vec<WindowEvent>>,
+	delta: UniqueView<Duration>,
+	window_size: UniqueView<WindowSize>,
+) {
+	if gui.frame_times.len() == FRAME_HISTORY {
+		gui.frame_times.pop_front();
+	}
+	gui.frame_times.push_back(*delta);
+	gui.ui.begin_frame(&events, window_size.0);
+
+	if input.pressed("release_cursor") {
+		if cursor.mode != CursorMode::Free {
+			cursor.release();
+		} else {
+			app.quit();
+		}
+	}
+	if input.pressed("toggle_gui") {
+		gui.visible = !gui.visible;
+		// The overlay needs the cursor
+		if gui.visible {
+			cursor.release();
 		}
 	}
+	if input.pressed("screenshot") {
+		capture.screenshot();
+	}
+	if input.pressed("grab_cursor") && !cursor.grabbed() && !gui.wants_mouse() {
+		cursor.grab();
+	}
+	if events.iter().any(|event| matches!(event, WindowEvent::Focused(false))) {
+		cursor.release();
+	}
+}
+
+/// Panels of the debug overlay.
+pub fn debug_ui(
+	mut gui: UniqueViewMut<Gui>,
+	mut player: UniqueViewMut<PlayerController>,
+	mut post: UniqueViewMut<PostSettings>,
+	models: NonSendSync<View<Model>>,
+	mut lights: ViewMut<Light>,
+	mut skyboxes: ViewMut<Skybox>,
+) {
+	if !gui.visible {
+		return;
+	}
+	let Gui { ui, frame_times, .. } = &mut *gui;
+
+	ui.window("Frame timings", [10.0, 10.0], |ui| {
+		let ms: Vec<f32> = frame_times.iter().map(|time| time.as_secs_f32() * 1000.0).collect();
+		let average = ms.iter().sum::<f32>() / ms.len().max(1) as f32;
+		let max = ms.iter().cloned().fold(0.0, f32::max);
+		ui.label(&format!("{:.2} ms ({:.0} fps)", average, 1000.0 / average));
+		ui.label(&format!("max {:.2} ms", max));
+		ui.plot(&ms, max.max(1000.0 / 60.0));
+	});
+
+	ui.window("Camera", [10.0, 130.0], |ui| {
+		let cam = &mut player.cam;
+		let mut changed = ui.slider("fov", &mut cam.fov, 10.0, 80.0);
+		ui.slider("sensitivity", &mut cam.sensitivity, 0.1, 5.0);
+		changed |= ui.slider("z near", &mut cam.z_near, 0.01, 10.0);
+		changed |= ui.slider("z far", &mut cam.z_far, 10.0, 10000.0);
+		changed |= ui.checkbox("reversed z", &mut cam.reversed_z);
+		if changed {
+			cam.update();
+		}
+		let pos = cam.uniform.pos;
+		ui.label(&format!("pos {:.2} {:.2} {:.2}", pos.x, pos.y, pos.z));
+	});
+
+	ui.window("Post processing", [10.0, 290.0], |ui| {
+		ui.checkbox("bloom", &mut post.bloom);
+		ui.checkbox("auto exposure", &mut post.exposure);
+		ui.slider("exposure ev", &mut post.exposure_ev, -5.0, 5.0);
+		ui.checkbox("color grading", &mut post.color_grading);
+		ui.checkbox("fxaa", &mut post.fxaa);
+	});
+
+	ui.window("Entities", [300.0, 10.0], |ui| {
+		// Every entity with a component the inspector knows, and the names of those components
+		let mut entities: Vec<(EntityId, Vec<&str>)> = vec![];
+		let mut add = |id, component| match entities.iter_mut().find(|(entity, _)| *entity == id) {
+			Some((_, components)) => components.push(component),
+			None => entities.push((id, vec![component])),
+		};
+		for (id, _) in models.iter().with_id() {
+			add(id, "model");
+		}
+		for (id, _) in (&lights).iter().with_id() {
+			add(id, "light");
+		}
+		for (id, _) in (&skyboxes).iter().with_id() {
+			add(id, "skybox");
+		}
+
+		for (entity, components) in entities {
+			ui.collapsing(&format!("{:?} {}", entity, components.join(", ")), |ui| {
+				for (_, model) in models.iter().with_id().filter(|(id, _)| *id == entity) {
+					ui.label(&format!("model {}", model.path));
+					match &model.failed {
+						Some(err) => ui.label(&format!("failed: {}", err)),
+						None => ui.label(if model.loaded { "loaded" } else { "loading" }),
+					}
+					ui.label(&format!("{} meshes, {} materials", model.meshes.len(), model.materials.len()));
+					ui.label(&format!("lod {} of {}", model.lod, model.lod_count()));
+				}
+				for (_, light) in (&mut lights).iter().with_id().filter(|(id, _)| *id == entity) {
+					let kind = match light.kind {
+						LightKind::Directional => "directional",
+						LightKind::Point => "point",
+						LightKind::Spot { .. } => "spot",
+					};
+					ui.label(&format!("{} light", kind));
+					ui.slider("intensity", &mut light.intensity, 0.0, 20.0);
+					ui.slider("range", &mut light.range, 0.0, 100.0);
+					ui.checkbox("shadows", &mut light.shadows);
+				}
+				for (_, skybox) in (&mut skyboxes).iter().with_id().filter(|(id, _)| *id == entity) {
+					match &skybox.source {
+						SkySource::Equirect(name) => ui.label(&format!("skybox {}", name)),
+						SkySource::Cubemap(faces) => ui.label(&format!("skybox cubemap {}", faces[0])),
+					}
+					ui.slider("sky intensity", &mut skybox.intensity, 0.0, 5.0);
+				}
+			});
+		}
+	});
+
+	ui.window("Assets", [300.0, 300.0], |ui| {
+		let loaded = models.iter().filter(|model| model.loaded).count();
+		let failed = models.iter().filter(|model| model.failed.is_some()).count();
+		let meshes: usize = models.iter().map(|model| model.meshes.len()).sum();
+		let materials: usize = models.iter().map(|model| model.materials.len()).sum();
+		let files: usize = models.iter().map(|model| model.files.len()).sum();
+		ui.label(&format!("models {} of {} loaded, {} failed", loaded, models.iter().count(), failed));
+		ui.label(&format!("meshes {}", meshes));
+		ui.label(&format!("materials {}", materials));
+		ui.label(&format!("files {}", files));
+	});
 }
@@ -0,0 +1,35 @@
+use crate::{
+	components::model::Model,
+	systems::{loader::ModelLoader, render::RenderState},
+	types::file_watcher::FileWatcher,
+};
+use shipyard::{IntoIter, NonSendSync, UniqueViewMut, View};
+
+pub fn hot_reload(
+	mut watcher: UniqueViewMut<FileWatcher>,
+	mut state: NonSendSync<UniqueViewMut<RenderState>>,
+	mut loader: NonSendSync<UniqueViewMut<ModelLoader>>,
+	models: NonSendSync<View<Model>>,
+) {
+	for file in state.shader_files() {
+		watcher.watch(file);
+	}
+	for model in models.iter() {
+		for file in &model.files {
+			watcher.watch(file);
+		}
+	}
+
+	let changed = watcher.poll();
+	if changed.is_empty() {
+		return;
+	}
+
+	state.reload_shaders(&changed);
+
+	for (id, model) in models.iter().with_id() {
+		if model.files.iter().any(|file| changed.contains(file)) {
+			loader.load(id, &model.path);
+		}
+	}
+}
@@ -0,0 +1,374 @@
+use crate::types::assets::Assets;
+use glutin::event::{DeviceEvent, ElementState, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent};
+use shipyard::{UniqueView, UniqueViewMut};
+use std::collections::{HashMap, HashSet};
+
+pub const BINDINGS: &str = "input.cfg";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GamepadButton {
+	South,
+	East,
+	North,
+	West,
+	LeftBumper,
+	RightBumper,
+	Select,
+	Start,
+	LeftStick,
+	RightStick,
+	DPadUp,
+	DPadDown,
+	DPadLeft,
+	DPadRight,
+}
+
+/// Sticks go from -1 to 1 with y up, triggers from 0 to 1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GamepadAxis {
+	LeftX,
+	LeftY,
+	RightX,
+	RightY,
+	LeftTrigger,
+	RightTrigger,
+}
+
+/// Anything a binding can read
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Input {
+	Key(VirtualKeyCode),
+	Mouse(MouseButton),
+	/// Raw mouse movement this frame, in unspecified units
+	MouseX,
+	MouseY,
+	/// Lines scrolled this frame, positive up
+	MouseWheel,
+	GamepadButton(GamepadButton),
+	GamepadAxis(GamepadAxis),
+}
+impl Input {
+	/// Whether the value can go past 1, like mouse movement
+	fn unbounded(self) -> bool {
+		self == Input::MouseX || self == Input::MouseY || self == Input::MouseWheel
+	}
+}
+
+/// `positive` minus `negative`, times `scale`. Held buttons count as 1.
+struct AxisBinding {
+	positive: Input,
+	negative: Option<Input>,
+	scale: f32,
+}
+
+/// Named actions and axes bound to inputs. Systems query these rather than raw events, so controls can be rebound
+/// in the bindings file.
+pub struct InputMap {
+	actions: HashMap<String, Vec<Input>>,
+	axes: HashMap<String, Vec<AxisBinding>>,
+	held: HashSet<Input>,
+	/// Went down or up this frame
+	pressed: HashSet<Input>,
+	released: HashSet<Input>,
+	/// Mouse movement this frame and the gamepad's current axes
+	values: HashMap<Input, f32>,
+}
+impl InputMap {
+	pub fn load(assets: &Assets, name: &str) -> Result<Self, String> {
+		Self::from_bindings(&assets.read_string(name)?).map_err(|e| format!("{}: {}", name, e))
+	}
+
+	/// Bindings in the same format as the file, for maps that don't come from one.
+	pub fn from_bindings(src: &str) -> Result<Self, String> {
+		let mut map = Self {
+			actions: HashMap::new(),
+			axes: HashMap::new(),
+			held: HashSet::new(),
+			pressed: HashSet::new(),
+			released: HashSet::new(),
+			values: HashMap::new(),
+		};
+		map.parse(src)?;
+		Ok(map)
+	}
+
+	/// Reads lines like `action jump = Space, GamepadSouth` and `axis move_x = D - A, GamepadLeftX * 0.5`. Each
+	/// line replaces earlier bindings of the same name. `#` starts a comment.
+	fn parse(&mut self, src: &str) -> Result<(), String> {
+		for (i, line) in src.lines().enumerate() {
+			let err = |e: String| format!("line {}: {}", i + 1, e);
+			let line = line.split('#').next().unwrap().trim();
+			if line.is_empty() {
+				continue;
+			}
+			let (decl, bindings) = split(line, '=').ok_or_else(|| err("expected `=`".to_owned()))?;
+			let mut decl = decl.split_whitespace();
+			let (kind, name) = match (decl.next(), decl.next(), decl.next()) {
+				(Some(kind), Some(name), None) => (kind, name.to_owned()),
+				_ => return Err(err("expected `action <name>` or `axis <name>`".to_owned())),
+			};
+			let bindings = bindings.split(',').map(str::trim);
+			match kind {
+				"action" => {
+					let inputs = bindings.map(parse_input).collect::<Result<_, _>>().map_err(err)?;
+					self.actions.insert(name, inputs);
+				},
+				"axis" => {
+					let bindings = bindings.map(parse_axis_binding).collect::<Result<_, _>>().map_err(err)?;
+					self.axes.insert(name, bindings);
+				},
+				_ => return Err(err(format!("unknown binding kind {}", kind))),
+			}
+		}
+		Ok(())
+	}
+
+	/// Whether any input bound to `action` is down.
+	pub fn held(&self, action: &str) -> bool {
+		self.action(action).any(|input| self.held.contains(input))
+	}
+
+	/// Whether an input bound to `action` went down this frame.
+	pub fn pressed(&self, action: &str) -> bool {
+		self.action(action).any(|input| self.pressed.contains(input))
+	}
+
+	/// Whether an input bound to `action` went up this frame.
+	pub fn released(&self, action: &str) -> bool {
+		self.action(action).any(|input| self.released.contains(input))
+	}
+
+	/// Sum of `axis`'s bindings. Buttons and gamepad axes together go from -1 to 1, and mouse movement adds to that.
+	pub fn axis(&self, axis: &str) -> f32 {
+		let (mut bounded, mut unbounded) = (0.0, 0.0);
+		for binding in self.axes.get(axis).into_iter().flatten() {
+			let negative = binding.negative.map_or(0.0, |negative| self.value(negative));
+			let value = (self.value(binding.positive) - negative) * binding.scale;
+			if binding.positive.unbounded() {
+				unbounded += value;
+			} else {
+				bounded += value;
+			}
+		}
+		bounded.max(-1.0).min(1.0) + unbounded
+	}
+
+	/// 1 for held buttons
+	fn value(&self, input: Input) -> f32 {
+		if self.held.contains(&input) { 1.0 } else { self.values.get(&input).copied().unwrap_or(0.0) }
+	}
+
+	/// Marks `input` as down or up, for sources outside the window's events.
+	pub fn set_held(&mut self, input: Input, down: bool) {
+		if down && self.held.insert(input) {
+			self.pressed.insert(input);
+		} else if !down && self.held.remove(&input) {
+			self.released.insert(input);
+		}
+	}
+
+	pub fn set_value(&mut self, input: Input, value: f32) {
+		self.values.insert(input, value);
+	}
+
+	/// Releases everything, for when the window loses focus and won't see inputs go up.
+	pub fn release_all(&mut self) {
+		self.released.extend(self.held.drain());
+	}
+
+	fn action<'a>(&'a self, action: &str) -> impl Iterator<Item = &'a Input> {
+		self.actions.get(action).into_iter().flatten()
+	}
+}
+
+/// Turns this frame's events into input state. Runs before anything queries it.
+pub fn update_input(
+	mut input: UniqueViewMut<InputMap>,
+	window_events: UniqueView<Vec<WindowEvent>>,
+	device_events: UniqueView<Vec<DeviceEvent>>,
+) {
+	input.pressed.clear();
+	input.released.clear();
+	for axis in &[Input::MouseX, Input::MouseY, Input::MouseWheel] {
+		input.values.remove(axis);
+	}
+
+	for event in window_events.iter() {
+		match event {
+			WindowEvent::KeyboardInput { input: key, .. } => {
+				if let Some(keycode) = key.virtual_keycode {
+					input.set_held(Input::Key(keycode), key.state == ElementState::Pressed);
+				}
+			},
+			WindowEvent::MouseInput { state, button, .. } => {
+				input.set_held(Input::Mouse(*button), *state == ElementState::Pressed);
+			},
+			WindowEvent::MouseWheel { delta, .. } => {
+				let lines = match delta {
+					MouseScrollDelta::LineDelta(_, y) => *y,
+					// Roughly how far a line scrolls
+					MouseScrollDelta::PixelDelta(pos) => pos.y as f32 / 20.0,
+				};
+				*input.values.entry(Input::MouseWheel).or_default() += lines;
+			},
+			WindowEvent::Focused(false) => input.release_all(),
+			_ => (),
+		}
+	}
+	for event in device_events.iter() {
+		if let DeviceEvent::MouseMotion { delta: (x, y) } = event {
+			*input.values.entry(Input::MouseX).or_default() += *x as f32;
+			*input.values.entry(Input::MouseY).or_default() += *y as f32;
+		}
+	}
+}
+
+/// Splits at the first `sep`.
+fn split(s: &str, sep: char) -> Option<(&str, &str)> {
+	let i = s.find(sep)?;
+	Some((s[..i].trim(), s[i + sep.len_utf8()..].trim()))
+}
+
+/// `Positive`, `Positive - Negative`, optionally followed by `* scale`
+fn parse_axis_binding(src: &str) -> Result<AxisBinding, String> {
+	let (inputs, scale) = match split(src, '*') {
+		Some((inputs, scale)) => (inputs, scale.parse().map_err(|_| format!("bad scale {}", scale))?),
+		None => (src, 1.0),
+	};
+	let (positive, negative) = match split(inputs, '-') {
+		Some((positive, negative)) => (parse_input(positive)?, Some(parse_input(negative)?)),
+		None => (parse_input(inputs)?, None),
+	};
+	Ok(AxisBinding { positive, negative, scale })
+}
+
+fn parse_input(name: &str) -> Result<Input, String> {
+	use GamepadAxis as A;
+	use GamepadButton as B;
+
+	let input = match name {
+		"MouseLeft" => Input::Mouse(MouseButton::Left),
+		"MouseRight" => Input::Mouse(MouseButton::Right),
+		"MouseMiddle" => Input::Mouse(MouseButton::Middle),
+		"MouseX" => Input::MouseX,
+		"MouseY" => Input::MouseY,
+		"MouseWheel" => Input::MouseWheel,
+		"GamepadSouth" => Input::GamepadButton(B::South),
+		"GamepadEast" => Input::GamepadButton(B::East),
+		"GamepadNorth" => Input::GamepadButton(B::North),
+		"GamepadWest" => Input::GamepadButton(B::West),
+		"GamepadLeftBumper" => Input::GamepadButton(B::LeftBumper),
+		"GamepadRightBumper" => Input::GamepadButton(B::RightBumper),
+		"GamepadSelect" => Input::GamepadButton(B::Select),
+		"GamepadStart" => Input::GamepadButton(B::Start),
+		"GamepadLeftStick" => Input::GamepadButton(B::LeftStick),
+		"GamepadRightStick" => Input::GamepadButton(B::RightStick),
+		"GamepadDPadUp" => Input::GamepadButton(B::DPadUp),
+		"GamepadDPadDown" => Input::GamepadButton(B::DPadDown),
+		"GamepadDPadLeft" => Input::GamepadButton(B::DPadLeft),
+		"GamepadDPadRight" => Input::GamepadButton(B::DPadRight),
+		"GamepadLeftX" => Input::GamepadAxis(A::LeftX),
+		"GamepadLeftY" => Input::GamepadAxis(A::LeftY),
+		"GamepadRightX" => Input::GamepadAxis(A::RightX),
+		"GamepadRightY" => Input::GamepadAxis(A::RightY),
+		"GamepadLeftTrigger" => Input::GamepadAxis(A::LeftTrigger),
+		"GamepadRightTrigger" => Input::GamepadAxis(A::RightTrigger),
+		_ => Input::Key(parse_key(name).ok_or_else(|| format!("unknown input {}", name))?),
+	};
+	Ok(input)
+}
+
+macro_rules! keys {
+	($($key:ident),* $(,)?) => {
+		/// `VirtualKeyCode` by variant name
+		pub fn parse_key(name: &str) -> Option<VirtualKeyCode> {
+			match name {
+				$(stringify!($key) => Some(VirtualKeyCode::$key),)*
+				_ => None,
+			}
+		}
+	};
+}
+
+keys![
+	Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9, Key0, A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R,
+	S, T, U, V, W, X, Y, Z, Escape, F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12, F13, F14, F15, F16, F17, F18,
+	F19, F20, F21, F22, F23, F24, Snapshot, Scroll, Pause, Insert, Home, Delete, End, PageDown, PageUp, Left, Up, Right,
+	Down, Back, Return, Space, Compose, Caret, Numlock, Numpad0, Numpad1, Numpad2, Numpad3, Numpad4, Numpad5, Numpad6,
+	Numpad7, Numpad8, Numpad9, AbntC1, AbntC2, Add, Apostrophe, Apps, At, Ax, Backslash, Calculator, Capital, Colon,
+	Comma, Convert, Decimal, Divide, Equals, Grave, Kana, Kanji, LAlt, LBracket, LControl, LShift, LWin, Mail,
+	MediaSelect, MediaStop, Minus, Multiply, Mute, MyComputer, NavigateForward, NavigateBackward, NextTrack, NoConvert,
+	NumpadComma, NumpadEnter, NumpadEquals, OEM102, Period, PlayPause, Power, PrevTrack, RAlt, RBracket, RControl,
+	RShift, RWin, Semicolon, Slash, Sleep, Stop, Subtract, Sysrq, Tab, Underline, Unlabeled, VolumeDown, VolumeUp, Wake,
+	WebBack, WebFavorites, WebForward, WebHome, WebRefresh, WebSearch, WebStop, Yen, Copy, Paste, Cut,
+];
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn map(src: &str) -> InputMap {
+		InputMap::from_bindings(src).unwrap()
+	}
+
+	fn err(src: &str) -> String {
+		InputMap::from_bindings(src).err().unwrap()
+	}
+
+	#[test]
+	fn actions_match_any_input() {
+		let mut input = map("# comment\n\naction jump = Space, GamepadSouth # trailing comment");
+		assert!(!input.held("jump"));
+		input.set_held(Input::GamepadButton(GamepadButton::South), true);
+		assert!(input.held("jump") && input.pressed("jump"));
+		input.set_held(Input::GamepadButton(GamepadButton::South), false);
+		assert!(!input.held("jump") && input.released("jump"));
+		assert!(!input.held("unbound"));
+	}
+
+	#[test]
+	fn later_lines_replace_bindings() {
+		let mut input = map("action jump = Space\naction jump = J");
+		input.set_held(Input::Key(VirtualKeyCode::Space), true);
+		assert!(!input.held("jump"));
+		input.set_held(Input::Key(VirtualKeyCode::J), true);
+		assert!(input.held("jump"));
+	}
+
+	#[test]
+	fn axes_combine_bindings() {
+		let mut input = map("axis move_x = D - A, GamepadLeftX * 0.5\naxis look_y = MouseY * -0.01");
+		input.set_held(Input::Key(VirtualKeyCode::D), true);
+		input.set_value(Input::GamepadAxis(GamepadAxis::LeftX), -0.5);
+		assert_eq!(input.axis("move_x"), 0.75);
+		// Buttons and gamepad axes are clamped together
+		input.set_value(Input::GamepadAxis(GamepadAxis::LeftX), 1.0);
+		assert_eq!(input.axis("move_x"), 1.0);
+		// Mouse movement isn't
+		input.set_value(Input::MouseY, 200.0);
+		assert_eq!(input.axis("look_y"), -2.0);
+		assert_eq!(input.axis("unbound"), 0.0);
+	}
+
+	#[test]
+	fn opposite_keys_cancel_and_recover() {
+		let mut input = map("axis move_x = D - A");
+		input.set_held(Input::Key(VirtualKeyCode::D), true);
+		assert_eq!(input.axis("move_x"), 1.0);
+		input.set_held(Input::Key(VirtualKeyCode::A), true);
+		assert_eq!(input.axis("move_x"), 0.0);
+		input.set_held(Input::Key(VirtualKeyCode::A), false);
+		assert_eq!(input.axis("move_x"), 1.0);
+	}
+
+	#[test]
+	fn errors_name_the_line() {
+		assert_eq!(err("action jump = Space\naction jump Space"), "line 2: expected `=`");
+		assert_eq!(err("action = Space"), "line 1: expected `action <name>` or `axis <name>`");
+		assert_eq!(err("action big jump = Space"), "line 1: expected `action <name>` or `axis <name>`");
+		assert_eq!(err("# comment\nbutton jump = Space"), "line 2: unknown binding kind button");
+		assert_eq!(err("action jump = Spacebar"), "line 1: unknown input Spacebar");
+		assert_eq!(err("axis move_x = D - Left Arrow"), "line 1: unknown input Left Arrow");
+		assert_eq!(err("axis look_x = MouseX * fast"), "line 1: bad scale fast");
+	}
+}
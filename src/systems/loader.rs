@@ -0,0 +1,170 @@
+use crate::{
+	components::model::{upload_material, upload_texture, Mesh, MeshData, Model, ModelData, TextureData},
+	systems::render::allocs::{MaterialAllocation, RenderAllocs},
+	types::{assets::Assets, material::Material},
+};
+use shipyard::{EntityId, IntoIter, NonSendSync, UniqueViewMut, ViewMut};
+use std::{
+	collections::{HashMap, VecDeque},
+	path::PathBuf,
+	rc::Rc,
+	sync::{
+		mpsc::{channel, Receiver, Sender},
+		Arc, Mutex,
+	},
+	thread,
+	vec::IntoIter as VecIntoIter,
+};
+
+type Job = (EntityId, String);
+type Loaded = (EntityId, String, Result<ModelData, String>);
+
+/// Parses models on worker threads and uploads them to the GPU a few meshes per frame.
+pub struct ModelLoader {
+	allocs: Rc<RenderAllocs>,
+	jobs: Sender<Job>,
+	loaded: Receiver<Loaded>,
+	uploads: VecDeque<Upload>,
+	/// Jobs sent to the workers that haven't come back yet
+	in_flight: usize,
+	/// Bytes uploaded per frame. At least one texture or mesh is always uploaded so large ones can't stall.
+	pub budget: usize,
+}
+impl ModelLoader {
+	pub fn new(allocs: &Rc<RenderAllocs>, assets: &Assets) -> Self {
+		let (jobs, job_recv) = channel::<Job>();
+		let (loaded_send, loaded) = channel();
+		let job_recv = Arc::new(Mutex::new(job_recv));
+
+		let workers = thread::available_parallelism().map_or(1, |n| n.get()).max(2) - 1;
+		for _ in 0..workers {
+			let job_recv = job_recv.clone();
+			let loaded_send = loaded_send.clone();
+			let assets = assets.clone();
+			thread::spawn(move || loop {
+				let job = job_recv.lock().unwrap().recv();
+				let (entity, path) = match job {
+					Ok(job) => job,
+					Err(_) => return,
+				};
+				let data = ModelData::load(&assets, &path);
+				if loaded_send.send((entity, path, data)).is_err() {
+					return;
+				}
+			});
+		}
+
+		Self { allocs: allocs.clone(), jobs, loaded, uploads: VecDeque::new(), in_flight: 0, budget: 4 * 1024 * 1024 }
+	}
+
+	/// Queues `path` to be loaded into the `Model` of `entity`. The model keeps its current meshes, or the
+	/// placeholder if it has none, until the new ones are uploaded.
+	pub fn load(&mut self, entity: EntityId, path: &str) {
+		self.jobs.send((entity, path.to_owned())).unwrap();
+		self.in_flight += 1;
+	}
+
+	/// True when nothing is loading or waiting to be uploaded.
+	pub fn is_idle(&self) -> bool {
+		self.in_flight == 0 && self.uploads.is_empty()
+	}
+
+	/// Queues finished jobs for upload, returning why the failed ones failed.
+	fn poll(&mut self) -> HashMap<EntityId, String> {
+		let mut failed = HashMap::new();
+		for (entity, path, data) in self.loaded.try_iter() {
+			self.in_flight -= 1;
+			match data {
+				Ok(data) => self.uploads.push_back(Upload::new(entity, path, data)),
+				Err(err) => {
+					eprintln!("failed to load model: {}", err);
+					failed.insert(entity, err);
+				},
+			}
+		}
+		failed
+	}
+
+	/// Uploads until the budget is spent, returning the models that finished.
+	fn upload(&mut self) -> HashMap<EntityId, Model> {
+		let mut done = HashMap::new();
+		let mut spent = 0;
+		while spent < self.budget {
+			let upload = match self.uploads.front_mut() {
+				Some(upload) => upload,
+				None => break,
+			};
+			match upload.step(&self.allocs) {
+				Some(size) => spent += size,
+				None => {
+					let Upload { entity, path, meshes, materials, files, .. } = self.uploads.pop_front().unwrap();
+					done.insert(entity, Model::new(&path, meshes, materials, files));
+				},
+			}
+		}
+		done
+	}
+}
+
+struct Upload {
+	entity: EntityId,
+	path: String,
+	files: Vec<PathBuf>,
+	textures: VecIntoIter<TextureData>,
+	material_data: Vec<Material>,
+	mesh_data: VecIntoIter<MeshData>,
+	materials: Vec<MaterialAllocation>,
+	meshes: Vec<Mesh>,
+}
+impl Upload {
+	fn new(entity: EntityId, path: String, data: ModelData) -> Self {
+		Self {
+			entity,
+			path,
+			files: data.files,
+			textures: data.textures.into_iter(),
+			material_data: data.materials,
+			mesh_data: data.meshes.into_iter(),
+			materials: vec![],
+			meshes: vec![],
+		}
+	}
+
+	/// Uploads one texture or mesh and returns its size, or `None` when everything is uploaded. Materials all go up
+	/// together between the textures they reference and the meshes that use them.
+	fn step(&mut self, allocs: &Rc<RenderAllocs>) -> Option<usize> {
+		if let Some(tex) = self.textures.next() {
+			let size = tex.size();
+			if let Err(err) = upload_texture(allocs, tex) {
+				eprintln!("failed to upload texture: {}", err);
+			}
+			Some(size)
+		} else if !self.material_data.is_empty() {
+			self.materials = self.material_data.drain(..).map(|mat| upload_material(allocs, &mat)).collect();
+			Some(0)
+		} else if let Some(mesh) = self.mesh_data.next() {
+			self.meshes.push(Mesh::upload(allocs, &mesh, &self.materials));
+			Some(mesh.size())
+		} else {
+			None
+		}
+	}
+}
+
+pub fn upload_models(mut loader: NonSendSync<UniqueViewMut<ModelLoader>>, mut models: NonSendSync<ViewMut<Model>>) {
+	let mut failed = loader.poll();
+	let mut done = loader.upload();
+	if done.is_empty() && failed.is_empty() {
+		return;
+	}
+
+	for (id, model) in (&mut models).iter().with_id() {
+		if let Some(mut loaded) = done.remove(&id) {
+			loaded.set_transform(*model.transform());
+			*model = loaded;
+		}
+		if let Some(err) = failed.remove(&id) {
+			model.failed = Some(err);
+		}
+	}
+}
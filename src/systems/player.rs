@@ -1,36 +1,28 @@
-use crate::PlayerController;
-use glutin::event::{DeviceEvent, ElementState, VirtualKeyCode};
+use crate::{
+	systems::{cursor::Cursor, input::InputMap},
+	PlayerController,
+};
+use nalgebra::Vector3;
 use shipyard::{UniqueView, UniqueViewMut};
 use std::time::Duration;
 
 pub fn update_player(
-	events: UniqueView<Vec<DeviceEvent>>,
+	input: UniqueView<InputMap>,
 	delta: UniqueView<Duration>,
 	mut player: UniqueViewMut<PlayerController>,
+	cursor: UniqueView<Cursor>,
 ) {
 	let PlayerController { movement, cam, .. } = &mut *player;
 
-	for event in &*events {
-		match event {
-			DeviceEvent::MouseMotion { delta: (x, y) } => cam.look((x * 0.01) as _, (y * 0.01) as _),
-			DeviceEvent::Key(key) => {
-				let val = if key.state == ElementState::Pressed { 1.0 } else { 0.0 };
-				match key.virtual_keycode {
-					Some(keycode) => match keycode {
-						VirtualKeyCode::W => movement.y = val,
-						VirtualKeyCode::A => movement.x = -val,
-						VirtualKeyCode::S => movement.y = -val,
-						VirtualKeyCode::D => movement.x = val,
-						VirtualKeyCode::Space => movement.z = val,
-						VirtualKeyCode::LShift => movement.z = -val,
-						_ => (),
-					},
-					_ => (),
-				}
-			},
-			_ => (),
-		}
+	// Without a grab the cursor would leave the window while looking around
+	let (mut x, mut y) = if cursor.grabbed() { (input.axis("look_x"), input.axis("look_y")) } else { (0.0, 0.0) };
+	// Sticks turn at a rate rather than by a distance
+	x += input.axis("turn_x") * delta.as_secs_f32();
+	y += input.axis("turn_y") * delta.as_secs_f32();
+	if x != 0.0 || y != 0.0 {
+		cam.look(x, y);
 	}
+	*movement = Vector3::new(input.axis("move_x"), input.axis("move_y"), input.axis("move_z"));
 
 	cam.uniform.pos += cam.uniform.rot * *movement * delta.as_secs_f32();
 }
@@ -1,38 +1,193 @@
 pub mod allocs;
+pub mod cull;
+pub mod debug;
+pub mod environment;
+pub mod graph;
+pub mod post;
+pub mod shader;
+pub mod shadow;
+pub mod storage;
+pub mod target;
+pub mod text;
 
 use crate::{
 	components::{
-		model::{Instance, Model, Vertex},
+		light::{Light, LightKind, LightsUniform, MAX_LIGHTS},
+		model::{Instance, Mesh, Model, Vertex},
 		player_controller::PlayerController,
+		skybox::Skybox,
 	},
-	types::camera::CameraUniform,
+	systems::{
+		gui::Gui,
+		render::{
+			allocs::SRGB_TEX_UNIT,
+			cull::GpuCull,
+			debug::{DebugRenderer, DEBUG_FRAGMENT_SHADER, DEBUG_VERTEX_SHADER, UI_VERTEX_SHADER},
+			environment::{Environment, BRDF_UNIT, IRRADIANCE_UNIT, PREFILTERED_UNIT},
+			graph::{Access, RenderGraph, TargetPool, TextureDesc},
+			post::{PostChain, PostSettings, HDR_FORMAT},
+			shader::ShaderCache,
+			shadow::{ShadowMaps, ShadowsUniform, CASCADES, MAX_SPOT_SHADOWS, SHADOW_LAYERS},
+			target::read_default,
+			text::TextRenderer,
+		},
+	},
+	types::{assets::Assets, camera::CameraUniform, debug_draw::DebugDraw, frustum::Frustum, text::TextDraw},
 	RenderAllocs,
 };
 use glrs::{
 	buffer::{Buffer, BufferSlice, DynamicBuffer},
 	commands::CommandBuffer,
-	framebuffer::Framebuffer,
+	gl,
 	shader::ShaderProgram,
-	texture::Texture2D,
 	vertex::VertexArray,
 };
-use shipyard::{IntoIter, NonSendSync, UniqueView, View, World};
-use std::rc::Rc;
+use glutin::{event::WindowEvent, window::Window};
+use image::RgbaImage;
+use shipyard::{IntoIter, NonSendSync, UniqueView, UniqueViewMut, View, ViewMut, World};
+use std::{cell::RefCell, cmp::Ordering, ffi::CStr, path::PathBuf, rc::Rc};
+
+const VERTEX_SHADER: &str = "shaders/shader.vert";
+const FRAGMENT_SHADER: &str = "shaders/shader.frag";
+const SHADOW_VERTEX_SHADER: &str = "shaders/shadow.vert";
+const SHADOW_FRAGMENT_SHADER: &str = "shaders/shadow.frag";
+const SHADOW_MAP_SIZE: i32 = 2048;
+/// Texture unit of the shadow maps
+const SHADOW_UNIT: u32 = 1;
+/// Storage buffer binding of the materials, matching `include/material.glsl`
+const MATERIALS_BINDING: u32 = 2;
+/// Storage buffer binding of the instance transforms, matching `include/transforms.glsl`
+const TRANSFORMS_BINDING: u32 = 5;
+
+/// Window size in physical pixels, for systems that don't need the `RenderState` otherwise. Kept current by `resize`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WindowSize(pub [u32; 2]);
 
-pub fn render_init(world: &World, allocs: &Rc<RenderAllocs>) {
-	world.add_unique_non_send_sync(RenderState::new(allocs));
+pub fn render_init(world: &World, allocs: &Rc<RenderAllocs>, assets: &Assets) {
+	let state = RenderState::new(allocs, assets);
+	let [width, height] = state.render_size;
+	world.run(|mut player: UniqueViewMut<PlayerController>| player.cam.resize(width as _, height as _));
+	world.add_unique(WindowSize(state.window_size()));
+	world.add_unique_non_send_sync(state);
+	world.add_unique(PostSettings::default());
 }
 
-pub fn render(
+pub fn resize(
+	events: UniqueView<Vec<WindowEvent>>,
+	mut state: NonSendSync<UniqueViewMut<RenderState>>,
+	mut player: UniqueViewMut<PlayerController>,
+	mut window_size: UniqueViewMut<WindowSize>,
+) {
+	for event in events.iter() {
+		if let WindowEvent::Resized(size) = event {
+			if size.width != 0 && size.height != 0 {
+				*window_size = WindowSize([size.width, size.height]);
+				state.resize_target([size.width, size.height]);
+				player.cam.resize(size.width as _, size.height as _);
+			}
+		}
+	}
+}
+
+/// Picks each model's LOD by its size on screen, and draws the placeholder while any model is still loading.
+pub fn update_lods(
 	state: NonSendSync<UniqueView<RenderState>>,
 	player: UniqueView<PlayerController>,
+	mut models: NonSendSync<ViewMut<Model>>,
+) {
+	let cam = &player.cam;
+	let tan_fov = cam.fov.to_radians().tan();
+	for model in (&mut models).iter() {
+		let bounds = model.world_bounds();
+		let dist = (bounds.center - cam.uniform.pos).norm();
+		model.select_lod(bounds.radius / (dist * tan_fov));
+	}
+	state.placeholder.enable_draws((&models).iter().any(Model::pending_load));
+}
+
+pub fn render(
+	mut state: NonSendSync<UniqueViewMut<RenderState>>,
+	mut player: UniqueViewMut<PlayerController>,
 	models: NonSendSync<View<Model>>,
+	lights: View<Light>,
+	skyboxes: View<Skybox>,
+	post_settings: UniqueView<PostSettings>,
+	debug_draw: UniqueView<DebugDraw>,
+	text_draw: UniqueView<TextDraw>,
+	gui: UniqueView<Gui>,
 ) {
-	state.cambuf.write(&player.cam.uniform);
+	let state = &mut *state;
+	if player.cam.reversed_z && !state.clip_control {
+		eprintln!("reversed-Z needs GL 4.5 or ARB_clip_control, turning it off");
+		player.cam.reversed_z = false;
+		player.cam.update();
+	}
+	let cam = &player.cam;
+	state.cambuf.write(&cam.uniform);
 
-	let mut cmds = CommandBuffer::new(&state.vao);
-	for model in models.iter() {
-		for mesh in &model.meshes {
+	let meshes: Vec<&Mesh> = (&models)
+		.iter()
+		.filter(|model| model.loaded || model.pending_load())
+		.flat_map(|model| {
+			let (model, lod) = if model.loaded { (model, model.lod) } else { (&state.placeholder, 0) };
+			model.meshes.iter().filter(move |mesh| mesh.lod == lod)
+		})
+		.collect();
+	let (blended, opaque): (Vec<&Mesh>, Vec<&Mesh>) = meshes.into_iter().partition(|mesh| mesh.blend);
+
+	state.environment.update(skyboxes.iter().next());
+	let ambient = [0.05, 0.05, 0.05, state.environment.intensity()].into();
+	let mut light_uniform = LightsUniform { ambient, ..LightsUniform::default() };
+	let mut shadows = ShadowsUniform::default();
+	let (mut sun, mut spots) = (false, 0);
+	for (dst, light) in light_uniform.lights.iter_mut().zip(lights.iter()) {
+		let layer = match light.kind {
+			LightKind::Directional if light.shadows && !sun => {
+				sun = true;
+				let (matrices, splits) = shadow::cascades(cam, &light.dir, state.shadow_maps.size());
+				shadows.matrices[..CASCADES].copy_from_slice(&matrices);
+				shadows.splits = splits;
+				Some(0)
+			},
+			LightKind::Spot { outer, .. } if light.shadows && spots < MAX_SPOT_SHADOWS => {
+				let layer = CASCADES + spots;
+				spots += 1;
+				shadows.matrices[layer] = shadow::spot(&light.pos, &light.dir, outer, light.range);
+				Some(layer)
+			},
+			_ => None,
+		};
+		*dst = light.uniform(layer);
+		light_uniform.count.x += 1;
+	}
+	state.lightbuf.write(&light_uniform);
+	state.shadowbuf.write(&shadows);
+
+	let window_size = state.window_size();
+	let RenderState {
+		ref allocs,
+		ref vao,
+		ref shader,
+		ref shadow_shader,
+		ref shadow_maps,
+		ref environment,
+		ref mut gpu_cull,
+		ref mut post,
+		ref debug_shader,
+		ref ui_shader,
+		ref mut debug,
+		ref mut text,
+		render_size,
+		ref mut targets,
+		clip_control,
+		..
+	} = *state;
+	let ctx = allocs.ctx();
+	// Every pass that draws meshes reads them
+	allocs.transforms.bind(TRANSFORMS_BINDING);
+	let commands = |meshes: &[&Mesh]| {
+		let mut cmds = CommandBuffer::new(vao);
+		for mesh in meshes {
 			cmds.push(
 				mesh.indices().len() as _,
 				mesh.instance.len() as _,
@@ -41,21 +196,199 @@ pub fn render(
 				mesh.instance.offset() as _,
 			);
 		}
+		cmds
+	};
+	// Used by more than one pass
+	let (debug, text, post) = (RefCell::new(debug), RefCell::new(text), RefCell::new(post));
+	// Supersampled screenshots read back the post output, so the overlays go there at the scaled size too
+	let supersampled = render_size != window_size;
+	let bind_overlay = || if supersampled { post.borrow().output().bind() } else { bind_default(window_size) };
+
+	let mut graph = RenderGraph::new();
+	let shadow_layers = graph.import("shadow maps");
+	let scene = graph.transient("scene", TextureDesc { size: render_size, format: HDR_FORMAT });
+	// Read by next frame's culling
+	let hiz = graph.import("hi-z pyramid");
+	graph.output(hiz);
+	let window = graph.import("window");
+	graph.output(window);
+
+	// Draws everything opaque, since off-screen meshes can cast into view. Cutouts discard below their alpha cutoff.
+	// Blended meshes don't cast shadows: a depth map can only block light fully or not at all, so glass and smoke
+	// would cast solid shadows.
+	graph.pass("shadows", &[], &[(shadow_layers, Access::Attachment)], |_| {
+		depth_state(false, clip_control);
+		allocs.materials.bind(MATERIALS_BINDING);
+		ctx.use_program(shadow_shader);
+		unsafe {
+			gl::Enable(gl::POLYGON_OFFSET_FILL);
+			gl::PolygonOffset(2.0, 4.0);
+		}
+		let cascades = if sun { 0..CASCADES } else { 0..0 };
+		for layer in cascades.chain(CASCADES..CASCADES + spots) {
+			shadow_maps.bind_layer(layer);
+			shadow_shader.set_uniform_i32("ShadowLayer", layer as _);
+			ctx.multi_draw_elements_indirect(commands(&opaque));
+		}
+		unsafe { gl::Disable(gl::POLYGON_OFFSET_FILL) };
+	});
+
+	let frustum = Frustum::new(cam);
+	let opaque_writes = [(scene, Access::Attachment), (hiz, Access::Image)];
+	graph.pass("opaque", &[(shadow_layers, Access::Sampled)], &opaque_writes, |res| {
+		let target = res.target(scene);
+		target.bind();
+		target.clear(0.1, 0.1, 0.1, 1.0);
+		target.clear_depth(depth_state(cam.reversed_z, clip_control));
+		allocs.materials.bind(MATERIALS_BINDING);
+		environment.bind_textures();
+		ctx.use_program(shader);
+		if let Some(gpu_cull) = gpu_cull {
+			ctx.bind_vertex_array(vao);
+			gpu_cull.draw(allocs, cam);
+			gpu_cull.build_pyramid(target.depth(), target.size(), cam.reversed_z);
+		} else {
+			let visible: Vec<_> =
+				opaque.iter().copied().filter(|mesh| frustum.intersects(&mesh.world_bounds())).collect();
+			ctx.multi_draw_elements_indirect(commands(&visible));
+		}
+	});
+
+	graph.pass("sky", &[], &[(scene, Access::Attachment)], |res| {
+		res.target(scene).bind();
+		environment.draw_sky(ctx, cam.reversed_z);
+	});
+
+	// Back to front, over the opaque pass without writing depth
+	graph.pass("blended", &[(shadow_layers, Access::Sampled)], &[(scene, Access::Attachment)], |res| {
+		let mut blended: Vec<_> = blended.into_iter().filter(|mesh| frustum.intersects(&mesh.world_bounds())).collect();
+		let dist = |mesh: &Mesh| (mesh.world_bounds().center - cam.uniform.pos).norm_squared();
+		blended.sort_by(|a, b| dist(b).partial_cmp(&dist(a)).unwrap_or(Ordering::Equal));
+		res.target(scene).bind();
+		ctx.use_program(shader);
+		unsafe {
+			gl::Enable(gl::BLEND);
+			gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+			gl::DepthMask(gl::FALSE);
+		}
+		ctx.multi_draw_elements_indirect(commands(&blended));
+		unsafe {
+			gl::Disable(gl::BLEND);
+			gl::DepthMask(gl::TRUE);
+		}
+	});
+
+	graph.pass("debug", &[], &[(scene, Access::Attachment)], |res| {
+		res.target(scene).bind();
+		depth_state(cam.reversed_z, clip_control);
+		debug.borrow_mut().draw(ctx, debug_shader, &debug_draw);
+		text.borrow_mut().draw_world(ctx, text_draw.world.iter().chain(debug_draw.texts()), cam);
+	});
+
+	graph.pass("post", &[(scene, Access::Sampled)], &[(window, Access::Attachment)], |res| {
+		let mut post = post.borrow_mut();
+		post.run(ctx, &post_settings, res.target(scene));
+		post.output().blit_to_default(window_size);
+	});
+
+	graph.pass("hud", &[], &[(window, Access::Attachment)], |_| {
+		bind_overlay();
+		text.borrow_mut().draw_screen(ctx, &text_draw.screen, window_size);
+	});
+
+	if gui.visible {
+		graph.pass("ui", &[], &[(window, Access::Attachment)], |_| {
+			bind_overlay();
+			debug.borrow_mut().draw_ui(ctx, ui_shader, &gui.ui);
+		});
+	}
+
+	graph.execute(targets);
+}
+
+fn bind_default([width, height]: [u32; 2]) {
+	unsafe {
+		gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+		gl::Viewport(0, 0, width as _, height as _);
+	}
+}
+
+/// Sets up depth testing for the camera's projection and returns the far depth to clear to.
+fn depth_state(reversed_z: bool, clip_control: bool) -> f32 {
+	unsafe {
+		gl::Enable(gl::DEPTH_TEST);
+		gl::DepthMask(gl::TRUE);
+		if reversed_z {
+			gl::ClipControl(gl::LOWER_LEFT, gl::ZERO_TO_ONE);
+			gl::DepthFunc(gl::GREATER);
+			0.0
+		} else {
+			// Without clip control the range is always [-1, 1]
+			if clip_control {
+				gl::ClipControl(gl::LOWER_LEFT, gl::NEGATIVE_ONE_TO_ONE);
+			}
+			gl::DepthFunc(gl::LESS);
+			1.0
+		}
+	}
+}
+
+/// GL 4.5 or `ARB_clip_control`, which reversed-Z needs for a [0, 1] depth range.
+fn has_clip_control() -> bool {
+	gl_version() >= (4, 5) || has_extension("GL_ARB_clip_control")
+}
+
+/// Major and minor version of the current context.
+fn gl_version() -> (i32, i32) {
+	let (mut major, mut minor) = (0, 0);
+	unsafe {
+		gl::GetIntegerv(gl::MAJOR_VERSION, &mut major);
+		gl::GetIntegerv(gl::MINOR_VERSION, &mut minor);
 	}
+	(major, minor)
+}
 
-	let ctx = state.allocs.ctx();
-	ctx.use_program(&state.shader);
-	ctx.multi_draw_elements_indirect(cmds);
+/// Whether the current context lists the extension `name`, like `"GL_ARB_clip_control"`. Unlike
+/// `get_proc_address`, which can return entry points the driver doesn't support, this is reliable.
+fn has_extension(name: &str) -> bool {
+	let mut count = 0;
+	unsafe { gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut count) };
+	(0..count as u32).any(|i| {
+		let ext = unsafe { gl::GetStringi(gl::EXTENSIONS, i) };
+		!ext.is_null() && unsafe { CStr::from_ptr(ext as *const _) }.to_bytes() == name.as_bytes()
+	})
 }
 
 pub struct RenderState {
 	allocs: Rc<RenderAllocs>,
 	vao: VertexArray,
-	shader: ShaderProgram,
+	shaders: ShaderCache,
+	shader: Rc<ShaderProgram>,
 	cambuf: Rc<DynamicBuffer<CameraUniform>>,
+	lightbuf: Rc<DynamicBuffer<LightsUniform>>,
+	shadow_shader: Rc<ShaderProgram>,
+	shadowbuf: Rc<DynamicBuffer<ShadowsUniform>>,
+	shadow_maps: ShadowMaps,
+	environment: Environment,
+	/// `None` falls back to culling on the CPU
+	gpu_cull: Option<GpuCull>,
+	post: PostChain,
+	debug_shader: Rc<ShaderProgram>,
+	ui_shader: Rc<ShaderProgram>,
+	debug: DebugRenderer,
+	text: TextRenderer,
+	/// Size of the HDR scene target
+	render_size: [u32; 2],
+	/// Transient targets of the render graph
+	targets: TargetPool,
+	/// Multiple of the window size the scene renders at
+	scale: u32,
+	placeholder: Model,
+	/// Reversed-Z is turned off without it
+	clip_control: bool,
 }
 impl RenderState {
-	fn new(allocs: &Rc<RenderAllocs>) -> Self {
+	fn new(allocs: &Rc<RenderAllocs>, assets: &Assets) -> Self {
 		let ctx = allocs.ctx();
 		ctx.bind_texture(0, &allocs.tex);
 
@@ -67,20 +400,153 @@ impl RenderState {
 		vao.vertex_buffer(1, &allocs.vert_alloc);
 
 		let cambuf = Buffer::from_val(ctx, &CameraUniform::default());
+		let lightbuf = Buffer::from_val(ctx, &LightsUniform::default());
+		let shadowbuf = Buffer::from_val(ctx, &ShadowsUniform::default());
+
+		let mut shaders = ShaderCache::new(ctx, assets);
+		let shader = main_shader(&mut shaders, &cambuf, &lightbuf, &shadowbuf).unwrap_or_else(|e| panic!("{}", e));
+		let shadow_shader = shadow_shader(&mut shaders, &shadowbuf).unwrap_or_else(|e| panic!("{}", e));
+		let debug_shader = debug_shader(&mut shaders, &cambuf).unwrap_or_else(|e| panic!("{}", e));
+		let ui_shader =
+			get_shader(&mut shaders, UI_VERTEX_SHADER, DEBUG_FRAGMENT_SHADER).unwrap_or_else(|e| panic!("{}", e));
+
+		let shadow_maps = ShadowMaps::new(SHADOW_MAP_SIZE);
+		shadow_maps.bind_texture(SHADOW_UNIT);
+
+		let environment = Environment::new(ctx, &mut shaders, assets, &lightbuf);
 
-		let shader = ShaderProgram::init(ctx)
-			.vertex_file("src/shaders/shader.vert")
-			.fragment_file("src/shaders/shader.frag")
-			.build();
-		shader.set_uniform_i32("tex", 0);
-		shader.bind_buffer_range("Camera", cambuf.clone());
+		let gpu_cull = GpuCull::new(ctx, &mut shaders);
 
-		let [width, height]: [_; 2] = ctx.window().window().inner_size().into();
-		let color = Texture2D::new(ctx, [width, height].into());
+		let text = TextRenderer::new(&mut shaders, assets, allocs, &cambuf);
 
-		let framebuffer = Framebuffer::new(ctx);
-		framebuffer.color(0, &color);
+		let size = ctx.window().window().inner_size().into();
+		let post = PostChain::new(ctx, &mut shaders, assets, size);
+
+		let placeholder = Model::placeholder(allocs);
+
+		Self {
+			allocs: allocs.clone(),
+			vao,
+			shaders,
+			shader,
+			cambuf,
+			lightbuf,
+			shadow_shader,
+			shadowbuf,
+			shadow_maps,
+			environment,
+			gpu_cull,
+			post,
+			debug_shader,
+			ui_shader,
+			debug: DebugRenderer::new(ctx),
+			text,
+			render_size: size,
+			targets: TargetPool::default(),
+			scale: 1,
+			placeholder,
+			clip_control: has_clip_control(),
+		}
+	}
+
+	pub fn window(&self) -> &Window {
+		self.allocs.ctx().window().window()
+	}
+
+	pub fn window_size(&self) -> [u32; 2] {
+		self.window().inner_size().into()
+	}
+
+	/// Renders at `scale` times the window size from the next frame on. Used for supersampled screenshots.
+	pub fn set_scale(&mut self, scale: u32) {
+		self.scale = scale;
+		self.resize_target(self.window_size());
+	}
 
-		Self { allocs: allocs.clone(), vao, shader, cambuf }
+	fn resize_target(&mut self, [width, height]: [u32; 2]) {
+		self.render_size = [width * self.scale, height * self.scale];
+		self.post.resize(self.render_size);
 	}
+
+	/// Reads back the last frame rendered, at the render scale.
+	pub fn read_pixels(&self) -> RgbaImage {
+		self.post.output().read_pixels()
+	}
+
+	/// Reads back the last frame presented to the window.
+	pub fn read_screen(&self) -> RgbaImage {
+		read_default(self.window_size())
+	}
+
+	pub fn shader_files(&self) -> impl Iterator<Item = &PathBuf> {
+		self.shaders.files()
+	}
+
+	/// Recompiles shaders that use any of `changed`. On failure the error is logged and the old program stays in use.
+	pub fn reload_shaders(&mut self, changed: &[PathBuf]) {
+		if self.shaders.reload(changed) {
+			self.shader = main_shader(&mut self.shaders, &self.cambuf, &self.lightbuf, &self.shadowbuf).unwrap();
+			self.shadow_shader = shadow_shader(&mut self.shaders, &self.shadowbuf).unwrap();
+			self.debug_shader = debug_shader(&mut self.shaders, &self.cambuf).unwrap();
+			self.ui_shader = get_shader(&mut self.shaders, UI_VERTEX_SHADER, DEBUG_FRAGMENT_SHADER).unwrap();
+			if let Some(gpu_cull) = &mut self.gpu_cull {
+				gpu_cull.reload_shaders(&mut self.shaders);
+			}
+			self.environment.reload_shaders(&mut self.shaders);
+			self.post.reload_shaders(&mut self.shaders);
+			self.text.reload_shaders(&mut self.shaders);
+		}
+	}
+}
+
+/// Engine constants the shaders are compiled with
+fn defines() -> Vec<String> {
+	vec![
+		format!("MAX_LIGHTS {}", MAX_LIGHTS),
+		format!("CASCADES {}", CASCADES),
+		format!("SHADOW_LAYERS {}", SHADOW_LAYERS),
+	]
+}
+
+fn get_shader(shaders: &mut ShaderCache, vertex: &str, fragment: &str) -> Result<Rc<ShaderProgram>, String> {
+	let defines = defines();
+	shaders.get(vertex, fragment, &defines.iter().map(String::as_str).collect::<Vec<_>>())
+}
+
+fn main_shader(
+	shaders: &mut ShaderCache,
+	cambuf: &Rc<DynamicBuffer<CameraUniform>>,
+	lightbuf: &Rc<DynamicBuffer<LightsUniform>>,
+	shadowbuf: &Rc<DynamicBuffer<ShadowsUniform>>,
+) -> Result<Rc<ShaderProgram>, String> {
+	let shader = get_shader(shaders, VERTEX_SHADER, FRAGMENT_SHADER)?;
+	shader.set_uniform_i32("tex", 0);
+	shader.set_uniform_i32("srgb_tex", SRGB_TEX_UNIT as _);
+	shader.set_uniform_i32("shadow_maps", SHADOW_UNIT as _);
+	shader.set_uniform_i32("irradiance_map", IRRADIANCE_UNIT as _);
+	shader.set_uniform_i32("prefiltered_map", PREFILTERED_UNIT as _);
+	shader.set_uniform_i32("brdf_lut", BRDF_UNIT as _);
+	shader.bind_buffer_range("Camera", cambuf.clone());
+	shader.bind_buffer_range("Lights", lightbuf.clone());
+	shader.bind_buffer_range("Shadows", shadowbuf.clone());
+	Ok(shader)
+}
+
+fn shadow_shader(
+	shaders: &mut ShaderCache,
+	shadowbuf: &Rc<DynamicBuffer<ShadowsUniform>>,
+) -> Result<Rc<ShaderProgram>, String> {
+	let shader = get_shader(shaders, SHADOW_VERTEX_SHADER, SHADOW_FRAGMENT_SHADER)?;
+	shader.set_uniform_i32("srgb_tex", SRGB_TEX_UNIT as _);
+	shader.bind_buffer_range("Shadows", shadowbuf.clone());
+	Ok(shader)
+}
+
+fn debug_shader(
+	shaders: &mut ShaderCache,
+	cambuf: &Rc<DynamicBuffer<CameraUniform>>,
+) -> Result<Rc<ShaderProgram>, String> {
+	let shader = get_shader(shaders, DEBUG_VERTEX_SHADER, DEBUG_FRAGMENT_SHADER)?;
+	shader.bind_buffer_range("Camera", cambuf.clone());
+	Ok(shader)
 }
@@ -1,10 +1,34 @@
-use crate::{components::model::Instance, systems::render::Vertex};
+use crate::{
+	components::model::Instance,
+	systems::render::{
+		cull::DrawInput,
+		storage::{Slot, SlotBuffer},
+		Vertex,
+	},
+	types::material::MaterialUniform,
+};
 use glrs::{
 	alloc::{Allocation, Allocator, AllocatorAbstract},
+	gl::{self, types::GLuint},
 	texture::{Filter, Texture2DArray, TextureAbstract},
 	Ctx,
 };
-use std::{rc::Rc, slice, sync::atomic::AtomicI32};
+use image::RgbaImage;
+use nalgebra::Matrix4;
+use std::{
+	cell::{Cell, RefCell},
+	collections::HashMap,
+	rc::Rc,
+	slice,
+	sync::atomic::{AtomicI32, Ordering},
+};
+
+/// Width and height of each layer of `RenderAllocs::tex` and `RenderAllocs::srgb_tex`
+pub const TEX_SIZE: u32 = 1024;
+/// Layers in each texture array
+const TEX_LAYERS: u32 = 64;
+/// Texture unit `RenderAllocs::srgb_tex` is bound to
+pub const SRGB_TEX_UNIT: u32 = 10;
 
 pub struct RenderAllocs {
 	pub vert_alloc: Rc<Allocator<Vertex>>,
@@ -12,11 +36,23 @@ pub struct RenderAllocs {
 	pub instance_alloc: Rc<Allocator<Instance>>,
 	pub tex: Texture2DArray,
 	// TODO: make non-atomic, since this struct is !Sync
-	pub tex_free: AtomicI32,
+	tex_free: AtomicI32,
+	/// Layer of `tex` each texture asset was uploaded to
+	pub tex_layers: RefCell<HashMap<String, i32>>,
+	/// Base color and emissive maps
+	pub srgb_tex: SrgbTextureArray,
+	srgb_free: Cell<i32>,
+	/// Layer of `srgb_tex` each color map was uploaded to
+	srgb_layers: RefCell<HashMap<String, i32>>,
+	pub materials: Rc<SlotBuffer<MaterialUniform>>,
+	/// Model transform of each instance
+	pub transforms: Rc<SlotBuffer<Matrix4<f32>>>,
+	/// Every mesh, for the GPU culling pass. Meshes enable their draw while their LOD is the one shown.
+	pub draws: Rc<SlotBuffer<DrawInput>>,
 }
 impl RenderAllocs {
 	pub fn new(ctx: &Rc<Ctx>) -> Rc<Self> {
-		let tex = Texture2DArray::new(ctx, [1024, 1024, 64].into());
+		let tex = Texture2DArray::new(ctx, [TEX_SIZE as _, TEX_SIZE as _, TEX_LAYERS as _].into());
 		tex.min_filter(Filter::Linear);
 		tex.mag_filter(Filter::Linear);
 
@@ -28,6 +64,13 @@ impl RenderAllocs {
 			instance_alloc: Allocator::new(ctx, size),
 			tex,
 			tex_free: AtomicI32::default(),
+			tex_layers: RefCell::default(),
+			srgb_tex: SrgbTextureArray::new(TEX_SIZE, TEX_LAYERS),
+			srgb_free: Cell::default(),
+			srgb_layers: RefCell::default(),
+			materials: SlotBuffer::new(MaterialUniform::default()),
+			transforms: SlotBuffer::new(Matrix4::identity()),
+			draws: SlotBuffer::new(DrawInput::default()),
 		})
 	}
 
@@ -43,7 +86,113 @@ impl RenderAllocs {
 		self.instance_alloc.alloc_slice(slice::from_ref(instance))
 	}
 
+	pub fn alloc_material(&self, material: MaterialUniform) -> MaterialAllocation {
+		self.materials.alloc(material)
+	}
+
+	pub fn alloc_transform(&self, transform: Matrix4<f32>) -> TransformAllocation {
+		self.transforms.alloc(transform)
+	}
+
+	pub fn alloc_draw(&self, draw: DrawInput) -> DrawAllocation {
+		self.draws.alloc(draw)
+	}
+
+	/// Layer of `srgb_tex` if `srgb`, otherwise of `tex`, the texture asset `name` was uploaded to, or -1.
+	pub fn tex_layer(&self, name: &str, srgb: bool) -> i32 {
+		let layers = if srgb { &self.srgb_layers } else { &self.tex_layers };
+		layers.borrow().get(name).copied().unwrap_or(-1)
+	}
+
+	/// Layer of `tex` for the texture asset `name`, reusing the one it had if it was uploaded before. Fails once every
+	/// layer is taken.
+	pub fn data_layer(&self, name: String) -> Result<i32, String> {
+		let mut layers = self.tex_layers.borrow_mut();
+		if let Some(&layer) = layers.get(&name) {
+			return Ok(layer);
+		}
+		let layer = self.tex_free.load(Ordering::Relaxed);
+		if layer >= TEX_LAYERS as i32 {
+			return Err(format!("{}: all {} texture layers are in use", name, TEX_LAYERS));
+		}
+		self.tex_free.store(layer + 1, Ordering::Relaxed);
+		layers.insert(name, layer);
+		Ok(layer)
+	}
+
+	/// Layer of `srgb_tex` for the color map `name`, reusing the one it had if it was uploaded before. Fails once
+	/// every layer is taken.
+	pub fn srgb_layer(&self, name: String) -> Result<i32, String> {
+		let mut layers = self.srgb_layers.borrow_mut();
+		if let Some(&layer) = layers.get(&name) {
+			return Ok(layer);
+		}
+		let layer = self.srgb_free.get();
+		if layer >= TEX_LAYERS as i32 {
+			return Err(format!("{}: all {} sRGB texture layers are in use", name, TEX_LAYERS));
+		}
+		self.srgb_free.set(layer + 1);
+		layers.insert(name, layer);
+		Ok(layer)
+	}
+
 	pub fn ctx(&self) -> &Rc<Ctx> {
 		self.vert_alloc.ctx()
 	}
 }
+
+/// `SRGB8_ALPHA8` texture array, so color maps are decoded to linear when sampled. glrs arrays are always linear.
+/// Stays bound to `SRGB_TEX_UNIT`.
+pub struct SrgbTextureArray {
+	id: GLuint,
+}
+impl SrgbTextureArray {
+	fn new(size: u32, layers: u32) -> Self {
+		let mut id = 0;
+		unsafe {
+			gl::GenTextures(1, &mut id);
+			gl::ActiveTexture(gl::TEXTURE0 + SRGB_TEX_UNIT);
+			gl::BindTexture(gl::TEXTURE_2D_ARRAY, id);
+			gl::TexStorage3D(gl::TEXTURE_2D_ARRAY, 1, gl::SRGB8_ALPHA8, size as _, size as _, layers as _);
+			gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_MIN_FILTER, gl::LINEAR as _);
+			gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_MAG_FILTER, gl::LINEAR as _);
+			gl::ActiveTexture(gl::TEXTURE0);
+		}
+		Self { id }
+	}
+
+	/// Writes `img` to the top left of `layer`.
+	pub fn upload(&self, layer: i32, img: &RgbaImage) {
+		let (w, h) = img.dimensions();
+		unsafe {
+			gl::ActiveTexture(gl::TEXTURE0 + SRGB_TEX_UNIT);
+			gl::BindTexture(gl::TEXTURE_2D_ARRAY, self.id);
+			gl::TexSubImage3D(
+				gl::TEXTURE_2D_ARRAY,
+				0,
+				0,
+				0,
+				layer,
+				w as _,
+				h as _,
+				1,
+				gl::RGBA,
+				gl::UNSIGNED_BYTE,
+				img.as_raw().as_ptr() as *const _,
+			);
+			gl::ActiveTexture(gl::TEXTURE0);
+		}
+	}
+}
+impl Drop for SrgbTextureArray {
+	fn drop(&mut self) {
+		unsafe { gl::DeleteTextures(1, &self.id) };
+	}
+}
+
+/// Slot in `RenderAllocs::materials`, freed on drop.
+pub type MaterialAllocation = Slot<MaterialUniform>;
+/// Slot in `RenderAllocs::transforms`, freed on drop.
+pub type TransformAllocation = Slot<Matrix4<f32>>;
+/// Slot in `RenderAllocs::draws`, disabled and freed on drop.
+pub type DrawAllocation = Slot<DrawInput>;
@@ -0,0 +1,290 @@
+use crate::{
+	components::model::{Instance, Vertex},
+	systems::render::{
+		allocs::RenderAllocs,
+		gl_version, has_extension,
+		shader::{ComputeProgram, ShaderCache},
+		storage::StorageBuffer,
+	},
+	types::{
+		camera::Camera,
+		frustum::{Bounds, Frustum},
+	},
+};
+use glrs::{
+	alloc::Allocation,
+	gl::{
+		self,
+		types::{GLenum, GLintptr, GLsizei, GLuint},
+	},
+	Ctx,
+};
+use nalgebra::Vector4;
+use std::{ffi::c_void, mem, ptr, rc::Rc};
+
+pub const CULL_SHADER: &str = "shaders/cull.comp";
+pub const HIZ_SHADER: &str = "shaders/hiz.comp";
+/// Work group sizes, matching the shaders
+const CULL_GROUP: u32 = 64;
+const HIZ_GROUP: u32 = 8;
+/// Storage buffer bindings, matching `cull.comp`
+const DRAWS_BINDING: u32 = 4;
+const COMMANDS_BINDING: u32 = 6;
+const COUNT_BINDING: u32 = 7;
+/// Texture unit the pyramid is sampled from
+const HIZ_UNIT: u32 = 2;
+/// GL 4.6, not in the generated bindings
+const PARAMETER_BUFFER: GLenum = 0x80EE;
+
+type MultiDrawElementsIndirectCount = extern "system" fn(GLenum, GLenum, *const c_void, GLintptr, GLsizei, GLsizei);
+
+/// std430 layout of one mesh in the cull shader's input
+#[allow(unused)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[repr(C)]
+pub struct DrawInput {
+	/// Bounding sphere center and radius
+	sphere: Vector4<f32>,
+	box_min: Vector4<f32>,
+	box_max: Vector4<f32>,
+	count: u32,
+	first_index: u32,
+	base_vertex: i32,
+	base_instance: u32,
+	instance_count: u32,
+	/// 0 for meshes that aren't drawn, like other LODs and freed slots
+	enabled: u32,
+	/// Index into the transforms storage buffer
+	transform: u32,
+	_pad: u32,
+}
+impl DrawInput {
+	/// Starts disabled.
+	pub fn new(
+		buf: &Allocation<Vertex>,
+		indices: &Allocation<u16>,
+		instance: &Allocation<Instance>,
+		bounds: &Bounds,
+		transform: usize,
+	) -> Self {
+		Self {
+			sphere: bounds.center.insert_row(3, bounds.radius),
+			box_min: bounds.min.insert_row(3, 1.0),
+			box_max: bounds.max.insert_row(3, 1.0),
+			count: indices.len() as _,
+			first_index: indices.offset() as _,
+			base_vertex: buf.offset() as _,
+			base_instance: instance.offset() as _,
+			instance_count: instance.len() as _,
+			enabled: 0,
+			transform: transform as _,
+			_pad: 0,
+		}
+	}
+
+	pub fn enabled(&self) -> bool {
+		self.enabled != 0
+	}
+
+	pub fn set_enabled(&mut self, enabled: bool) {
+		self.enabled = enabled as _;
+	}
+}
+
+/// `DrawElementsIndirectCommand`, written by the cull shader
+#[allow(unused)]
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+struct DrawCommand {
+	count: u32,
+	instance_count: u32,
+	first_index: u32,
+	base_vertex: i32,
+	base_instance: u32,
+}
+
+/// Culls draws against the frustum and last frame's depth on the GPU, writing the survivors straight into an
+/// indirect buffer for `glMultiDrawElementsIndirectCount`. Needs GL 4.6 or `ARB_indirect_parameters`.
+///
+/// The draw list is `RenderAllocs::draws`, which meshes fill in as they're uploaded and switch on and off as their LOD
+/// changes, so nothing is rebuilt per frame.
+pub struct GpuCull {
+	draw_count: MultiDrawElementsIndirectCount,
+	cull: Rc<ComputeProgram>,
+	hiz: Rc<ComputeProgram>,
+	commands: StorageBuffer<DrawCommand>,
+	count: StorageBuffer<u32>,
+	pyramid: HiZPyramid,
+}
+impl GpuCull {
+	/// `None` if the driver can't draw with a GPU-written count.
+	pub fn new(ctx: &Ctx, shaders: &mut ShaderCache) -> Option<Self> {
+		// GLX hands out pointers for entry points the driver doesn't have, so check for support first
+		let name = if gl_version() >= (4, 6) {
+			"glMultiDrawElementsIndirectCount"
+		} else if has_extension("GL_ARB_indirect_parameters") {
+			"glMultiDrawElementsIndirectCountARB"
+		} else {
+			return None;
+		};
+		let draw_count = ctx.window().get_proc_address(name);
+		if draw_count.is_null() {
+			return None;
+		}
+		let draw_count = unsafe { mem::transmute::<_, MultiDrawElementsIndirectCount>(draw_count) };
+
+		let (cull, hiz) = match programs(shaders) {
+			Ok(programs) => programs,
+			Err(err) => {
+				eprintln!("GPU culling disabled: {}", err);
+				return None;
+			},
+		};
+
+		Some(Self {
+			draw_count,
+			cull,
+			hiz,
+			commands: StorageBuffer::new(),
+			count: StorageBuffer::new(),
+			pyramid: HiZPyramid::new(),
+		})
+	}
+
+	pub fn reload_shaders(&mut self, shaders: &mut ShaderCache) {
+		match programs(shaders) {
+			Ok((cull, hiz)) => {
+				self.cull = cull;
+				self.hiz = hiz;
+			},
+			Err(err) => eprintln!("failed to reload cull shaders: {}", err),
+		}
+	}
+
+	/// Culls every enabled draw and draws what's left with the bound program. Only uploads the draws that changed,
+	/// the transforms must already be bound.
+	pub fn draw(&mut self, allocs: &RenderAllocs, cam: &Camera) {
+		let total = allocs.draws.total();
+		self.commands.resize(total, DrawCommand::default());
+		self.count.write(&[0]);
+
+		allocs.draws.bind(DRAWS_BINDING);
+		self.commands.bind(COMMANDS_BINDING);
+		self.count.bind(COUNT_BINDING);
+
+		let frustum = Frustum::new(cam);
+		let planes: Vec<_> = frustum.planes().iter().map(|p| [p.x, p.y, p.z, p.w]).collect();
+		self.cull.set_uniform_u32("DrawTotal", total as _);
+		self.cull.set_uniform_vec4s("Planes", &planes);
+		self.cull.set_uniform_i32("PlaneCount", planes.len() as _);
+		self.cull.set_uniform_i32("HiZLevels", self.pyramid.levels);
+		self.cull.set_uniform_i32("ReversedZ", cam.reversed_z as _);
+		self.pyramid.bind_texture(HIZ_UNIT);
+		self.cull.set_uniform_i32("HiZ", HIZ_UNIT as _);
+		self.cull.dispatch([groups(total as _, CULL_GROUP), 1, 1]);
+
+		unsafe {
+			gl::MemoryBarrier(gl::COMMAND_BARRIER_BIT);
+			gl::BindBuffer(gl::DRAW_INDIRECT_BUFFER, self.commands.id());
+			gl::BindBuffer(PARAMETER_BUFFER, self.count.id());
+			(self.draw_count)(
+				gl::TRIANGLES,
+				gl::UNSIGNED_SHORT,
+				ptr::null(),
+				0,
+				total as _,
+				mem::size_of::<DrawCommand>() as _,
+			);
+			gl::BindBuffer(PARAMETER_BUFFER, 0);
+		}
+	}
+
+	/// Builds next frame's occlusion pyramid from the depth buffer just drawn.
+	pub fn build_pyramid(&mut self, depth: GLuint, size: [u32; 2], reversed_z: bool) {
+		self.pyramid.build(&self.hiz, depth, size, reversed_z);
+	}
+}
+
+fn programs(shaders: &mut ShaderCache) -> Result<(Rc<ComputeProgram>, Rc<ComputeProgram>), String> {
+	Ok((shaders.get_compute(CULL_SHADER, &[])?, shaders.get_compute(HIZ_SHADER, &[])?))
+}
+
+fn groups(n: u32, size: u32) -> u32 {
+	(n + size - 1) / size
+}
+
+/// Mip chain of the farthest depth in each texel's footprint. Level 0 matches the depth buffer.
+struct HiZPyramid {
+	tex: GLuint,
+	size: [u32; 2],
+	/// 0 until the first build, which turns occlusion culling off
+	levels: i32,
+}
+impl HiZPyramid {
+	fn new() -> Self {
+		Self { tex: 0, size: [0, 0], levels: 0 }
+	}
+
+	fn bind_texture(&self, unit: u32) {
+		unsafe {
+			gl::ActiveTexture(gl::TEXTURE0 + unit);
+			gl::BindTexture(gl::TEXTURE_2D, self.tex);
+			gl::ActiveTexture(gl::TEXTURE0);
+		}
+	}
+
+	fn build(&mut self, hiz: &ComputeProgram, depth: GLuint, size: [u32; 2], reversed_z: bool) {
+		if size != self.size {
+			self.resize(size);
+		}
+
+		hiz.set_uniform_i32("ReversedZ", reversed_z as _);
+		hiz.set_uniform_i32("Depth", HIZ_UNIT as _);
+		let [mut width, mut height] = size;
+		for level in 0..self.levels {
+			unsafe {
+				gl::ActiveTexture(gl::TEXTURE0 + HIZ_UNIT);
+				if level == 0 {
+					gl::BindTexture(gl::TEXTURE_2D, depth);
+				} else {
+					gl::BindTexture(gl::TEXTURE_2D, self.tex);
+				}
+				gl::ActiveTexture(gl::TEXTURE0);
+				gl::BindImageTexture(0, self.tex, level, gl::FALSE, 0, gl::WRITE_ONLY, gl::R32F);
+			}
+			hiz.set_uniform_i32("SourceLevel", (level - 1).max(0));
+			hiz.set_uniform_i32("Copy", (level == 0) as _);
+			hiz.dispatch([groups(width, HIZ_GROUP), groups(height, HIZ_GROUP), 1]);
+			unsafe { gl::MemoryBarrier(gl::TEXTURE_FETCH_BARRIER_BIT | gl::SHADER_IMAGE_ACCESS_BARRIER_BIT) };
+			width = (width / 2).max(1);
+			height = (height / 2).max(1);
+		}
+	}
+
+	fn resize(&mut self, size: [u32; 2]) {
+		self.destroy();
+		self.size = size;
+		self.levels = 32 - size[0].max(size[1]).leading_zeros() as i32;
+		unsafe {
+			gl::GenTextures(1, &mut self.tex);
+			gl::BindTexture(gl::TEXTURE_2D, self.tex);
+			gl::TexStorage2D(gl::TEXTURE_2D, self.levels, gl::R32F, size[0] as _, size[1] as _);
+			gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST_MIPMAP_NEAREST as _);
+			gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as _);
+			gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as _);
+			gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as _);
+		}
+	}
+
+	fn destroy(&mut self) {
+		if self.tex != 0 {
+			unsafe { gl::DeleteTextures(1, &self.tex) };
+			self.tex = 0;
+		}
+	}
+}
+impl Drop for HiZPyramid {
+	fn drop(&mut self) {
+		self.destroy();
+	}
+}
@@ -0,0 +1,131 @@
+use crate::types::{
+	debug_draw::{DebugDraw, DebugVertex},
+	ui::Ui,
+};
+use glrs::{
+	alloc::{Allocator, AllocatorAbstract},
+	gl::{self, types::GLenum},
+	shader::ShaderProgram,
+	vertex::VertexArray,
+	Ctx,
+};
+use nalgebra::Vector3;
+use std::{mem, rc::Rc};
+
+pub const DEBUG_VERTEX_SHADER: &str = "shaders/debug.vert";
+pub const DEBUG_FRAGMENT_SHADER: &str = "shaders/debug.frag";
+pub const UI_VERTEX_SHADER: &str = "shaders/ui.vert";
+/// Size of the allocators lines, UI and text are streamed through
+pub const STREAM_SIZE: usize = 4 * 1024 * 1024;
+
+/// Draws `DebugDraw`'s lines and the debug UI. Vertices are allocated each draw and freed right after.
+pub struct DebugRenderer {
+	vertices: Rc<Allocator<DebugVertex>>,
+	vao: VertexArray,
+}
+impl DebugRenderer {
+	pub fn new(ctx: &Rc<Ctx>) -> Self {
+		let vertices = Allocator::new(ctx, STREAM_SIZE);
+		let mut vao = VertexArray::new(ctx);
+		vao.enable_vertices::<DebugVertex>(0);
+		vao.vertex_buffer(0, &vertices);
+		Self { vertices, vao }
+	}
+
+	/// Draws the depth tested lines, then the rest over everything. Expects the depth state set up for the camera.
+	pub fn draw(&mut self, ctx: &Ctx, program: &Rc<ShaderProgram>, debug: &DebugDraw) {
+		let (tested, untested) = (debug.vertices(true), debug.vertices(false));
+		if tested.is_empty() && untested.is_empty() {
+			return;
+		}
+
+		ctx.use_program(program);
+		ctx.bind_vertex_array(&self.vao);
+		unsafe { gl::DepthMask(gl::FALSE) };
+		self.draw_vertices(gl::LINES, tested);
+		unsafe { gl::Disable(gl::DEPTH_TEST) };
+		self.draw_vertices(gl::LINES, untested);
+		unsafe {
+			gl::Enable(gl::DEPTH_TEST);
+			gl::DepthMask(gl::TRUE);
+		}
+	}
+
+	/// Draws `ui` blended over whatever framebuffer is bound, which should be `ui.size()` pixels.
+	pub fn draw_ui(&mut self, ctx: &Ctx, program: &Rc<ShaderProgram>, ui: &Ui) {
+		let [width, height] = ui.size();
+		let to_ndc = |vertex: &DebugVertex| {
+			let pos = Vector3::new(vertex.pos.x / width * 2.0 - 1.0, 1.0 - vertex.pos.y / height * 2.0, 0.0);
+			DebugVertex { pos, color: vertex.color }
+		};
+		let triangles: Vec<_> = ui.triangles().iter().map(to_ndc).collect();
+		let lines: Vec<_> = ui.lines().iter().map(to_ndc).collect();
+
+		ctx.use_program(program);
+		ctx.bind_vertex_array(&self.vao);
+		unsafe {
+			gl::Disable(gl::DEPTH_TEST);
+			gl::Enable(gl::BLEND);
+			gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+		}
+		self.draw_vertices(gl::TRIANGLES, &triangles);
+		self.draw_vertices(gl::LINES, &lines);
+		unsafe {
+			gl::Disable(gl::BLEND);
+			gl::Enable(gl::DEPTH_TEST);
+		}
+	}
+
+	/// Draws with the vertex array bound, in as many batches as it takes to fit the allocator.
+	fn draw_vertices(&self, mode: GLenum, vertices: &[DebugVertex]) {
+		let per_primitive = if mode == gl::TRIANGLES { 3 } else { 2 };
+		for batch in stream_batches(vertices, per_primitive) {
+			let alloc = self.vertices.alloc_slice(batch);
+			unsafe { gl::DrawArrays(mode, alloc.offset() as _, alloc.len() as _) };
+		}
+	}
+}
+
+/// Splits `vertices` into batches that each fit in a `STREAM_SIZE` allocator, without splitting primitives of
+/// `per_primitive` vertices.
+pub fn stream_batches<T>(vertices: &[T], per_primitive: usize) -> impl Iterator<Item = &[T]> {
+	let max = STREAM_SIZE / mem::size_of::<T>() / per_primitive * per_primitive;
+	vertices.chunks(max)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use nalgebra::Vector4;
+
+	#[test]
+	fn large_batches_are_split() {
+		let mut debug = DebugDraw::new();
+		let color = Vector4::new(1.0, 0.0, 0.0, 1.0);
+		let lines = STREAM_SIZE / mem::size_of::<DebugVertex>();
+		for i in 0..lines {
+			debug.line(Vector3::new(i as f32, 0.0, 0.0), Vector3::new(i as f32, 1.0, 0.0), color);
+		}
+		let vertices = debug.vertices(true);
+		assert!(mem::size_of_val(vertices) > STREAM_SIZE);
+
+		let batches: Vec<_> = stream_batches(vertices, 2).collect();
+		assert_eq!(batches.len(), 2);
+		for batch in &batches {
+			assert!(mem::size_of_val(*batch) <= STREAM_SIZE);
+			assert_eq!(batch.len() % 2, 0);
+		}
+		assert_eq!(batches.iter().map(|batch| batch.len()).sum::<usize>(), vertices.len());
+		assert_eq!(batches[1][0].pos, Vector3::new(batches[0].len() as f32 / 2.0, 0.0, 0.0));
+	}
+
+	#[test]
+	fn triangles_stay_whole() {
+		let vertices = vec![0u8; STREAM_SIZE + 5];
+		let batches: Vec<_> = stream_batches(&vertices, 3).collect();
+		assert!(batches.iter().all(|batch| batch.len() <= STREAM_SIZE));
+		assert!(batches[..batches.len() - 1].iter().all(|batch| batch.len() % 3 == 0));
+		assert_eq!(batches.iter().map(|batch| batch.len()).sum::<usize>(), STREAM_SIZE + 5);
+		assert_eq!(stream_batches(&[] as &[u8], 3).count(), 0);
+	}
+}
@@ -0,0 +1,335 @@
+use crate::{
+	components::{
+		light::LightsUniform,
+		skybox::{SkyImage, SkySource, Skybox},
+	},
+	systems::render::shader::{ComputeProgram, ShaderCache},
+	types::assets::Assets,
+};
+use glrs::{
+	buffer::DynamicBuffer,
+	gl::{self, types::*},
+	shader::ShaderProgram,
+	vertex::VertexArray,
+	Ctx,
+};
+use std::rc::Rc;
+
+const SKY_VERTEX_SHADER: &str = "shaders/sky.vert";
+const SKY_FRAGMENT_SHADER: &str = "shaders/sky.frag";
+const EQUIRECT_SHADER: &str = "shaders/ibl/equirect.comp";
+const IRRADIANCE_SHADER: &str = "shaders/ibl/irradiance.comp";
+const PREFILTER_SHADER: &str = "shaders/ibl/prefilter.comp";
+const BRDF_SHADER: &str = "shaders/ibl/brdf.comp";
+/// Work group size of the compute shaders
+const GROUP: u32 = 8;
+/// Face size equirectangular images are resampled to
+const ENVIRONMENT_SIZE: u32 = 512;
+const IRRADIANCE_SIZE: u32 = 32;
+const PREFILTERED_SIZE: u32 = 128;
+/// Roughness goes from 0 at level 0 to 1 at the last level
+const PREFILTERED_LEVELS: i32 = 5;
+const BRDF_SIZE: u32 = 128;
+/// Texture units, after the post-process chain's
+pub const ENVIRONMENT_UNIT: u32 = 6;
+pub const IRRADIANCE_UNIT: u32 = 7;
+pub const PREFILTERED_UNIT: u32 = 8;
+pub const BRDF_UNIT: u32 = 9;
+
+struct Programs {
+	/// Indexed by reversed-Z
+	sky: [Rc<ShaderProgram>; 2],
+	equirect: Rc<ComputeProgram>,
+	irradiance: Rc<ComputeProgram>,
+	prefilter: Rc<ComputeProgram>,
+	brdf: Rc<ComputeProgram>,
+}
+impl Programs {
+	fn new(shaders: &mut ShaderCache, lightbuf: &Rc<DynamicBuffer<LightsUniform>>) -> Result<Self, String> {
+		let sky = |shaders: &mut ShaderCache, depth| {
+			let program = shaders.get(SKY_VERTEX_SHADER, SKY_FRAGMENT_SHADER, &[depth])?;
+			program.set_uniform_i32("environment", ENVIRONMENT_UNIT as _);
+			// For the intensity
+			program.bind_buffer_range("Lights", lightbuf.clone());
+			Ok::<_, String>(program)
+		};
+		Ok(Self {
+			sky: [sky(shaders, "DEPTH 1.0")?, sky(shaders, "DEPTH 0.0")?],
+			equirect: shaders.get_compute(EQUIRECT_SHADER, &[])?,
+			irradiance: shaders.get_compute(IRRADIANCE_SHADER, &[])?,
+			prefilter: shaders.get_compute(PREFILTER_SHADER, &[])?,
+			brdf: shaders.get_compute(BRDF_SHADER, &[])?,
+		})
+	}
+}
+
+/// Cubemaps built from the skybox: the sky itself, plus irradiance and prefiltered specular maps for image-based
+/// lighting. Images load synchronously when the skybox changes, so expect a hitch.
+pub struct Environment {
+	assets: Assets,
+	programs: Programs,
+	lightbuf: Rc<DynamicBuffer<LightsUniform>>,
+	/// What the maps were built from, even if that failed
+	source: Option<SkySource>,
+	intensity: f32,
+	/// Cubemaps, 0 without a skybox
+	environment: GLuint,
+	irradiance: GLuint,
+	prefiltered: GLuint,
+	/// Doesn't depend on the skybox, so it's built once
+	brdf: GLuint,
+	vao: VertexArray,
+}
+impl Environment {
+	pub fn new(
+		ctx: &Rc<Ctx>,
+		shaders: &mut ShaderCache,
+		assets: &Assets,
+		lightbuf: &Rc<DynamicBuffer<LightsUniform>>,
+	) -> Self {
+		let programs = Programs::new(shaders, lightbuf).unwrap_or_else(|e| panic!("{}", e));
+		unsafe { gl::Enable(gl::TEXTURE_CUBE_MAP_SEAMLESS) };
+		let mut env = Self {
+			assets: assets.clone(),
+			programs,
+			lightbuf: lightbuf.clone(),
+			source: None,
+			intensity: 0.0,
+			environment: 0,
+			irradiance: 0,
+			prefiltered: 0,
+			brdf: 0,
+			vao: VertexArray::new(ctx),
+		};
+		env.build_brdf();
+		env
+	}
+
+	pub fn reload_shaders(&mut self, shaders: &mut ShaderCache) {
+		match Programs::new(shaders, &self.lightbuf) {
+			Ok(programs) => {
+				self.programs = programs;
+				self.build_brdf();
+				// Rebuilt on the next update
+				self.source = None;
+			},
+			Err(err) => eprintln!("failed to reload environment shaders: {}", err),
+		}
+	}
+
+	/// Rebuilds the maps if `skybox` changed. Failures are logged and leave the scene without a sky.
+	pub fn update(&mut self, skybox: Option<&Skybox>) {
+		self.intensity = skybox.map_or(0.0, |skybox| skybox.intensity);
+		let source = skybox.map(|skybox| &skybox.source);
+		if source == self.source.as_ref() {
+			return;
+		}
+		self.destroy_maps();
+		self.source = source.cloned();
+		if let Some(source) = source {
+			if let Err(err) = self.build(source) {
+				eprintln!("failed to load skybox: {}", err);
+				self.destroy_maps();
+			}
+		}
+	}
+
+	/// Scale for the environment's light, 0 if there is none. Goes in `LightsUniform::ambient`'s w.
+	pub fn intensity(&self) -> f32 {
+		if self.environment == 0 { 0.0 } else { self.intensity }
+	}
+
+	/// Binds the maps the main shader samples.
+	pub fn bind_textures(&self) {
+		let textures = [
+			(ENVIRONMENT_UNIT, gl::TEXTURE_CUBE_MAP, self.environment),
+			(IRRADIANCE_UNIT, gl::TEXTURE_CUBE_MAP, self.irradiance),
+			(PREFILTERED_UNIT, gl::TEXTURE_CUBE_MAP, self.prefiltered),
+			(BRDF_UNIT, gl::TEXTURE_2D, self.brdf),
+		];
+		unsafe {
+			for &(unit, target, tex) in &textures {
+				gl::ActiveTexture(gl::TEXTURE0 + unit);
+				gl::BindTexture(target, tex);
+			}
+			gl::ActiveTexture(gl::TEXTURE0);
+		}
+	}
+
+	/// Fills the background left after the opaque pass. Expects the depth state set up for the camera, and the lights
+	/// uniform written with `intensity`.
+	pub fn draw_sky(&self, ctx: &Ctx, reversed_z: bool) {
+		if self.environment == 0 {
+			return;
+		}
+		let program = &self.programs.sky[reversed_z as usize];
+		ctx.use_program(program);
+		ctx.bind_vertex_array(&self.vao);
+		unsafe {
+			// Only where nothing was drawn, which is still at the cleared far depth
+			gl::DepthFunc(if reversed_z { gl::GEQUAL } else { gl::LEQUAL });
+			gl::DepthMask(gl::FALSE);
+			gl::DrawArrays(gl::TRIANGLES, 0, 3);
+			gl::DepthMask(gl::TRUE);
+			gl::DepthFunc(if reversed_z { gl::GREATER } else { gl::LESS });
+		}
+	}
+
+	fn build(&mut self, source: &SkySource) -> Result<(), String> {
+		self.environment = match source {
+			SkySource::Equirect(name) => {
+				let img = SkyImage::load(&self.assets, name)?;
+				self.equirect_to_cube(&img)
+			},
+			SkySource::Cubemap(names) => {
+				let faces: Result<Vec<_>, _> = names.iter().map(|name| SkyImage::load(&self.assets, name)).collect();
+				from_faces(&faces?)?
+			},
+		};
+		unsafe {
+			gl::BindTexture(gl::TEXTURE_CUBE_MAP, self.environment);
+			gl::GenerateMipmap(gl::TEXTURE_CUBE_MAP);
+			gl::BindTexture(gl::TEXTURE_CUBE_MAP, 0);
+		}
+		self.bind_textures();
+
+		self.irradiance = cubemap(IRRADIANCE_SIZE, 1);
+		self.programs.irradiance.set_uniform_i32("Environment", ENVIRONMENT_UNIT as _);
+		dispatch_cube(&self.programs.irradiance, self.irradiance, 0, IRRADIANCE_SIZE);
+
+		self.prefiltered = cubemap(PREFILTERED_SIZE, PREFILTERED_LEVELS);
+		self.programs.prefilter.set_uniform_i32("Environment", ENVIRONMENT_UNIT as _);
+		for level in 0..PREFILTERED_LEVELS {
+			let roughness = level as f32 / (PREFILTERED_LEVELS - 1) as f32;
+			self.programs.prefilter.set_uniform_f32("Roughness", roughness);
+			dispatch_cube(&self.programs.prefilter, self.prefiltered, level, PREFILTERED_SIZE >> level);
+		}
+		self.bind_textures();
+		Ok(())
+	}
+
+	fn equirect_to_cube(&self, img: &SkyImage) -> GLuint {
+		let mut source = 0;
+		unsafe {
+			gl::GenTextures(1, &mut source);
+			gl::BindTexture(gl::TEXTURE_2D, source);
+			gl::TexImage2D(
+				gl::TEXTURE_2D,
+				0,
+				gl::RGB16F as _,
+				img.width as _,
+				img.height as _,
+				0,
+				gl::RGB,
+				gl::FLOAT,
+				img.pixels.as_ptr() as *const _,
+			);
+			gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as _);
+			gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as _);
+			gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::REPEAT as _);
+			gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as _);
+			gl::ActiveTexture(gl::TEXTURE0 + ENVIRONMENT_UNIT);
+			gl::BindTexture(gl::TEXTURE_2D, source);
+			gl::ActiveTexture(gl::TEXTURE0);
+		}
+
+		let env = cubemap(ENVIRONMENT_SIZE, levels(ENVIRONMENT_SIZE));
+		self.programs.equirect.set_uniform_i32("Source", ENVIRONMENT_UNIT as _);
+		dispatch_cube(&self.programs.equirect, env, 0, ENVIRONMENT_SIZE);
+		unsafe { gl::DeleteTextures(1, &source) };
+		env
+	}
+
+	fn build_brdf(&mut self) {
+		if self.brdf == 0 {
+			unsafe {
+				gl::GenTextures(1, &mut self.brdf);
+				gl::BindTexture(gl::TEXTURE_2D, self.brdf);
+				gl::TexStorage2D(gl::TEXTURE_2D, 1, gl::RG16F, BRDF_SIZE as _, BRDF_SIZE as _);
+				gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as _);
+				gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as _);
+				gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as _);
+				gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as _);
+				gl::BindTexture(gl::TEXTURE_2D, 0);
+			}
+		}
+		unsafe { gl::BindImageTexture(0, self.brdf, 0, gl::FALSE, 0, gl::WRITE_ONLY, gl::RG16F) };
+		self.programs.brdf.dispatch([groups(BRDF_SIZE), groups(BRDF_SIZE), 1]);
+		unsafe { gl::MemoryBarrier(gl::TEXTURE_FETCH_BARRIER_BIT) };
+		self.bind_textures();
+	}
+
+	fn destroy_maps(&mut self) {
+		for tex in &mut [&mut self.environment, &mut self.irradiance, &mut self.prefiltered] {
+			if **tex != 0 {
+				unsafe { gl::DeleteTextures(1, &**tex) };
+				**tex = 0;
+			}
+		}
+	}
+}
+impl Drop for Environment {
+	fn drop(&mut self) {
+		self.destroy_maps();
+		unsafe { gl::DeleteTextures(1, &self.brdf) };
+	}
+}
+
+/// Uploads six square faces of the same size, with a full mip chain.
+fn from_faces(faces: &[SkyImage]) -> Result<GLuint, String> {
+	let size = faces[0].width;
+	if faces.iter().any(|face| face.width != size || face.height != size) {
+		return Err("cubemap faces must be square and the same size".to_owned());
+	}
+	let env = cubemap(size, levels(size));
+	unsafe {
+		gl::BindTexture(gl::TEXTURE_CUBE_MAP, env);
+		for (i, face) in faces.iter().enumerate() {
+			gl::TexSubImage2D(
+				gl::TEXTURE_CUBE_MAP_POSITIVE_X + i as u32,
+				0,
+				0,
+				0,
+				size as _,
+				size as _,
+				gl::RGB,
+				gl::FLOAT,
+				face.pixels.as_ptr() as *const _,
+			);
+		}
+		gl::BindTexture(gl::TEXTURE_CUBE_MAP, 0);
+	}
+	Ok(env)
+}
+
+fn cubemap(size: u32, levels: i32) -> GLuint {
+	let mut tex = 0;
+	let min_filter = if levels > 1 { gl::LINEAR_MIPMAP_LINEAR } else { gl::LINEAR };
+	unsafe {
+		gl::GenTextures(1, &mut tex);
+		gl::BindTexture(gl::TEXTURE_CUBE_MAP, tex);
+		gl::TexStorage2D(gl::TEXTURE_CUBE_MAP, levels, gl::RGBA16F, size as _, size as _);
+		gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MIN_FILTER, min_filter as _);
+		gl::TexParameteri(gl::TEXTURE_CUBE_MAP, gl::TEXTURE_MAG_FILTER, gl::LINEAR as _);
+		for &wrap in &[gl::TEXTURE_WRAP_S, gl::TEXTURE_WRAP_T, gl::TEXTURE_WRAP_R] {
+			gl::TexParameteri(gl::TEXTURE_CUBE_MAP, wrap, gl::CLAMP_TO_EDGE as _);
+		}
+		gl::BindTexture(gl::TEXTURE_CUBE_MAP, 0);
+	}
+	tex
+}
+
+/// Runs `program` over every face of `level` of the cubemap `dest`, whose size at that level is `size`.
+fn dispatch_cube(program: &ComputeProgram, dest: GLuint, level: i32, size: u32) {
+	unsafe { gl::BindImageTexture(0, dest, level, gl::TRUE, 0, gl::WRITE_ONLY, gl::RGBA16F) };
+	program.dispatch([groups(size), groups(size), 6]);
+	unsafe { gl::MemoryBarrier(gl::TEXTURE_FETCH_BARRIER_BIT | gl::SHADER_IMAGE_ACCESS_BARRIER_BIT) };
+}
+
+fn levels(size: u32) -> i32 {
+	32 - size.leading_zeros() as i32
+}
+
+fn groups(n: u32) -> u32 {
+	(n + GROUP - 1) / GROUP
+}
@@ -0,0 +1,472 @@
+use crate::systems::render::target::RenderTarget;
+use glrs::gl::{self, types::*};
+use std::collections::HashMap;
+
+/// Handle to a texture or buffer declared in a `RenderGraph`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Resource(usize);
+
+/// Size and format of a transient render target.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TextureDesc {
+	pub size: [u32; 2],
+	/// Sized color format like `gl::RGBA16F`. Transient targets always get a depth attachment too.
+	pub format: GLenum,
+}
+
+/// How a pass uses a resource. Decides the barriers needed after shader writes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Access {
+	/// Sampled as a texture
+	Sampled,
+	/// Rendered into as a framebuffer attachment
+	Attachment,
+	/// Image load/store
+	Image,
+	/// Shader storage buffer
+	Storage,
+	/// Indirect draw commands or parameters
+	Indirect,
+	Uniform,
+}
+impl Access {
+	/// Writes that need a `glMemoryBarrier` before anything else sees them
+	fn incoherent(self) -> bool {
+		self == Access::Image || self == Access::Storage
+	}
+
+	/// Barrier making incoherent writes visible to this kind of access
+	fn barrier(self) -> GLbitfield {
+		match self {
+			Access::Sampled => gl::TEXTURE_FETCH_BARRIER_BIT,
+			Access::Attachment => gl::FRAMEBUFFER_BARRIER_BIT,
+			Access::Image => gl::SHADER_IMAGE_ACCESS_BARRIER_BIT,
+			Access::Storage => gl::SHADER_STORAGE_BARRIER_BIT,
+			Access::Indirect => gl::COMMAND_BARRIER_BIT,
+			Access::Uniform => gl::UNIFORM_BARRIER_BIT,
+		}
+	}
+}
+
+#[derive(Clone, Debug)]
+struct ResourceDecl {
+	name: &'static str,
+	/// `None` for resources owned outside the graph
+	transient: Option<TextureDesc>,
+	/// Kept alive even if no pass reads it, like the window or next frame's occlusion pyramid
+	output: bool,
+}
+
+#[derive(Clone, Debug)]
+struct PassDecl {
+	name: &'static str,
+	reads: Vec<(Resource, Access)>,
+	writes: Vec<(Resource, Access)>,
+}
+
+/// What passes use which resources, without anything to run. Compiles without a GL context.
+#[derive(Clone, Debug, Default)]
+pub struct GraphLayout {
+	resources: Vec<ResourceDecl>,
+	passes: Vec<PassDecl>,
+}
+impl GraphLayout {
+	/// A target the graph allocates, which only lives while passes use it.
+	pub fn transient(&mut self, name: &'static str, desc: TextureDesc) -> Resource {
+		self.resource(ResourceDecl { name, transient: Some(desc), output: false })
+	}
+
+	/// Something owned outside the graph, declared so passes using it get ordered.
+	pub fn import(&mut self, name: &'static str) -> Resource {
+		self.resource(ResourceDecl { name, transient: None, output: false })
+	}
+
+	/// Marks `resource` as a result of the frame. Passes that don't lead to an output are pruned.
+	pub fn output(&mut self, resource: Resource) {
+		self.resources[resource.0].output = true;
+	}
+
+	/// Declares a pass, returning its index.
+	pub fn pass(&mut self, name: &'static str, reads: &[(Resource, Access)], writes: &[(Resource, Access)]) -> usize {
+		self.passes.push(PassDecl { name, reads: reads.to_vec(), writes: writes.to_vec() });
+		self.passes.len() - 1
+	}
+
+	fn resource(&mut self, decl: ResourceDecl) -> Resource {
+		self.resources.push(decl);
+		Resource(self.resources.len() - 1)
+	}
+
+	/// Prunes, orders and allocates. Every writer of a resource runs before its readers, and writers of the same
+	/// resource run in the order they were declared.
+	pub fn compile(&self) -> Result<CompiledGraph, String> {
+		let kept = self.prune();
+
+		// Edges from each writer to later writers and to every reader of the same resource
+		let mut writers: HashMap<Resource, Vec<usize>> = HashMap::new();
+		for &pass in &kept {
+			for &(res, _) in &self.passes[pass].writes {
+				writers.entry(res).or_default().push(pass);
+			}
+		}
+		let mut edges = vec![vec![]; self.passes.len()];
+		let mut incoming = vec![0; self.passes.len()];
+		let mut edge = |from: usize, to: usize| {
+			if from != to && !edges[from].contains(&to) {
+				edges[from].push(to);
+				incoming[to] += 1;
+			}
+		};
+		for &pass in &kept {
+			let decl = &self.passes[pass];
+			for &(res, _) in &decl.reads {
+				match writers.get(&res) {
+					Some(writers) => writers.iter().for_each(|&writer| edge(writer, pass)),
+					None if self.resources[res.0].transient.is_some() => {
+						return Err(format!("pass {} reads {} before anything writes it", decl.name, self.name(res)));
+					},
+					None => (),
+				}
+			}
+			for &(res, _) in &decl.writes {
+				writers[&res].iter().filter(|&&writer| writer < pass).for_each(|&writer| edge(writer, pass));
+			}
+		}
+
+		// Kahn's algorithm, taking the earliest declared pass that's ready so the order is stable
+		let mut ready: Vec<_> = kept.iter().copied().filter(|&pass| incoming[pass] == 0).collect();
+		let mut order = vec![];
+		while !ready.is_empty() {
+			let (i, &pass) = ready.iter().enumerate().min_by_key(|&(_, &pass)| pass).unwrap();
+			ready.swap_remove(i);
+			order.push(pass);
+			for &next in &edges[pass] {
+				incoming[next] -= 1;
+				if incoming[next] == 0 {
+					ready.push(next);
+				}
+			}
+		}
+		if order.len() != kept.len() {
+			let stuck = kept.iter().filter(|pass| !order.contains(pass));
+			let stuck: Vec<_> = stuck.map(|&pass| self.passes[pass].name).collect();
+			return Err(format!("cycle between {}", stuck.join(", ")));
+		}
+
+		let barriers = self.barriers(&order);
+		let (slots, targets) = self.allocate(&order);
+		Ok(CompiledGraph { order, barriers, slots, targets })
+	}
+
+	/// Passes that lead to an output, in declaration order.
+	fn prune(&self) -> Vec<usize> {
+		let mut needed: Vec<_> = self.resources.iter().map(|res| res.output).collect();
+		let mut kept = vec![false; self.passes.len()];
+		let mut changed = true;
+		while changed {
+			changed = false;
+			for (pass, decl) in self.passes.iter().enumerate() {
+				if !kept[pass] && decl.writes.iter().any(|&(res, _)| needed[res.0]) {
+					kept[pass] = true;
+					changed = true;
+					for &(res, _) in &decl.reads {
+						needed[res.0] = true;
+					}
+				}
+			}
+		}
+		(0..self.passes.len()).filter(|&pass| kept[pass]).collect()
+	}
+
+	/// Bits for a `glMemoryBarrier` before each pass, covering what it uses after image or storage writes.
+	fn barriers(&self, order: &[usize]) -> Vec<GLbitfield> {
+		// Bits already issued for each resource whose last write was incoherent
+		let mut dirty: HashMap<Resource, GLbitfield> = HashMap::new();
+		order
+			.iter()
+			.map(|&pass| {
+				let decl = &self.passes[pass];
+				let mut bits = 0;
+				for &(res, access) in decl.reads.iter().chain(&decl.writes) {
+					if let Some(done) = dirty.get_mut(&res) {
+						if *done & access.barrier() == 0 {
+							bits |= access.barrier();
+							*done |= access.barrier();
+						}
+					}
+				}
+				for &(res, access) in &decl.writes {
+					if access.incoherent() {
+						dirty.insert(res, 0);
+					} else {
+						dirty.remove(&res);
+					}
+				}
+				bits
+			})
+			.collect()
+	}
+
+	/// Gives each transient a target slot, sharing slots between transients whose lifetimes don't overlap.
+	fn allocate(&self, order: &[usize]) -> (Vec<Option<usize>>, Vec<TextureDesc>) {
+		let mut lifetimes: Vec<Option<(usize, usize)>> = vec![None; self.resources.len()];
+		for (step, &pass) in order.iter().enumerate() {
+			let decl = &self.passes[pass];
+			for &(res, _) in decl.reads.iter().chain(&decl.writes) {
+				let lifetime = lifetimes[res.0].get_or_insert((step, step));
+				lifetime.1 = step;
+			}
+		}
+
+		let mut transients: Vec<_> = (0..self.resources.len())
+			.filter_map(|res| Some((res, self.resources[res].transient?, lifetimes[res]?)))
+			.collect();
+		transients.sort_by_key(|&(_, _, (first, _))| first);
+
+		let mut slots = vec![None; self.resources.len()];
+		// Description and last use of each slot
+		let mut targets: Vec<(TextureDesc, usize)> = vec![];
+		for (res, desc, (first, last)) in transients {
+			let free = targets.iter().position(|&(slot_desc, slot_last)| slot_desc == desc && slot_last < first);
+			let slot = match free {
+				Some(slot) => slot,
+				None => {
+					targets.push((desc, last));
+					targets.len() - 1
+				},
+			};
+			targets[slot].1 = last;
+			slots[res] = Some(slot);
+		}
+		(slots, targets.into_iter().map(|(desc, _)| desc).collect())
+	}
+
+	fn name(&self, res: Resource) -> &'static str {
+		self.resources[res.0].name
+	}
+}
+
+/// Result of `GraphLayout::compile`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompiledGraph {
+	/// Pass indices in the order to run them
+	pub order: Vec<usize>,
+	/// Barrier bits to issue before each pass in `order`
+	pub barriers: Vec<GLbitfield>,
+	/// Target slot of each transient resource that's used
+	pub slots: Vec<Option<usize>>,
+	pub targets: Vec<TextureDesc>,
+}
+
+/// Transient targets, kept between frames so a graph with the same shape doesn't reallocate.
+#[derive(Default)]
+pub struct TargetPool {
+	targets: Vec<RenderTarget>,
+	descs: Vec<TextureDesc>,
+}
+impl TargetPool {
+	fn prepare(&mut self, descs: &[TextureDesc]) {
+		self.targets.truncate(descs.len());
+		self.descs.truncate(descs.len());
+		for (i, desc) in descs.iter().enumerate() {
+			if i == self.targets.len() {
+				self.targets.push(RenderTarget::new(desc.size, desc.format));
+				self.descs.push(*desc);
+			} else if self.descs[i].format != desc.format {
+				self.targets[i] = RenderTarget::new(desc.size, desc.format);
+				self.descs[i] = *desc;
+			} else {
+				self.targets[i].resize(desc.size);
+				self.descs[i] = *desc;
+			}
+		}
+	}
+}
+
+/// What a pass gets when it runs.
+pub struct PassResources<'p> {
+	pool: &'p TargetPool,
+	slots: &'p [Option<usize>],
+}
+impl PassResources<'_> {
+	/// Target allocated for a transient. Panics if `resource` is imported.
+	pub fn target(&self, resource: Resource) -> &RenderTarget {
+		&self.pool.targets[self.slots[resource.0].expect("only transients have targets")]
+	}
+}
+
+type PassFn<'a> = Box<dyn FnOnce(&PassResources) + 'a>;
+
+/// A frame's passes and the resources they use. Built every frame, so passes can borrow whatever they draw.
+#[derive(Default)]
+pub struct RenderGraph<'a> {
+	layout: GraphLayout,
+	/// Indexed like `layout.passes`
+	passes: Vec<PassFn<'a>>,
+}
+impl<'a> RenderGraph<'a> {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn transient(&mut self, name: &'static str, desc: TextureDesc) -> Resource {
+		self.layout.transient(name, desc)
+	}
+
+	pub fn import(&mut self, name: &'static str) -> Resource {
+		self.layout.import(name)
+	}
+
+	pub fn output(&mut self, resource: Resource) {
+		self.layout.output(resource)
+	}
+
+	/// Adds a pass that runs `run` after what it reads is written.
+	pub fn pass(
+		&mut self,
+		name: &'static str,
+		reads: &[(Resource, Access)],
+		writes: &[(Resource, Access)],
+		run: impl FnOnce(&PassResources) + 'a,
+	) {
+		self.layout.pass(name, reads, writes);
+		self.passes.push(Box::new(run));
+	}
+
+	/// Runs the graph, allocating transients from `pool`. A graph that doesn't compile is logged and skipped.
+	pub fn execute(self, pool: &mut TargetPool) {
+		let compiled = match self.layout.compile() {
+			Ok(compiled) => compiled,
+			Err(err) => {
+				eprintln!("render graph: {}", err);
+				return;
+			},
+		};
+		pool.prepare(&compiled.targets);
+
+		let resources = PassResources { pool, slots: &compiled.slots };
+		let mut passes: Vec<_> = self.passes.into_iter().map(Some).collect();
+		for (&pass, &barrier) in compiled.order.iter().zip(&compiled.barriers) {
+			if barrier != 0 {
+				unsafe { gl::MemoryBarrier(barrier) };
+			}
+			(passes[pass].take().unwrap())(&resources);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn desc(format: GLenum) -> TextureDesc {
+		TextureDesc { size: [64, 64], format }
+	}
+
+	#[test]
+	fn prunes_passes_without_outputs() {
+		let mut layout = GraphLayout::default();
+		let window = layout.import("window");
+		let hdr = layout.transient("hdr", desc(gl::RGBA16F));
+		let unused = layout.transient("unused", desc(gl::RGBA8));
+		layout.output(window);
+		let scene = layout.pass("scene", &[], &[(hdr, Access::Attachment)]);
+		let orphan = layout.pass("orphan", &[(hdr, Access::Sampled)], &[(unused, Access::Attachment)]);
+		let post = layout.pass("post", &[(hdr, Access::Sampled)], &[(window, Access::Attachment)]);
+
+		let compiled = layout.compile().unwrap();
+		assert_eq!(compiled.order, vec![scene, post]);
+		assert!(!compiled.order.contains(&orphan));
+		assert_eq!(compiled.slots[unused.0], None);
+		assert_eq!(compiled.targets, vec![desc(gl::RGBA16F)]);
+	}
+
+	#[test]
+	fn writers_run_before_readers() {
+		let mut layout = GraphLayout::default();
+		let window = layout.import("window");
+		let hdr = layout.transient("hdr", desc(gl::RGBA16F));
+		layout.output(window);
+		// Declared before anything it reads
+		let post = layout.pass("post", &[(hdr, Access::Sampled)], &[(window, Access::Attachment)]);
+		let opaque = layout.pass("opaque", &[], &[(hdr, Access::Attachment)]);
+		let sky = layout.pass("sky", &[], &[(hdr, Access::Attachment)]);
+
+		// Writers of the same resource keep their declaration order
+		assert_eq!(layout.compile().unwrap().order, vec![opaque, sky, post]);
+	}
+
+	#[test]
+	fn cycle_is_an_error() {
+		let mut layout = GraphLayout::default();
+		let a = layout.import("a");
+		let b = layout.import("b");
+		layout.output(b);
+		layout.pass("first", &[(a, Access::Sampled)], &[(b, Access::Attachment)]);
+		layout.pass("second", &[(b, Access::Sampled)], &[(a, Access::Attachment)]);
+
+		assert_eq!(layout.compile().unwrap_err(), "cycle between first, second");
+	}
+
+	#[test]
+	fn reading_unwritten_transient_is_an_error() {
+		let mut layout = GraphLayout::default();
+		let window = layout.import("window");
+		let hdr = layout.transient("hdr", desc(gl::RGBA16F));
+		layout.output(window);
+		layout.pass("post", &[(hdr, Access::Sampled)], &[(window, Access::Attachment)]);
+
+		assert_eq!(layout.compile().unwrap_err(), "pass post reads hdr before anything writes it");
+	}
+
+	#[test]
+	fn barriers_follow_incoherent_writes() {
+		let mut layout = GraphLayout::default();
+		let window = layout.import("window");
+		let draws = layout.import("draws");
+		let pyramid = layout.import("pyramid");
+		let mask = layout.import("mask");
+		layout.output(window);
+		layout.pass("cull", &[], &[(draws, Access::Storage)]);
+		layout.pass("draw", &[(draws, Access::Indirect), (draws, Access::Storage)], &[(window, Access::Attachment)]);
+		// Same accesses again don't need another barrier
+		layout.pass("draw again", &[(draws, Access::Indirect)], &[(window, Access::Attachment)]);
+		layout.pass("hiz", &[], &[(pyramid, Access::Image)]);
+		layout.pass("read hiz", &[(pyramid, Access::Sampled)], &[(window, Access::Attachment)]);
+		// Rendering over an image write needs a barrier, but sampling after that doesn't
+		layout.pass("write mask", &[], &[(mask, Access::Image)]);
+		layout.pass("draw mask", &[], &[(mask, Access::Attachment)]);
+		layout.pass("read mask", &[(mask, Access::Sampled)], &[(window, Access::Attachment)]);
+
+		let compiled = layout.compile().unwrap();
+		assert_eq!(compiled.order, (0..8).collect::<Vec<_>>());
+		let draw = gl::COMMAND_BARRIER_BIT | gl::SHADER_STORAGE_BARRIER_BIT;
+		let expected = [0, draw, 0, 0, gl::TEXTURE_FETCH_BARRIER_BIT, 0, gl::FRAMEBUFFER_BARRIER_BIT, 0];
+		assert_eq!(compiled.barriers, expected);
+	}
+
+	#[test]
+	fn transients_share_slots_when_lifetimes_dont_overlap() {
+		let mut layout = GraphLayout::default();
+		let window = layout.import("window");
+		let first = layout.transient("first", desc(gl::RGBA16F));
+		let second = layout.transient("second", desc(gl::RGBA16F));
+		let third = layout.transient("third", desc(gl::RGBA16F));
+		let other = layout.transient("other format", desc(gl::RGBA8));
+		layout.output(window);
+		layout.pass("a", &[], &[(first, Access::Attachment)]);
+		layout.pass("b", &[(first, Access::Sampled)], &[(second, Access::Attachment)]);
+		layout.pass("c", &[(second, Access::Sampled)], &[(third, Access::Attachment)]);
+		layout.pass("d", &[(third, Access::Sampled)], &[(other, Access::Attachment)]);
+		layout.pass("e", &[(other, Access::Sampled)], &[(window, Access::Attachment)]);
+
+		let compiled = layout.compile().unwrap();
+		// `first` is done by the time `third` is written, but `second` overlaps both
+		assert_eq!(compiled.slots[first.0], Some(0));
+		assert_eq!(compiled.slots[second.0], Some(1));
+		assert_eq!(compiled.slots[third.0], Some(0));
+		// Slots only hold one format
+		assert_eq!(compiled.slots[other.0], Some(2));
+		assert_eq!(compiled.targets, vec![desc(gl::RGBA16F), desc(gl::RGBA16F), desc(gl::RGBA8)]);
+	}
+}
@@ -0,0 +1,321 @@
+use crate::{
+	systems::render::{shader::ShaderCache, target::RenderTarget},
+	types::assets::Assets,
+};
+use glrs::{
+	buffer::{Buffer, DynamicBuffer},
+	gl::{self, types::GLuint},
+	shader::ShaderProgram,
+	vertex::VertexArray,
+	Ctx,
+};
+use image::RgbaImage;
+use std::rc::Rc;
+
+const FULLSCREEN_SHADER: &str = "shaders/post/fullscreen.vert";
+const BRIGHT_SHADER: &str = "shaders/post/bright.frag";
+const BLUR_SHADER: &str = "shaders/post/blur.frag";
+const COMPOSITE_SHADER: &str = "shaders/post/composite.frag";
+const EXPOSURE_SHADER: &str = "shaders/post/exposure.frag";
+const TONEMAP_SHADER: &str = "shaders/post/tonemap.frag";
+const GRADE_SHADER: &str = "shaders/post/grade.frag";
+const FXAA_SHADER: &str = "shaders/post/fxaa.frag";
+/// First texture unit pass inputs are bound to. Lower units hold the scene textures, shadows and Hi-Z.
+const POST_UNIT: u32 = 3;
+const LUT_UNIT: u32 = POST_UNIT + 2;
+/// Size of the identity LUT used when no grading LUT is set
+const LUT_SIZE: u32 = 16;
+/// Format of the scene and every target before tonemapping
+pub const HDR_FORMAT: u32 = gl::RGBA16F;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Tonemapper {
+	Aces,
+	Reinhard,
+	/// Clamps to [0, 1]
+	None,
+}
+
+/// Which post-process passes run, and their parameters. Can be changed at any time.
+#[derive(Clone, Debug)]
+pub struct PostSettings {
+	pub bloom: bool,
+	/// Luminance above which pixels bloom
+	pub bloom_threshold: f32,
+	pub bloom_intensity: f32,
+	pub exposure: bool,
+	/// In stops
+	pub exposure_ev: f32,
+	pub tonemapper: Tonemapper,
+	pub color_grading: bool,
+	/// Asset name of a grading LUT laid out as a horizontal strip of square slices, blue increasing left to right.
+	/// Identity if `None`.
+	pub lut: Option<String>,
+	pub fxaa: bool,
+}
+impl Default for PostSettings {
+	fn default() -> Self {
+		Self {
+			bloom: true,
+			bloom_threshold: 1.0,
+			bloom_intensity: 0.3,
+			exposure: true,
+			exposure_ev: 0.0,
+			tonemapper: Tonemapper::Aces,
+			color_grading: false,
+			lut: None,
+			fxaa: true,
+		}
+	}
+}
+
+/// std140 layout of the parameters shared by every pass, matching `include/post.glsl`
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+struct PostUniform {
+	exposure: f32,
+	bloom_threshold: f32,
+	bloom_intensity: f32,
+	lut_size: f32,
+}
+
+struct PostPrograms {
+	bright: Rc<ShaderProgram>,
+	blur: [Rc<ShaderProgram>; 2],
+	composite: Rc<ShaderProgram>,
+	exposure: Rc<ShaderProgram>,
+	/// Indexed by `Tonemapper`
+	tonemap: [Rc<ShaderProgram>; 3],
+	grade: Rc<ShaderProgram>,
+	fxaa: Rc<ShaderProgram>,
+}
+impl PostPrograms {
+	fn new(shaders: &mut ShaderCache, buf: &Rc<DynamicBuffer<PostUniform>>) -> Result<Self, String> {
+		let mut get = |fragment: &str, defines: &[&str]| {
+			let program = shaders.get(FULLSCREEN_SHADER, fragment, defines)?;
+			program.set_uniform_i32("Source", POST_UNIT as _);
+			program.set_uniform_i32("Bloom", POST_UNIT as i32 + 1);
+			program.set_uniform_i32("Lut", LUT_UNIT as _);
+			program.bind_buffer_range("Post", buf.clone());
+			Ok::<_, String>(program)
+		};
+		Ok(Self {
+			bright: get(BRIGHT_SHADER, &[])?,
+			blur: [get(BLUR_SHADER, &["BLUR_HORIZONTAL"])?, get(BLUR_SHADER, &[])?],
+			composite: get(COMPOSITE_SHADER, &[])?,
+			exposure: get(EXPOSURE_SHADER, &[])?,
+			tonemap: [
+				get(TONEMAP_SHADER, &["TONEMAP_ACES"])?,
+				get(TONEMAP_SHADER, &["TONEMAP_REINHARD"])?,
+				get(TONEMAP_SHADER, &[])?,
+			],
+			grade: get(GRADE_SHADER, &[])?,
+			fxaa: get(FXAA_SHADER, &[])?,
+		})
+	}
+}
+
+/// Fullscreen passes taking the HDR scene to the image shown in the window.
+pub struct PostChain {
+	assets: Assets,
+	programs: PostPrograms,
+	buf: Rc<DynamicBuffer<PostUniform>>,
+	/// Core profiles need a bound vertex array even for attributeless draws
+	vao: VertexArray,
+	/// Ping-pongs with the scene target
+	hdr: RenderTarget,
+	/// Half resolution
+	bloom: [RenderTarget; 2],
+	ldr: [RenderTarget; 2],
+	/// `ldr` target the last run finished in
+	output: usize,
+	lut: GLuint,
+	lut_size: u32,
+	/// Asset the LUT was loaded from
+	lut_name: Option<String>,
+}
+impl PostChain {
+	pub fn new(ctx: &Rc<Ctx>, shaders: &mut ShaderCache, assets: &Assets, size: [u32; 2]) -> Self {
+		let buf = Buffer::from_val(ctx, &PostUniform::default());
+		let programs = PostPrograms::new(shaders, &buf).unwrap_or_else(|e| panic!("{}", e));
+
+		let mut lut = 0;
+		unsafe { gl::GenTextures(1, &mut lut) };
+
+		let half = half_size(size);
+		let mut post = Self {
+			assets: assets.clone(),
+			programs,
+			buf,
+			vao: VertexArray::new(ctx),
+			hdr: RenderTarget::new(size, HDR_FORMAT),
+			bloom: [RenderTarget::new(half, HDR_FORMAT), RenderTarget::new(half, HDR_FORMAT)],
+			ldr: [RenderTarget::new(size, gl::RGBA8), RenderTarget::new(size, gl::RGBA8)],
+			output: 0,
+			lut,
+			lut_size: 0,
+			lut_name: None,
+		};
+		post.upload_lut(&identity_lut(LUT_SIZE), LUT_SIZE);
+		post
+	}
+
+	pub fn resize(&mut self, size: [u32; 2]) {
+		self.hdr.resize(size);
+		for target in &mut self.bloom {
+			target.resize(half_size(size));
+		}
+		for target in &mut self.ldr {
+			target.resize(size);
+		}
+	}
+
+	pub fn reload_shaders(&mut self, shaders: &mut ShaderCache) {
+		match PostPrograms::new(shaders, &self.buf) {
+			Ok(programs) => self.programs = programs,
+			Err(err) => eprintln!("failed to reload post-process shaders: {}", err),
+		}
+	}
+
+	/// The target the last run finished in.
+	pub fn output(&self) -> &RenderTarget {
+		&self.ldr[self.output]
+	}
+
+	/// Runs the enabled passes over `scene`, which may be overwritten.
+	pub fn run(&mut self, ctx: &Ctx, settings: &PostSettings, scene: &RenderTarget) {
+		if settings.lut != self.lut_name {
+			self.load_lut(settings.lut.clone());
+		}
+		self.buf.write(&PostUniform {
+			exposure: settings.exposure_ev.exp2(),
+			bloom_threshold: settings.bloom_threshold,
+			bloom_intensity: settings.bloom_intensity,
+			lut_size: self.lut_size as _,
+		});
+
+		ctx.bind_vertex_array(&self.vao);
+		unsafe {
+			gl::Disable(gl::DEPTH_TEST);
+			gl::DepthMask(gl::FALSE);
+			gl::ActiveTexture(gl::TEXTURE0 + LUT_UNIT);
+			gl::BindTexture(gl::TEXTURE_3D, self.lut);
+			gl::ActiveTexture(gl::TEXTURE0);
+		}
+
+		let programs = &self.programs;
+		let hdr = [scene, &self.hdr];
+		let mut cur = 0;
+		if settings.bloom {
+			pass(ctx, &programs.bright, &[hdr[cur].color()], &self.bloom[0]);
+			pass(ctx, &programs.blur[0], &[self.bloom[0].color()], &self.bloom[1]);
+			pass(ctx, &programs.blur[1], &[self.bloom[1].color()], &self.bloom[0]);
+			pass(ctx, &programs.composite, &[hdr[cur].color(), self.bloom[0].color()], hdr[1 - cur]);
+			cur = 1 - cur;
+		}
+		if settings.exposure {
+			pass(ctx, &programs.exposure, &[hdr[cur].color()], hdr[1 - cur]);
+			cur = 1 - cur;
+		}
+
+		let tonemap = &programs.tonemap[settings.tonemapper as usize];
+		pass(ctx, tonemap, &[hdr[cur].color()], &self.ldr[0]);
+		let mut out = 0;
+		if settings.color_grading {
+			pass(ctx, &programs.grade, &[self.ldr[out].color()], &self.ldr[1 - out]);
+			out = 1 - out;
+		}
+		if settings.fxaa {
+			pass(ctx, &programs.fxaa, &[self.ldr[out].color()], &self.ldr[1 - out]);
+			out = 1 - out;
+		}
+		self.output = out;
+
+		unsafe { gl::DepthMask(gl::TRUE) };
+	}
+
+	/// Falls back to the identity LUT if `name` is `None` or can't be loaded.
+	fn load_lut(&mut self, name: Option<String>) {
+		let strip = name.as_ref().map(|name| {
+			let data = self.assets.read(name)?;
+			let img = image::load_from_memory(&data).map_err(|e| format!("{}: {}", name, e))?.to_rgba();
+			let size = img.height();
+			if img.width() != size * size {
+				return Err(format!("{}: expected a {}x{} strip", name, size * size, size));
+			}
+			Ok((img, size))
+		});
+		match strip {
+			Some(Ok((img, size))) => self.upload_lut(&img, size),
+			Some(Err(err)) => {
+				eprintln!("failed to load LUT: {}", err);
+				self.upload_lut(&identity_lut(LUT_SIZE), LUT_SIZE);
+			},
+			None => self.upload_lut(&identity_lut(LUT_SIZE), LUT_SIZE),
+		}
+		self.lut_name = name;
+	}
+
+	/// `strip` is `size` slices of `size` by `size` side by side, which is the same memory layout as a 3D texture
+	/// read slice by slice.
+	fn upload_lut(&mut self, strip: &RgbaImage, size: u32) {
+		let mut data = Vec::with_capacity(strip.as_raw().len());
+		for b in 0..size {
+			for g in 0..size {
+				for r in 0..size {
+					data.extend_from_slice(&strip.get_pixel(b * size + r, g).0);
+				}
+			}
+		}
+		unsafe {
+			gl::BindTexture(gl::TEXTURE_3D, self.lut);
+			gl::TexImage3D(
+				gl::TEXTURE_3D,
+				0,
+				gl::RGBA8 as _,
+				size as _,
+				size as _,
+				size as _,
+				0,
+				gl::RGBA,
+				gl::UNSIGNED_BYTE,
+				data.as_ptr() as *const _,
+			);
+			gl::TexParameteri(gl::TEXTURE_3D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as _);
+			gl::TexParameteri(gl::TEXTURE_3D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as _);
+			for &wrap in &[gl::TEXTURE_WRAP_S, gl::TEXTURE_WRAP_T, gl::TEXTURE_WRAP_R] {
+				gl::TexParameteri(gl::TEXTURE_3D, wrap, gl::CLAMP_TO_EDGE as _);
+			}
+			gl::BindTexture(gl::TEXTURE_3D, 0);
+		}
+		self.lut_size = size;
+	}
+}
+impl Drop for PostChain {
+	fn drop(&mut self) {
+		unsafe { gl::DeleteTextures(1, &self.lut) };
+	}
+}
+
+/// Draws a fullscreen triangle into `dst` with `inputs` bound from `POST_UNIT` up.
+fn pass(ctx: &Ctx, program: &Rc<ShaderProgram>, inputs: &[GLuint], dst: &RenderTarget) {
+	dst.bind();
+	ctx.use_program(program);
+	unsafe {
+		for (i, &tex) in inputs.iter().enumerate() {
+			gl::ActiveTexture(gl::TEXTURE0 + POST_UNIT + i as u32);
+			gl::BindTexture(gl::TEXTURE_2D, tex);
+		}
+		gl::ActiveTexture(gl::TEXTURE0);
+		gl::DrawArrays(gl::TRIANGLES, 0, 3);
+	}
+}
+
+fn half_size([width, height]: [u32; 2]) -> [u32; 2] {
+	[(width / 2).max(1), (height / 2).max(1)]
+}
+
+fn identity_lut(size: u32) -> RgbaImage {
+	let scale = |x: u32| (x * 255 / (size - 1)) as u8;
+	RgbaImage::from_fn(size * size, size, |x, y| image::Rgba([scale(x % size), scale(y), scale(x / size), 255]))
+}
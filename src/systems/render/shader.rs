@@ -0,0 +1,395 @@
+use crate::types::assets::{relative, Assets};
+use glrs::{
+	gl::{
+		self,
+		types::{GLchar, GLenum, GLfloat, GLint, GLuint},
+	},
+	shader::ShaderProgram,
+	Ctx,
+};
+use std::{collections::HashMap, ffi::CString, fmt::Write, path::PathBuf, ptr, rc::Rc};
+
+/// Compiled shader permutations, keyed by source files and defines.
+pub struct ShaderCache {
+	ctx: Rc<Ctx>,
+	assets: Assets,
+	entries: HashMap<ShaderKey, CacheEntry>,
+}
+impl ShaderCache {
+	pub fn new(ctx: &Rc<Ctx>, assets: &Assets) -> Self {
+		Self { ctx: ctx.clone(), assets: assets.clone(), entries: HashMap::new() }
+	}
+
+	/// Returns the program built from the assets `vertex` and `fragment` with `defines` (`"NAME"` or `"NAME VALUE"`),
+	/// compiling it on first use.
+	pub fn get(&mut self, vertex: &str, fragment: &str, defines: &[&str]) -> Result<Rc<ShaderProgram>, String> {
+		let key = ShaderKey::new(&[(gl::VERTEX_SHADER, vertex), (gl::FRAGMENT_SHADER, fragment)], defines);
+		match self.entry(key)? {
+			Program::Graphics(program) => Ok(program.clone()),
+			Program::Compute(_) => unreachable!(),
+		}
+	}
+
+	/// Like `get`, for a compute shader.
+	pub fn get_compute(&mut self, compute: &str, defines: &[&str]) -> Result<Rc<ComputeProgram>, String> {
+		let key = ShaderKey::new(&[(gl::COMPUTE_SHADER, compute)], defines);
+		match self.entry(key)? {
+			Program::Compute(program) => Ok(program.clone()),
+			Program::Graphics(_) => unreachable!(),
+		}
+	}
+
+	fn entry(&mut self, key: ShaderKey) -> Result<&Program, String> {
+		if !self.entries.contains_key(&key) {
+			let entry = compile(&self.ctx, &self.assets, &key)?;
+			self.entries.insert(key.clone(), entry);
+		}
+		Ok(&self.entries[&key].program)
+	}
+
+	/// Every file on disk, including `#include`s, used by a cached program.
+	pub fn files(&self) -> impl Iterator<Item = &PathBuf> {
+		self.entries.values().flat_map(|entry| entry.files.iter())
+	}
+
+	/// Recompiles the programs that use any of `changed`. Failures are logged and the old program is kept. Returns
+	/// true if any program was replaced, in which case callers should `get` their programs again.
+	pub fn reload(&mut self, changed: &[PathBuf]) -> bool {
+		let mut replaced = false;
+		for (key, entry) in &mut self.entries {
+			if entry.files.iter().any(|file| changed.contains(file)) {
+				match compile(&self.ctx, &self.assets, key) {
+					Ok(new) => {
+						*entry = new;
+						replaced = true;
+					},
+					Err(err) => eprintln!("failed to reload shader: {}", err),
+				}
+			}
+		}
+		replaced
+	}
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+struct ShaderKey {
+	/// Stage and asset name of each shader
+	stages: Vec<(GLenum, String)>,
+	defines: Vec<String>,
+}
+impl ShaderKey {
+	fn new(stages: &[(GLenum, &str)], defines: &[&str]) -> Self {
+		let mut defines: Vec<_> = defines.iter().map(|&d| d.to_owned()).collect();
+		defines.sort();
+		defines.dedup();
+		Self { stages: stages.iter().map(|&(kind, name)| (kind, name.to_owned())).collect(), defines }
+	}
+}
+
+struct CacheEntry {
+	program: Program,
+	files: Vec<PathBuf>,
+}
+
+enum Program {
+	Graphics(Rc<ShaderProgram>),
+	Compute(Rc<ComputeProgram>),
+}
+
+fn compile(ctx: &Rc<Ctx>, assets: &Assets, key: &ShaderKey) -> Result<CacheEntry, String> {
+	let defines: Vec<_> = key.defines.iter().map(String::as_str).collect();
+	let sources = key
+		.stages
+		.iter()
+		.map(|(kind, name)| Ok((*kind, preprocess(assets, name, &defines)?)))
+		.collect::<Result<Vec<_>, String>>()?;
+	let stages: Vec<_> = sources.iter().map(|(kind, source)| (*kind, source)).collect();
+	let files = sources.iter().flat_map(|(_, source)| &source.files).filter_map(|name| assets.path(name)).collect();
+
+	let program = match stages[..] {
+		[(gl::COMPUTE_SHADER, _)] => Program::Compute(Rc::new(ComputeProgram { id: link_program(&stages)? })),
+		[(gl::VERTEX_SHADER, vert), (gl::FRAGMENT_SHADER, frag)] => {
+			check_program(&stages)?;
+			let program = ShaderProgram::init(ctx).vertex_source(&vert.source).fragment_source(&frag.source).build();
+			Program::Graphics(Rc::new(program))
+		},
+		_ => unreachable!(),
+	};
+	Ok(CacheEntry { program, files })
+}
+
+/// Compute shader program. glrs only builds vertex and fragment programs, so this is raw GL.
+pub struct ComputeProgram {
+	id: GLuint,
+}
+impl ComputeProgram {
+	/// Runs `groups` work groups. Callers need a `glMemoryBarrier` before using what it wrote. The program bound
+	/// before is restored, since glrs doesn't know about this one.
+	pub fn dispatch(&self, groups: [u32; 3]) {
+		unsafe {
+			let mut current = 0;
+			gl::GetIntegerv(gl::CURRENT_PROGRAM, &mut current);
+			gl::UseProgram(self.id);
+			gl::DispatchCompute(groups[0], groups[1], groups[2]);
+			gl::UseProgram(current as _);
+		}
+	}
+
+	pub fn set_uniform_i32(&self, name: &str, val: i32) {
+		unsafe { gl::ProgramUniform1i(self.id, self.location(name), val) };
+	}
+
+	pub fn set_uniform_f32(&self, name: &str, val: f32) {
+		unsafe { gl::ProgramUniform1f(self.id, self.location(name), val) };
+	}
+
+	pub fn set_uniform_u32(&self, name: &str, val: u32) {
+		unsafe { gl::ProgramUniform1ui(self.id, self.location(name), val) };
+	}
+
+	pub fn set_uniform_vec4s(&self, name: &str, vals: &[[GLfloat; 4]]) {
+		unsafe { gl::ProgramUniform4fv(self.id, self.location(name), vals.len() as _, vals.as_ptr() as *const _) };
+	}
+
+	fn location(&self, name: &str) -> GLint {
+		let name = CString::new(name).unwrap();
+		unsafe { gl::GetUniformLocation(self.id, name.as_ptr()) }
+	}
+}
+impl Drop for ComputeProgram {
+	fn drop(&mut self) {
+		unsafe { gl::DeleteProgram(self.id) };
+	}
+}
+
+/// GLSL source with `#include`s expanded.
+pub struct Preprocessed {
+	pub source: String,
+	/// Asset names in `#line` source string number order
+	pub files: Vec<String>,
+}
+impl Preprocessed {
+	/// Replaces source string numbers in a driver info log with file names. Handles the `0:12(3):` (Mesa),
+	/// `0(12) :` (Nvidia) and `ERROR: 0:12:` (AMD) styles.
+	pub fn remap_log(&self, log: &str) -> String {
+		log.lines().map(|line| self.remap_line(line)).collect::<Vec<_>>().join("\n")
+	}
+
+	fn remap_line(&self, line: &str) -> String {
+		let start = match line.find(|c: char| c.is_ascii_digit()) {
+			Some(start) if start == 0 || line[..start].ends_with(' ') => start,
+			_ => return line.to_owned(),
+		};
+		let len = line[start..].find(|c: char| !c.is_ascii_digit()).unwrap_or(line.len() - start);
+		let rest = &line[start + len..];
+		let file = line[start..start + len].parse::<usize>().ok().and_then(|i| self.files.get(i));
+		match file {
+			Some(file) if rest.starts_with(':') || rest.starts_with('(') => {
+				format!("{}{}{}", &line[..start], file, rest)
+			},
+			_ => line.to_owned(),
+		}
+	}
+}
+
+/// Expands `#include "file"` (relative to the including file, each file included once) and inserts `defines` after
+/// `#version`. `#line` directives keep compiler errors pointing at the original files.
+pub fn preprocess(assets: &Assets, name: &str, defines: &[&str]) -> Result<Preprocessed, String> {
+	let mut out = Preprocessed { source: String::new(), files: vec![] };
+	// Normalized like the includes, so a file reached through different paths is only included once
+	include(&mut out, assets, &relative("", name), defines)?;
+	Ok(out)
+}
+
+fn include(out: &mut Preprocessed, assets: &Assets, name: &str, defines: &[&str]) -> Result<(), String> {
+	let idx = out.files.len();
+	out.files.push(name.to_owned());
+	let source = assets.read_string(name)?;
+
+	if idx != 0 {
+		writeln!(out.source, "#line 1 {}", idx).unwrap();
+	}
+
+	for (i, line) in source.lines().enumerate() {
+		let directive = line.trim_start();
+		if directive.starts_with("#include") {
+			let file = directive["#include".len()..].trim().trim_matches(|c| c == '"' || c == '<' || c == '>');
+			let file = relative(name, file);
+			if !out.files.contains(&file) {
+				include(out, assets, &file, defines).map_err(|e| format!("{}:{}: {}", name, i + 1, e))?;
+			}
+			writeln!(out.source, "#line {} {}", i + 2, idx).unwrap();
+		} else if directive.starts_with("#version") && idx == 0 {
+			writeln!(out.source, "{}", line).unwrap();
+			for define in defines {
+				writeln!(out.source, "#define {}", define).unwrap();
+			}
+			writeln!(out.source, "#line {} {}", i + 2, idx).unwrap();
+		} else {
+			writeln!(out.source, "{}", line).unwrap();
+		}
+	}
+
+	Ok(())
+}
+
+/// Compiles and links `stages` into a throwaway program and returns the info log if that fails. Lets a broken
+/// shader be reported without replacing the program that currently works.
+pub fn check_program(stages: &[(GLenum, &Preprocessed)]) -> Result<(), String> {
+	let program = link_program(stages)?;
+	unsafe { gl::DeleteProgram(program) };
+	Ok(())
+}
+
+/// Compiles and links `stages`, returning the info log on failure.
+fn link_program(stages: &[(GLenum, &Preprocessed)]) -> Result<GLuint, String> {
+	unsafe {
+		let program = gl::CreateProgram();
+		let mut shaders = vec![];
+		let mut result = Ok(());
+
+		for &(kind, source) in stages {
+			let shader = gl::CreateShader(kind);
+			let ptr = source.source.as_ptr() as *const GLchar;
+			let len = source.source.len() as GLint;
+			gl::ShaderSource(shader, 1, &ptr, &len);
+			gl::CompileShader(shader);
+
+			let mut status = 0;
+			gl::GetShaderiv(shader, gl::COMPILE_STATUS, &mut status);
+			if status == 0 && result.is_ok() {
+				result = Err(source.remap_log(&shader_log(shader)));
+			}
+
+			gl::AttachShader(program, shader);
+			shaders.push(shader);
+		}
+
+		if result.is_ok() {
+			gl::LinkProgram(program);
+			let mut status = 0;
+			gl::GetProgramiv(program, gl::LINK_STATUS, &mut status);
+			if status == 0 {
+				result = Err(program_log(program));
+			}
+		}
+
+		for shader in shaders {
+			gl::DetachShader(program, shader);
+			gl::DeleteShader(shader);
+		}
+		if result.is_err() {
+			gl::DeleteProgram(program);
+		}
+
+		result.map(|()| program)
+	}
+}
+
+unsafe fn shader_log(shader: GLuint) -> String {
+	let mut len = 0;
+	gl::GetShaderiv(shader, gl::INFO_LOG_LENGTH, &mut len);
+	let mut buf = vec![0u8; len as usize];
+	gl::GetShaderInfoLog(shader, len, ptr::null_mut(), buf.as_mut_ptr() as *mut GLchar);
+	String::from_utf8_lossy(&buf).trim_end_matches('\0').to_owned()
+}
+
+unsafe fn program_log(program: GLuint) -> String {
+	let mut len = 0;
+	gl::GetProgramiv(program, gl::INFO_LOG_LENGTH, &mut len);
+	let mut buf = vec![0u8; len as usize];
+	gl::GetProgramInfoLog(program, len, ptr::null_mut(), buf.as_mut_ptr() as *mut GLchar);
+	String::from_utf8_lossy(&buf).trim_end_matches('\0').to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::{env, fs, process};
+
+	/// Removes the directory when dropped
+	struct TempDir(PathBuf);
+	impl Drop for TempDir {
+		fn drop(&mut self) {
+			let _ = fs::remove_dir_all(&self.0);
+		}
+	}
+
+	/// Writes `files` to a fresh assets directory, which lives as long as the returned guard.
+	fn assets(test: &str, files: &[(&str, &str)]) -> (TempDir, Assets) {
+		let dir = TempDir(env::temp_dir().join(format!("immoral-shader-{}-{}", process::id(), test)));
+		for (name, source) in files {
+			let path = dir.0.join(name);
+			fs::create_dir_all(path.parent().unwrap()).unwrap();
+			fs::write(path, source).unwrap();
+		}
+		let assets = Assets::from_dir(dir.0.clone());
+		(dir, assets)
+	}
+
+	#[test]
+	fn nested_and_duplicate_includes() {
+		let (_dir, assets) = assets(
+			"includes",
+			&[
+				(
+					"shaders/main.vert",
+					"#version 450\n#include \"include/a.glsl\"\n#include \"include/../include/b.glsl\"\nvoid main() {}\n",
+				),
+				("shaders/include/a.glsl", "#include \"b.glsl\"\nfloat a;\n"),
+				("shaders/include/b.glsl", "float b;\n"),
+			],
+		);
+		let out = preprocess(&assets, "shaders/./main.vert", &[]).unwrap();
+		assert_eq!(out.files, ["shaders/main.vert", "shaders/include/a.glsl", "shaders/include/b.glsl"]);
+		let expected = [
+			"#version 450",
+			"#line 2 0",
+			"#line 1 1",
+			"#line 1 2",
+			"float b;",
+			"#line 2 1",
+			"float a;",
+			"#line 3 0",
+			"#line 4 0",
+			"void main() {}",
+		];
+		assert_eq!(out.source.lines().collect::<Vec<_>>(), expected);
+	}
+
+	#[test]
+	fn defines_follow_version() {
+		let (_dir, assets) = assets("defines", &[("shaders/main.frag", "#version 450\nvoid main() {}\n")]);
+		let out = preprocess(&assets, "shaders/main.frag", &["SHADOWS", "SAMPLES 4"]).unwrap();
+		let expected = ["#version 450", "#define SHADOWS", "#define SAMPLES 4", "#line 2 0", "void main() {}"];
+		assert_eq!(out.source.lines().collect::<Vec<_>>(), expected);
+	}
+
+	#[test]
+	fn missing_include_names_the_line() {
+		let (_dir, assets) = assets("missing", &[("shaders/main.frag", "#version 450\n#include \"nope.glsl\"\n")]);
+		let err = preprocess(&assets, "shaders/main.frag", &[]).err().unwrap();
+		assert_eq!(err, "shaders/main.frag:2: shaders/nope.glsl: asset not found");
+	}
+
+	#[test]
+	fn log_lines_name_files() {
+		let out = Preprocessed { source: String::new(), files: vec!["main.frag".to_owned(), "lights.glsl".to_owned()] };
+		let log = [
+			"0:12(3): error: `x' undeclared",
+			"1(7) : error C0000: syntax error",
+			"ERROR: 1:4: 'y' : undeclared identifier",
+			"5:1: no such file",
+			"warning: 2 unused uniforms",
+		]
+		.join("\n");
+		let expected = [
+			"main.frag:12(3): error: `x' undeclared",
+			"lights.glsl(7) : error C0000: syntax error",
+			"ERROR: lights.glsl:4: 'y' : undeclared identifier",
+			"5:1: no such file",
+			"warning: 2 unused uniforms",
+		];
+		assert_eq!(out.remap_log(&log).lines().collect::<Vec<_>>(), expected);
+	}
+}
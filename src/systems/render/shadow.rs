@@ -0,0 +1,153 @@
+use crate::types::camera::Camera;
+use glrs::gl::{self, types::*};
+use nalgebra::{Matrix4, Orthographic3, Perspective3, Point3, Vector3, Vector4};
+
+/// Shadow map layers given to the first directional light, each covering a slice of the view distance
+pub const CASCADES: usize = 4;
+/// Spot lights past this many don't cast shadows
+pub const MAX_SPOT_SHADOWS: usize = 4;
+pub const SHADOW_LAYERS: usize = CASCADES + MAX_SPOT_SHADOWS;
+/// Directional shadows end this far from the camera
+const SHADOW_DISTANCE: f32 = 100.0;
+/// Blend between uniform (0) and logarithmic (1) cascade splits
+const SPLIT_LAMBDA: f32 = 0.75;
+/// Distance behind each cascade that still casts into it
+const CASTER_DISTANCE: f32 = 50.0;
+
+/// Depth texture array with one layer per cascade or spot light, and a framebuffer for rendering into each layer.
+pub struct ShadowMaps {
+	tex: GLuint,
+	fbos: Vec<GLuint>,
+	size: i32,
+}
+impl ShadowMaps {
+	pub fn new(size: i32) -> Self {
+		let mut tex = 0;
+		let mut fbos = vec![0; SHADOW_LAYERS];
+		unsafe {
+			gl::GenTextures(1, &mut tex);
+			gl::BindTexture(gl::TEXTURE_2D_ARRAY, tex);
+			gl::TexStorage3D(gl::TEXTURE_2D_ARRAY, 1, gl::DEPTH_COMPONENT32F, size, size, SHADOW_LAYERS as _);
+			// Linear filtering with compare mode gives 2x2 PCF for free on top of the shader's taps
+			gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_MIN_FILTER, gl::LINEAR as _);
+			gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_MAG_FILTER, gl::LINEAR as _);
+			gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as _);
+			gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as _);
+			gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_COMPARE_MODE, gl::COMPARE_REF_TO_TEXTURE as _);
+			gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_COMPARE_FUNC, gl::LEQUAL as _);
+
+			gl::GenFramebuffers(fbos.len() as _, fbos.as_mut_ptr());
+			for (layer, &fbo) in fbos.iter().enumerate() {
+				gl::BindFramebuffer(gl::FRAMEBUFFER, fbo);
+				gl::FramebufferTextureLayer(gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT, tex, 0, layer as _);
+				gl::DrawBuffer(gl::NONE);
+				gl::ReadBuffer(gl::NONE);
+			}
+			gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+		}
+		Self { tex, fbos, size }
+	}
+
+	/// Binds the depth texture for sampling.
+	pub fn bind_texture(&self, unit: u32) {
+		unsafe {
+			gl::ActiveTexture(gl::TEXTURE0 + unit);
+			gl::BindTexture(gl::TEXTURE_2D_ARRAY, self.tex);
+			gl::ActiveTexture(gl::TEXTURE0);
+		}
+	}
+
+	/// Binds `layer` for rendering and clears it.
+	pub fn bind_layer(&self, layer: usize) {
+		unsafe {
+			gl::BindFramebuffer(gl::FRAMEBUFFER, self.fbos[layer]);
+			gl::Viewport(0, 0, self.size, self.size);
+			gl::ClearBufferfv(gl::DEPTH, 0, &1.0);
+		}
+	}
+
+	pub fn size(&self) -> i32 {
+		self.size
+	}
+}
+impl Drop for ShadowMaps {
+	fn drop(&mut self) {
+		unsafe {
+			gl::DeleteFramebuffers(self.fbos.len() as _, self.fbos.as_ptr());
+			gl::DeleteTextures(1, &self.tex);
+		}
+	}
+}
+
+/// std140 layout of the shadow map matrices, matching `include/shadows.glsl`
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct ShadowsUniform {
+	/// World to clip space of each layer
+	pub matrices: [Matrix4<f32>; SHADOW_LAYERS],
+	/// View distance at which each cascade ends
+	pub splits: Vector4<f32>,
+}
+impl Default for ShadowsUniform {
+	fn default() -> Self {
+		Self { matrices: [Matrix4::identity(); SHADOW_LAYERS], splits: Vector4::zeros() }
+	}
+}
+
+/// Fits an orthographic projection along `dir` around each slice of the camera's view. `size` is the shadow map
+/// resolution, used to snap cascades to whole texels so shadow edges don't shimmer as the camera moves.
+pub fn cascades(cam: &Camera, dir: &Vector3<f32>, size: i32) -> ([Matrix4<f32>; CASCADES], Vector4<f32>) {
+	let near = cam.z_near;
+	let far = if cam.reversed_z { SHADOW_DISTANCE } else { cam.z_far.min(SHADOW_DISTANCE) };
+	let mut splits = Vector4::zeros();
+	for i in 0..CASCADES {
+		let t = (i + 1) as f32 / CASCADES as f32;
+		let log = near * (far / near).powf(t);
+		let uniform = near + (far - near) * t;
+		splits[i] = SPLIT_LAMBDA * log + (1.0 - SPLIT_LAMBDA) * uniform;
+	}
+
+	let rot = cam.uniform.rot;
+	let (right, forward, up) = (rot * Vector3::x(), rot * Vector3::y(), rot * Vector3::z());
+	let tan_y = cam.fov.to_radians().tan();
+	let tan_x = tan_y / cam.aspect;
+	let light_up = if dir.z.abs() > 0.99 { Vector3::y() } else { Vector3::z() };
+	let light_rot = Matrix4::look_at_rh(&Point3::origin(), &Point3::from(*dir), &light_up);
+
+	let mut matrices = [Matrix4::identity(); CASCADES];
+	let mut start = near;
+	for (i, matrix) in matrices.iter_mut().enumerate() {
+		let end = splits[i];
+		// Bounding sphere of the slice, so the projection doesn't change size as the camera turns
+		let corners: Vec<_> = [start, end]
+			.iter()
+			.flat_map(|&d| {
+				let (x, y) = (right * d * tan_x, up * d * tan_y);
+				let center = cam.uniform.pos + forward * d;
+				vec![center + x + y, center + x - y, center - x + y, center - x - y]
+			})
+			.collect();
+		let center = corners.iter().sum::<Vector3<f32>>() / corners.len() as f32;
+		let radius = corners.iter().map(|c| (c - center).norm()).fold(0.0, f32::max).ceil();
+
+		let texel = 2.0 * radius / size as f32;
+		let snapped = light_rot.transform_point(&Point3::from(center));
+		let snapped = Point3::new((snapped.x / texel).floor() * texel, (snapped.y / texel).floor() * texel, snapped.z);
+		let center = light_rot.try_inverse().unwrap().transform_point(&snapped);
+
+		let eye = center - dir * (radius + CASTER_DISTANCE);
+		let view = Matrix4::look_at_rh(&eye, &center, &light_up);
+		let proj = Orthographic3::new(-radius, radius, -radius, radius, 0.0, 2.0 * radius + CASTER_DISTANCE);
+		*matrix = proj.as_matrix() * view;
+		start = end;
+	}
+	(matrices, splits)
+}
+
+/// Perspective projection covering a spot light's cone out to its range.
+pub fn spot(pos: &Vector3<f32>, dir: &Vector3<f32>, outer: f32, range: f32) -> Matrix4<f32> {
+	let up = if dir.z.abs() > 0.99 { Vector3::y() } else { Vector3::z() };
+	let view = Matrix4::look_at_rh(&Point3::from(*pos), &Point3::from(pos + dir), &up);
+	let fov = (outer * 2.0).min(3.0);
+	Perspective3::new(1.0, fov, 0.05, range.max(0.1)).as_matrix() * view
+}
@@ -0,0 +1,153 @@
+use glrs::gl::{self, types::GLuint};
+use std::{cell::RefCell, mem::size_of, ops::Range, ptr, rc::Rc};
+
+/// Shader storage buffer holding an array of `T`. Changes are kept on the CPU and uploaded on `bind`, only the range
+/// that changed unless the buffer had to grow.
+pub struct StorageBuffer<T> {
+	id: GLuint,
+	data: Vec<T>,
+	/// Elements the GL buffer has room for
+	capacity: usize,
+	/// Elements changed since the last upload
+	dirty: Option<Range<usize>>,
+}
+impl<T: Copy> StorageBuffer<T> {
+	pub fn new() -> Self {
+		let mut id = 0;
+		unsafe { gl::GenBuffers(1, &mut id) };
+		// Empty but dirty, so the first bind allocates
+		Self { id, data: vec![], capacity: 0, dirty: Some(0..0) }
+	}
+
+	pub fn push(&mut self, val: T) -> usize {
+		self.data.push(val);
+		self.mark(self.data.len() - 1..self.data.len());
+		self.data.len() - 1
+	}
+
+	pub fn set(&mut self, idx: usize, val: T) {
+		self.data[idx] = val;
+		self.mark(idx..idx + 1);
+	}
+
+	/// Contents as last set on the CPU. Doesn't include anything shaders wrote.
+	pub fn as_slice(&self) -> &[T] {
+		&self.data
+	}
+
+	/// Grows or shrinks to `len`, filling with `val`. Leaves the GL buffer alone if the length is unchanged, so it
+	/// can hold shader output.
+	pub fn resize(&mut self, len: usize, val: T) {
+		let old = self.data.len();
+		if len != old {
+			self.data.resize(len, val);
+			self.mark(old.min(len)..len);
+		}
+	}
+
+	pub fn id(&self) -> GLuint {
+		self.id
+	}
+
+	/// Replaces the whole contents.
+	pub fn write(&mut self, data: &[T]) {
+		self.data.clear();
+		self.data.extend_from_slice(data);
+		self.mark(0..data.len());
+	}
+
+	/// Uploads pending changes and binds to `binding`.
+	pub fn bind(&mut self, binding: u32) {
+		unsafe {
+			gl::BindBuffer(gl::SHADER_STORAGE_BUFFER, self.id);
+			if let Some(mut range) = self.dirty.take() {
+				// Never empty, since binding a zero-sized buffer is an error
+				let len = self.data.len().max(1);
+				if len > self.capacity {
+					self.capacity = len.next_power_of_two();
+					let size = (self.capacity * size_of::<T>()) as _;
+					gl::BufferData(gl::SHADER_STORAGE_BUFFER, size, ptr::null(), gl::DYNAMIC_DRAW);
+					range = 0..self.data.len();
+				}
+				// Shrinking can leave the range past the end
+				let end = range.end.min(self.data.len());
+				let start = range.start.min(end);
+				let offset = (start * size_of::<T>()) as _;
+				let size = ((end - start) * size_of::<T>()) as _;
+				gl::BufferSubData(gl::SHADER_STORAGE_BUFFER, offset, size, self.data[start..].as_ptr() as *const _);
+			}
+			gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, binding, self.id);
+		}
+	}
+
+	fn mark(&mut self, range: Range<usize>) {
+		self.dirty = Some(match self.dirty.take() {
+			Some(dirty) => dirty.start.min(range.start)..dirty.end.max(range.end),
+			None => range,
+		});
+	}
+}
+impl<T> Drop for StorageBuffer<T> {
+	fn drop(&mut self) {
+		unsafe { gl::DeleteBuffers(1, &self.id) };
+	}
+}
+
+/// `StorageBuffer` handed out one element at a time as `Slot`s. Dropped slots are overwritten with `empty` and reused.
+pub struct SlotBuffer<T> {
+	buffer: RefCell<StorageBuffer<T>>,
+	free: RefCell<Vec<usize>>,
+	empty: T,
+}
+impl<T: Copy> SlotBuffer<T> {
+	pub fn new(empty: T) -> Rc<Self> {
+		Rc::new(Self { buffer: RefCell::new(StorageBuffer::new()), free: RefCell::default(), empty })
+	}
+
+	pub fn alloc(self: &Rc<Self>, val: T) -> Slot<T> {
+		let mut buffer = self.buffer.borrow_mut();
+		let idx = match self.free.borrow_mut().pop() {
+			Some(idx) => {
+				buffer.set(idx, val);
+				idx
+			},
+			None => buffer.push(val),
+		};
+		Slot { slots: self.clone(), idx }
+	}
+
+	/// Slots ever allocated, including freed ones
+	pub fn total(&self) -> usize {
+		self.buffer.borrow().as_slice().len()
+	}
+
+	/// Uploads pending changes and binds to `binding`.
+	pub fn bind(&self, binding: u32) {
+		self.buffer.borrow_mut().bind(binding);
+	}
+}
+
+/// Element of a `SlotBuffer`, freed on drop.
+pub struct Slot<T: Copy> {
+	slots: Rc<SlotBuffer<T>>,
+	idx: usize,
+}
+impl<T: Copy> Slot<T> {
+	pub fn idx(&self) -> usize {
+		self.idx
+	}
+
+	pub fn get(&self) -> T {
+		self.slots.buffer.borrow().as_slice()[self.idx]
+	}
+
+	pub fn set(&self, val: T) {
+		self.slots.buffer.borrow_mut().set(self.idx, val);
+	}
+}
+impl<T: Copy> Drop for Slot<T> {
+	fn drop(&mut self) {
+		self.set(self.slots.empty);
+		self.slots.free.borrow_mut().push(self.idx);
+	}
+}
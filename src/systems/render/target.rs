@@ -0,0 +1,153 @@
+use glrs::gl::{
+	self,
+	types::{GLenum, GLint, GLuint},
+};
+use image::{imageops, RgbaImage};
+
+/// Offscreen framebuffer with color and depth textures, sized to match the window.
+///
+/// Raw GL because glrs's `Texture2D` is always RGBA8 and has no depth format to attach.
+pub struct RenderTarget {
+	fbo: GLuint,
+	color: GLuint,
+	depth: GLuint,
+	format: GLenum,
+	size: [u32; 2],
+}
+impl RenderTarget {
+	/// `format` is a sized internal format like `gl::RGBA8`.
+	pub fn new(size: [u32; 2], format: GLenum) -> Self {
+		let mut target = Self { fbo: 0, color: 0, depth: 0, format, size };
+		target.create();
+		target
+	}
+
+	pub fn size(&self) -> [u32; 2] {
+		self.size
+	}
+
+	/// Color attachment texture, for sampling after the pass that drew into it.
+	pub fn color(&self) -> GLuint {
+		self.color
+	}
+
+	/// Depth attachment texture, for sampling after the pass that drew into it.
+	pub fn depth(&self) -> GLuint {
+		self.depth
+	}
+
+	/// Recreates the attachments if `size` is different. Does nothing for zero sizes, which happen while minimized.
+	pub fn resize(&mut self, size: [u32; 2]) {
+		if size != self.size && size[0] != 0 && size[1] != 0 {
+			self.destroy();
+			self.size = size;
+			self.create();
+		}
+	}
+
+	/// Binds for drawing and sets the viewport to cover the whole target.
+	pub fn bind(&self) {
+		unsafe {
+			gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, self.fbo);
+			gl::Viewport(0, 0, self.size[0] as _, self.size[1] as _);
+		}
+	}
+
+	pub fn clear(&self, r: f32, g: f32, b: f32, a: f32) {
+		unsafe {
+			gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, self.fbo);
+			gl::ClearBufferfv(gl::COLOR, 0, [r, g, b, a].as_ptr());
+		}
+	}
+
+	pub fn clear_depth(&self, depth: f32) {
+		unsafe {
+			gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, self.fbo);
+			gl::DepthMask(gl::TRUE);
+			gl::ClearBufferfv(gl::DEPTH, 0, &depth);
+		}
+	}
+
+	/// Copies the color attachment to the default framebuffer, scaled to `size`.
+	pub fn blit_to_default(&self, size: [u32; 2]) {
+		unsafe {
+			gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.fbo);
+			gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, 0);
+			gl::BlitFramebuffer(
+				0,
+				0,
+				self.size[0] as _,
+				self.size[1] as _,
+				0,
+				0,
+				size[0] as _,
+				size[1] as _,
+				gl::COLOR_BUFFER_BIT,
+				gl::LINEAR,
+			);
+			gl::BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
+		}
+	}
+
+	/// Reads the color attachment back to the CPU.
+	pub fn read_pixels(&self) -> RgbaImage {
+		read_pixels(self.fbo, self.size)
+	}
+
+	fn create(&mut self) {
+		unsafe {
+			gl::GenTextures(1, &mut self.color);
+			gl::BindTexture(gl::TEXTURE_2D, self.color);
+			gl::TexStorage2D(gl::TEXTURE_2D, 1, self.format, self.size[0] as _, self.size[1] as _);
+			gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::LINEAR as GLint);
+			gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::LINEAR as GLint);
+			gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as GLint);
+			gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as GLint);
+
+			// Float depth keeps precision with reversed-Z
+			gl::GenTextures(1, &mut self.depth);
+			gl::BindTexture(gl::TEXTURE_2D, self.depth);
+			gl::TexStorage2D(gl::TEXTURE_2D, 1, gl::DEPTH_COMPONENT32F, self.size[0] as _, self.size[1] as _);
+			gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as GLint);
+			gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as GLint);
+			gl::BindTexture(gl::TEXTURE_2D, 0);
+
+			gl::GenFramebuffers(1, &mut self.fbo);
+			gl::BindFramebuffer(gl::FRAMEBUFFER, self.fbo);
+			gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, gl::TEXTURE_2D, self.color, 0);
+			gl::FramebufferTexture2D(gl::FRAMEBUFFER, gl::DEPTH_ATTACHMENT, gl::TEXTURE_2D, self.depth, 0);
+			assert_eq!(gl::CheckFramebufferStatus(gl::FRAMEBUFFER), gl::FRAMEBUFFER_COMPLETE);
+			gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+		}
+	}
+
+	fn destroy(&mut self) {
+		unsafe {
+			gl::DeleteFramebuffers(1, &self.fbo);
+			gl::DeleteTextures(1, &self.color);
+			gl::DeleteTextures(1, &self.depth);
+		}
+	}
+}
+impl Drop for RenderTarget {
+	fn drop(&mut self) {
+		self.destroy();
+	}
+}
+
+/// Reads the back buffer of the default framebuffer.
+pub fn read_default(size: [u32; 2]) -> RgbaImage {
+	read_pixels(0, size)
+}
+
+fn read_pixels(fbo: GLuint, [width, height]: [u32; 2]) -> RgbaImage {
+	let mut buf = vec![0u8; width as usize * height as usize * 4];
+	unsafe {
+		gl::BindFramebuffer(gl::READ_FRAMEBUFFER, fbo);
+		gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+		gl::ReadPixels(0, 0, width as _, height as _, gl::RGBA, gl::UNSIGNED_BYTE, buf.as_mut_ptr() as *mut _);
+		gl::BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
+	}
+	// GL rows start at the bottom
+	imageops::flip_vertical(&RgbaImage::from_raw(width, height, buf).unwrap())
+}
@@ -0,0 +1,312 @@
+use crate::{
+	systems::render::{
+		allocs::{RenderAllocs, TEX_SIZE},
+		debug::{stream_batches, STREAM_SIZE},
+		shader::ShaderCache,
+	},
+	types::{
+		assets::Assets,
+		camera::{Camera, CameraUniform},
+		font::Font,
+		text::{ScreenText, TextStyle, WorldText},
+	},
+};
+use glrs::{
+	alloc::{Allocator, AllocatorAbstract},
+	buffer::{DynamicBuffer, ImmutableBuffer},
+	gl, implement_vertex,
+	shader::ShaderProgram,
+	vertex::VertexArray,
+	Ctx,
+};
+use nalgebra::{Vector3, Vector4};
+use rusttype::GlyphId;
+use std::{collections::HashMap, rc::Rc};
+
+const TEXT_VERTEX_SHADER: &str = "shaders/text.vert";
+const TEXT_FRAGMENT_SHADER: &str = "shaders/text.frag";
+const FONT: &str = "fonts/DejaVuSans.ttf";
+/// Name the atlas layer is reserved under in `RenderAllocs::tex_layers`
+const ATLAS_LAYER: &str = "fonts/atlas";
+/// Pixels per em world text is rasterized at before scaling
+const WORLD_TEXT_SIZE: f32 = 64.0;
+
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+struct TextVertex {
+	pos: Vector3<f32>,
+	/// Texture coordinates and layer in the texture array
+	uvw: Vector3<f32>,
+	color: Vector4<f32>,
+}
+implement_vertex!(TextVertex, pos, uvw, color);
+
+struct Programs {
+	screen: Rc<ShaderProgram>,
+	world: Rc<ShaderProgram>,
+}
+impl Programs {
+	fn new(shaders: &mut ShaderCache, cambuf: &Rc<DynamicBuffer<CameraUniform>>) -> Result<Self, String> {
+		let screen = shaders.get(TEXT_VERTEX_SHADER, TEXT_FRAGMENT_SHADER, &[])?;
+		screen.set_uniform_i32("tex", 0);
+		let world = shaders.get(TEXT_VERTEX_SHADER, TEXT_FRAGMENT_SHADER, &["WORLD"])?;
+		world.set_uniform_i32("tex", 0);
+		world.bind_buffer_range("Camera", cambuf.clone());
+		Ok(Self { screen, world })
+	}
+}
+
+/// Where a rasterized glyph is in the atlas
+#[derive(Clone, Copy)]
+struct AtlasGlyph {
+	/// Top left relative to the pen position, in pixels with y down
+	offset: [i32; 2],
+	size: [u32; 2],
+	/// Top left in the atlas layer
+	origin: [u32; 2],
+}
+
+/// Returned when a glyph doesn't fit in what's left of the atlas layer.
+struct AtlasFull;
+
+/// Glyphs packed into rows of a layer of the texture array as they're first drawn. When the layer fills up the text
+/// laid out so far is drawn, then the layer is cleared and filled again.
+struct GlyphAtlas {
+	allocs: Rc<RenderAllocs>,
+	layer: i32,
+	/// By glyph and pixels per em. `None` for glyphs without an outline.
+	glyphs: HashMap<(GlyphId, u32), Option<AtlasGlyph>>,
+	/// Top left of the free space in the current row
+	cursor: [u32; 2],
+	row_height: u32,
+}
+impl GlyphAtlas {
+	fn new(allocs: &Rc<RenderAllocs>) -> Result<Self, String> {
+		let layer = allocs.data_layer(ATLAS_LAYER.to_owned())?;
+		let atlas = Self { allocs: allocs.clone(), layer, glyphs: HashMap::new(), cursor: [0, 0], row_height: 0 };
+		atlas.blank();
+		Ok(atlas)
+	}
+
+	/// Quads using glyphs from before an `AtlasFull` have to be drawn before calling `clear`.
+	fn get(&mut self, font: &Font, id: GlyphId, size: u32) -> Result<Option<AtlasGlyph>, AtlasFull> {
+		if let Some(&glyph) = self.glyphs.get(&(id, size)) {
+			return Ok(glyph);
+		}
+		let glyph = match font.rasterize(id, size as _) {
+			Some(bitmap) => self.pack(bitmap.size[0], bitmap.size[1])?.map(|origin| {
+				let mut rgba = Vec::with_capacity(bitmap.coverage.len() * 4);
+				for &a in &bitmap.coverage {
+					rgba.extend_from_slice(&[255, 255, 255, a]);
+				}
+				let buf = ImmutableBuffer::from_slice(self.allocs.ctx(), &rgba);
+				let pos = [origin[0] as _, origin[1] as _, self.layer];
+				let size = [bitmap.size[0] as _, bitmap.size[1] as _, 1];
+				self.allocs.tex.subimage_u8(pos.into(), size.into(), gl::RGBA, &buf);
+				AtlasGlyph { offset: bitmap.offset, size: bitmap.size, origin }
+			}),
+			None => None,
+		};
+		self.glyphs.insert((id, size), glyph);
+		Ok(glyph)
+	}
+
+	/// Forgets every glyph and blanks the layer.
+	fn clear(&mut self) {
+		eprintln!("glyph atlas full, clearing it");
+		self.glyphs.clear();
+		self.cursor = [0, 0];
+		self.row_height = 0;
+		self.blank();
+	}
+
+	/// Glyphs are sampled with linear filtering, so the gaps between them need to be transparent.
+	fn blank(&self) {
+		let blank = ImmutableBuffer::from_slice(self.allocs.ctx(), &vec![0u8; (TEX_SIZE * TEX_SIZE * 4) as usize]);
+		let size = [TEX_SIZE as _, TEX_SIZE as _, 1];
+		self.allocs.tex.subimage_u8([0, 0, self.layer].into(), size.into(), gl::RGBA, &blank);
+	}
+
+	/// Finds room for a glyph with a pixel of space around it. Too big for the atlas gives `None`.
+	fn pack(&mut self, width: u32, height: u32) -> Result<Option<[u32; 2]>, AtlasFull> {
+		if width + 1 > TEX_SIZE || height + 1 > TEX_SIZE {
+			return Ok(None);
+		}
+		if self.cursor[0] + width + 1 > TEX_SIZE {
+			self.cursor = [0, self.cursor[1] + self.row_height];
+			self.row_height = 0;
+		}
+		if self.cursor[1] + height + 1 > TEX_SIZE {
+			return Err(AtlasFull);
+		}
+		let origin = [self.cursor[0] + 1, self.cursor[1] + 1];
+		self.cursor[0] += width + 1;
+		self.row_height = self.row_height.max(height + 1);
+		Ok(Some(origin))
+	}
+}
+
+/// Quads waiting to be drawn, and whether they're depth tested.
+type Batch = (bool, Vec<TextVertex>);
+
+/// Lays out and draws `TextDraw`'s text as quads sampling glyphs from the atlas.
+pub struct TextRenderer {
+	font: Font,
+	atlas: GlyphAtlas,
+	programs: Programs,
+	cambuf: Rc<DynamicBuffer<CameraUniform>>,
+	/// Allocated each draw and freed right after
+	vertices: Rc<Allocator<TextVertex>>,
+	vao: VertexArray,
+}
+impl TextRenderer {
+	pub fn new(
+		shaders: &mut ShaderCache,
+		assets: &Assets,
+		allocs: &Rc<RenderAllocs>,
+		cambuf: &Rc<DynamicBuffer<CameraUniform>>,
+	) -> Self {
+		let font = Font::load(assets, FONT).unwrap_or_else(|e| panic!("{}", e));
+		let programs = Programs::new(shaders, cambuf).unwrap_or_else(|e| panic!("{}", e));
+
+		let vertices = Allocator::new(allocs.ctx(), STREAM_SIZE);
+		let mut vao = VertexArray::new(allocs.ctx());
+		vao.enable_vertices::<TextVertex>(0);
+		vao.vertex_buffer(0, &vertices);
+
+		let atlas = GlyphAtlas::new(allocs).unwrap_or_else(|e| panic!("{}", e));
+		Self { font, atlas, programs, cambuf: cambuf.clone(), vertices, vao }
+	}
+
+	pub fn reload_shaders(&mut self, shaders: &mut ShaderCache) {
+		match Programs::new(shaders, &self.cambuf) {
+			Ok(programs) => self.programs = programs,
+			Err(err) => eprintln!("failed to reload text shaders: {}", err),
+		}
+	}
+
+	/// Draws `texts` blended over whatever framebuffer is bound, which should be `window_size` pixels.
+	pub fn draw_screen(&mut self, ctx: &Ctx, texts: &[ScreenText], window_size: [u32; 2]) {
+		if texts.is_empty() {
+			return;
+		}
+		ctx.use_program(&self.programs.screen);
+		ctx.bind_vertex_array(&self.vao);
+		unsafe {
+			gl::Enable(gl::BLEND);
+			gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+		}
+
+		let [width, height] = [window_size[0] as f32, window_size[1] as f32];
+		let mut batches = [(false, vec![])];
+		for text in texts {
+			let [x, y] = [text.pos[0].round(), text.pos[1].round()];
+			let size = text.style.size.round();
+			let place = |[px, py]: [f32; 2]| {
+				Vector3::new((x + px.round()) / width * 2.0 - 1.0, 1.0 - (y + py.round()) / height * 2.0, 0.0)
+			};
+			self.quads(&text.text, &text.style, size, text.style.wrap, place, &mut batches, 0);
+		}
+		self.flush(&mut batches);
+
+		unsafe {
+			gl::Disable(gl::BLEND);
+			gl::Enable(gl::DEPTH_TEST);
+		}
+	}
+
+	/// Draws `texts` facing `cam`, the depth tested ones first. Expects the depth state set up for the camera.
+	pub fn draw_world<'a>(&mut self, ctx: &Ctx, texts: impl Iterator<Item = &'a WorldText>, cam: &Camera) {
+		let mut texts = texts.peekable();
+		if texts.peek().is_none() {
+			return;
+		}
+		ctx.use_program(&self.programs.world);
+		ctx.bind_vertex_array(&self.vao);
+		unsafe {
+			gl::Enable(gl::BLEND);
+			gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+			gl::DepthMask(gl::FALSE);
+		}
+
+		let rot = cam.uniform.rot;
+		let (right, up) = (rot * Vector3::x(), rot * Vector3::z());
+		let mut batches = [(true, vec![]), (false, vec![])];
+		for text in texts {
+			let scale = text.style.size / WORLD_TEXT_SIZE;
+			let place = |[x, y]: [f32; 2]| text.pos + (right * x - up * y) * scale;
+			let wrap = text.style.wrap.map(|wrap| wrap / scale);
+			let batch = if text.depth_test { 0 } else { 1 };
+			self.quads(&text.text, &text.style, WORLD_TEXT_SIZE, wrap, place, &mut batches, batch);
+		}
+		self.flush(&mut batches);
+
+		unsafe {
+			gl::Enable(gl::DEPTH_TEST);
+			gl::DepthMask(gl::TRUE);
+			gl::Disable(gl::BLEND);
+		}
+	}
+
+	/// Pushes a triangle list of `text` laid out at `size` pixels per em to `batches[batch]`. `place` maps pixels from
+	/// the text's anchor, y down, to vertex positions. Flushes `batches` if the atlas fills up.
+	#[allow(clippy::too_many_arguments)]
+	fn quads(
+		&mut self,
+		text: &str,
+		style: &TextStyle,
+		size: f32,
+		wrap: Option<f32>,
+		place: impl Fn([f32; 2]) -> Vector3<f32>,
+		batches: &mut [Batch],
+		batch: usize,
+	) {
+		let layout = self.font.layout(text, size, wrap);
+		let layer = self.atlas.layer as f32;
+		for laid in &layout.glyphs {
+			let glyph = self.atlas.get(&self.font, laid.id, size as _).unwrap_or_else(|AtlasFull| {
+				// Quads laid out so far point at glyphs that are about to be overwritten
+				self.flush(batches);
+				self.atlas.clear();
+				self.atlas.get(&self.font, laid.id, size as _).unwrap_or(None)
+			});
+			let glyph = match glyph {
+				Some(glyph) => glyph,
+				None => continue,
+			};
+			let x = laid.pos[0] - layout.line_widths[laid.line] * style.align.factor() + glyph.offset[0] as f32;
+			let y = laid.pos[1] + glyph.offset[1] as f32;
+			let [width, height] = [glyph.size[0] as f32, glyph.size[1] as f32];
+			let [u, v] = [glyph.origin[0] as f32, glyph.origin[1] as f32];
+			let corner = |dx: f32, dy: f32| TextVertex {
+				pos: place([x + dx, y + dy]),
+				uvw: Vector3::new((u + dx) / TEX_SIZE as f32, (v + dy) / TEX_SIZE as f32, layer),
+				color: style.color,
+			};
+			let (tl, tr) = (corner(0.0, 0.0), corner(width, 0.0));
+			let (bl, br) = (corner(0.0, height), corner(width, height));
+			batches[batch].1.extend_from_slice(&[tl, tr, br, tl, br, bl]);
+		}
+	}
+
+	/// Draws and empties `batches` in order, with the program and vertex array bound. Splits them to fit the allocator.
+	fn flush(&self, batches: &mut [Batch]) {
+		for (depth_test, vertices) in batches {
+			if vertices.is_empty() {
+				continue;
+			}
+			unsafe {
+				if *depth_test {
+					gl::Enable(gl::DEPTH_TEST);
+				} else {
+					gl::Disable(gl::DEPTH_TEST);
+				}
+			}
+			for batch in stream_batches(vertices, 3) {
+				let alloc = self.vertices.alloc_slice(batch);
+				unsafe { gl::DrawArrays(gl::TRIANGLES, alloc.offset() as _, alloc.len() as _) };
+			}
+			vertices.clear();
+		}
+	}
+}
@@ -0,0 +1,519 @@
+use crate::{
+	systems::{
+		gamepad::{FakeGamepads, GamepadEvent, Gamepads, AXES, BUTTONS},
+		input::parse_key,
+	},
+	Application,
+};
+use glutin::{
+	dpi::{PhysicalPosition, PhysicalSize},
+	event::{
+		DeviceEvent, DeviceId, ElementState, KeyboardInput, ModifiersState, MouseButton, MouseScrollDelta, TouchPhase,
+		WindowEvent,
+	},
+};
+use shipyard::{NonSendSync, UniqueView, UniqueViewMut};
+use std::{
+	collections::VecDeque,
+	fmt::Debug,
+	fs::{self, File},
+	io::{self, BufWriter, Write},
+	path::Path,
+	str::{FromStr, SplitWhitespace},
+	time::Duration,
+};
+
+// Recordings are text, one event per line after the `frame <delta in ns>` line starting each frame, so they can be
+// read and edited by hand. Only events input handling reads are kept. Resizes are recorded but not played back, since
+// the window being played into keeps its own size.
+
+const HEADER: &str = "replay 1";
+
+/// One frame's input
+pub struct Frame {
+	pub delta: Duration,
+	pub window_events: Vec<WindowEvent<'static>>,
+	pub device_events: Vec<DeviceEvent>,
+	pub gamepad_events: Vec<GamepadEvent>,
+}
+
+/// Writes every frame's input to a file.
+pub struct Recorder {
+	out: BufWriter<File>,
+	frames: usize,
+}
+impl Recorder {
+	pub fn create(path: &Path) -> Result<Self, String> {
+		let err = |e: io::Error| format!("{}: {}", path.display(), e);
+		let mut out = BufWriter::new(File::create(path).map_err(err)?);
+		writeln!(out, "{}", HEADER).map_err(err)?;
+		Ok(Self { out, frames: 0 })
+	}
+
+	pub fn write(
+		&mut self,
+		delta: Duration,
+		window_events: &[WindowEvent],
+		device_events: &[DeviceEvent],
+		gamepad_events: &[GamepadEvent],
+	) -> io::Result<()> {
+		write_frame(&mut self.out, delta, window_events, device_events, gamepad_events)?;
+		self.frames += 1;
+		// Flushed every frame so a crash still leaves a replay of everything leading up to it
+		self.out.flush()
+	}
+}
+
+/// Frames left to play back, in place of the event loop's input.
+pub struct Playback {
+	frames: VecDeque<Frame>,
+	played: usize,
+	/// Gamepads have to be faked too, so playback must be given the fake backend's handle
+	gamepads: FakeGamepads,
+}
+impl Playback {
+	pub fn load(path: &Path, gamepads: FakeGamepads) -> Result<Self, String> {
+		let src = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+		let frames = parse(&src).map_err(|e| format!("{}: {}", path.display(), e))?;
+		Ok(Self { frames, played: 0, gamepads })
+	}
+}
+
+/// Appends this frame's input to the recording. Runs after the workload, once gamepads have been polled.
+pub fn record_frame(
+	mut recorder: UniqueViewMut<Recorder>,
+	delta: UniqueView<Duration>,
+	window_events: UniqueView<Vec<WindowEvent>>,
+	device_events: UniqueView<Vec<DeviceEvent>>,
+	gamepads: UniqueView<Gamepads>,
+) {
+	if let Err(err) = recorder.write(*delta, &window_events, &device_events, &gamepads.events) {
+		eprintln!("failed to record frame {}: {}", recorder.frames, err);
+	}
+}
+
+/// Replaces this frame's input with the next recorded frame, and quits once there are none left. Runs before the
+/// workload.
+pub fn play_frame(
+	mut playback: NonSendSync<UniqueViewMut<Playback>>,
+	mut delta: UniqueViewMut<Duration>,
+	mut window_events: UniqueViewMut<Vec<WindowEvent>>,
+	mut device_events: UniqueViewMut<Vec<DeviceEvent>>,
+	mut app: UniqueViewMut<Application>,
+) {
+	match playback.frames.pop_front() {
+		Some(frame) => {
+			*delta = frame.delta;
+			// Only the real window's resizes are kept, since targets have to match its size
+			let resized = |event: &WindowEvent| matches!(event, WindowEvent::Resized(_));
+			window_events.retain(resized);
+			window_events.extend(frame.window_events.into_iter().filter(|event| !resized(event)));
+			*device_events = frame.device_events;
+			for event in frame.gamepad_events {
+				playback.gamepads.push(event);
+			}
+			playback.played += 1;
+		},
+		None => {
+			if !app.quit {
+				println!("replay finished after {} frames", playback.played);
+			}
+			*delta = Duration::new(0, 0);
+			window_events.clear();
+			device_events.clear();
+			app.quit();
+		},
+	}
+}
+
+fn write_frame(
+	out: &mut impl Write,
+	delta: Duration,
+	window_events: &[WindowEvent],
+	device_events: &[DeviceEvent],
+	gamepad_events: &[GamepadEvent],
+) -> io::Result<()> {
+	writeln!(out, "frame {}", delta.as_nanos())?;
+	for event in window_events {
+		write_window_event(out, event)?;
+	}
+	for event in device_events {
+		write_device_event(out, event)?;
+	}
+	for event in gamepad_events {
+		write_gamepad_event(out, event)?;
+	}
+	Ok(())
+}
+
+fn state_name(state: ElementState) -> &'static str {
+	match state {
+		ElementState::Pressed => "pressed",
+		ElementState::Released => "released",
+	}
+}
+
+fn button_name(button: MouseButton) -> String {
+	match button {
+		MouseButton::Other(n) => n.to_string(),
+		_ => format!("{:?}", button),
+	}
+}
+
+fn write_key(out: &mut impl Write, kind: &str, key: &KeyboardInput) -> io::Result<()> {
+	let keycode = key.virtual_keycode.map_or("-".to_owned(), |keycode| format!("{:?}", keycode));
+	writeln!(out, "{} {} {} {}", kind, key.scancode, state_name(key.state), keycode)
+}
+
+fn write_wheel(out: &mut impl Write, kind: &str, delta: &MouseScrollDelta) -> io::Result<()> {
+	match delta {
+		MouseScrollDelta::LineDelta(x, y) => writeln!(out, "{} lines {} {}", kind, x, y),
+		MouseScrollDelta::PixelDelta(pos) => writeln!(out, "{} pixels {} {}", kind, pos.x, pos.y),
+	}
+}
+
+fn write_window_event(out: &mut impl Write, event: &WindowEvent) -> io::Result<()> {
+	match event {
+		WindowEvent::Resized(size) => writeln!(out, "resized {} {}", size.width, size.height),
+		WindowEvent::Focused(focused) => writeln!(out, "focused {}", focused),
+		WindowEvent::ReceivedCharacter(c) => writeln!(out, "char {}", *c as u32),
+		WindowEvent::KeyboardInput { input, .. } => write_key(out, "key", input),
+		WindowEvent::ModifiersChanged(modifiers) => writeln!(out, "modifiers {}", modifiers.bits()),
+		WindowEvent::CursorMoved { position, .. } => writeln!(out, "cursor {} {}", position.x, position.y),
+		WindowEvent::CursorEntered { .. } => writeln!(out, "entered"),
+		WindowEvent::CursorLeft { .. } => writeln!(out, "left"),
+		WindowEvent::MouseWheel { delta, .. } => write_wheel(out, "wheel", delta),
+		WindowEvent::MouseInput { state, button, .. } => {
+			writeln!(out, "mouse {} {}", button_name(*button), state_name(*state))
+		},
+		_ => Ok(()),
+	}
+}
+
+fn write_device_event(out: &mut impl Write, event: &DeviceEvent) -> io::Result<()> {
+	match event {
+		DeviceEvent::MouseMotion { delta: (x, y) } => writeln!(out, "motion {} {}", x, y),
+		DeviceEvent::MouseWheel { delta } => write_wheel(out, "device_wheel", delta),
+		DeviceEvent::Button { button, state } => writeln!(out, "device_button {} {}", button, state_name(*state)),
+		DeviceEvent::Key(key) => write_key(out, "device_key", key),
+		_ => Ok(()),
+	}
+}
+
+fn write_gamepad_event(out: &mut impl Write, event: &GamepadEvent) -> io::Result<()> {
+	match event {
+		GamepadEvent::Connected { pad, name } => writeln!(out, "pad_connected {} {}", pad, name),
+		GamepadEvent::Disconnected { pad } => writeln!(out, "pad_disconnected {}", pad),
+		GamepadEvent::Button { pad, button, down } => writeln!(out, "pad_button {} {:?} {}", pad, button, down),
+		GamepadEvent::Axis { pad, axis, value } => writeln!(out, "pad_axis {} {:?} {}", pad, axis, value),
+	}
+}
+
+/// Reads a recording's frames.
+fn parse(src: &str) -> Result<VecDeque<Frame>, String> {
+	let mut lines = src.lines().enumerate();
+	match lines.next() {
+		Some((_, HEADER)) => (),
+		_ => return Err(format!("not a recording, expected `{}` on the first line", HEADER)),
+	}
+
+	let mut frames = VecDeque::new();
+	for (i, line) in lines {
+		let err = |e: String| format!("line {}: {}", i + 1, e);
+		let mut words = line.split_whitespace();
+		let kind = match words.next() {
+			Some(kind) => kind,
+			None => continue,
+		};
+		if kind == "frame" {
+			let nanos: u64 = next(&mut words).map_err(err)?;
+			frames.push_back(Frame {
+				delta: Duration::from_nanos(nanos),
+				window_events: vec![],
+				device_events: vec![],
+				gamepad_events: vec![],
+			});
+			continue;
+		}
+		let frame = frames.back_mut().ok_or_else(|| err("event before the first frame".to_owned()))?;
+		parse_event(frame, kind, &mut words).map_err(err)?;
+	}
+	Ok(frames)
+}
+
+// Deprecated fields still have to be filled in
+#[allow(deprecated)]
+fn parse_event(frame: &mut Frame, kind: &str, words: &mut SplitWhitespace) -> Result<(), String> {
+	// Nothing reads device ids, and winit has no other way to make one
+	let device_id = unsafe { DeviceId::dummy() };
+	let modifiers = ModifiersState::empty();
+
+	let window_event = match kind {
+		"resized" => WindowEvent::Resized(PhysicalSize::new(next(words)?, next(words)?)),
+		"focused" => WindowEvent::Focused(next(words)?),
+		"char" => {
+			let c = std::char::from_u32(next(words)?).ok_or_else(|| "bad character".to_owned())?;
+			WindowEvent::ReceivedCharacter(c)
+		},
+		"key" => WindowEvent::KeyboardInput { device_id, input: parse_key_input(words)?, is_synthetic: false },
+		"modifiers" => {
+			let bits = next(words)?;
+			let modifiers = ModifiersState::from_bits(bits).ok_or_else(|| format!("bad modifiers {}", bits))?;
+			WindowEvent::ModifiersChanged(modifiers)
+		},
+		"cursor" => {
+			let position = PhysicalPosition::new(next(words)?, next(words)?);
+			WindowEvent::CursorMoved { device_id, position, modifiers }
+		},
+		"entered" => WindowEvent::CursorEntered { device_id },
+		"left" => WindowEvent::CursorLeft { device_id },
+		"wheel" => {
+			let delta = parse_wheel(words)?;
+			WindowEvent::MouseWheel { device_id, delta, phase: TouchPhase::Moved, modifiers }
+		},
+		"mouse" => {
+			let button = match words.next() {
+				Some("Left") => MouseButton::Left,
+				Some("Right") => MouseButton::Right,
+				Some("Middle") => MouseButton::Middle,
+				Some(word) => MouseButton::Other(word.parse().map_err(|_| format!("bad mouse button {}", word))?),
+				None => return Err("missing mouse button".to_owned()),
+			};
+			WindowEvent::MouseInput { device_id, state: parse_state(words)?, button, modifiers }
+		},
+		_ => return parse_other_event(frame, kind, words),
+	};
+	frame.window_events.push(window_event);
+	Ok(())
+}
+
+fn parse_other_event(frame: &mut Frame, kind: &str, words: &mut SplitWhitespace) -> Result<(), String> {
+	let device_event = match kind {
+		"motion" => DeviceEvent::MouseMotion { delta: (next(words)?, next(words)?) },
+		"device_wheel" => DeviceEvent::MouseWheel { delta: parse_wheel(words)? },
+		"device_button" => DeviceEvent::Button { button: next(words)?, state: parse_state(words)? },
+		"device_key" => DeviceEvent::Key(parse_key_input(words)?),
+		_ => {
+			frame.gamepad_events.push(parse_gamepad_event(kind, words)?);
+			return Ok(());
+		},
+	};
+	frame.device_events.push(device_event);
+	Ok(())
+}
+
+fn parse_gamepad_event(kind: &str, words: &mut SplitWhitespace) -> Result<GamepadEvent, String> {
+	let event = match kind {
+		"pad_connected" => GamepadEvent::Connected { pad: next(words)?, name: words.collect::<Vec<_>>().join(" ") },
+		"pad_disconnected" => GamepadEvent::Disconnected { pad: next(words)? },
+		"pad_button" => GamepadEvent::Button { pad: next(words)?, button: named(words, &BUTTONS)?, down: next(words)? },
+		"pad_axis" => GamepadEvent::Axis { pad: next(words)?, axis: named(words, &AXES)?, value: next(words)? },
+		_ => return Err(format!("unknown event {}", kind)),
+	};
+	Ok(event)
+}
+
+#[allow(deprecated)]
+fn parse_key_input(words: &mut SplitWhitespace) -> Result<KeyboardInput, String> {
+	let scancode = next(words)?;
+	let state = parse_state(words)?;
+	let virtual_keycode = match words.next() {
+		Some("-") => None,
+		Some(name) => Some(parse_key(name).ok_or_else(|| format!("unknown key {}", name))?),
+		None => return Err("missing key".to_owned()),
+	};
+	Ok(KeyboardInput { scancode, state, virtual_keycode, modifiers: ModifiersState::empty() })
+}
+
+fn parse_state(words: &mut SplitWhitespace) -> Result<ElementState, String> {
+	match words.next() {
+		Some("pressed") => Ok(ElementState::Pressed),
+		Some("released") => Ok(ElementState::Released),
+		word => Err(format!("expected `pressed` or `released`, got {:?}", word)),
+	}
+}
+
+fn parse_wheel(words: &mut SplitWhitespace) -> Result<MouseScrollDelta, String> {
+	match words.next() {
+		Some("lines") => Ok(MouseScrollDelta::LineDelta(next(words)?, next(words)?)),
+		Some("pixels") => {
+			let (x, y): (f64, f64) = (next(words)?, next(words)?);
+			Ok(MouseScrollDelta::PixelDelta((x, y).into()))
+		},
+		word => Err(format!("expected `lines` or `pixels`, got {:?}", word)),
+	}
+}
+
+/// The next word, parsed
+fn next<T: FromStr>(words: &mut SplitWhitespace) -> Result<T, String> {
+	let word = words.next().ok_or_else(|| "missing value".to_owned())?;
+	word.parse().map_err(|_| format!("bad value {}", word))
+}
+
+/// The value in `values` whose debug name is the next word
+fn named<T: Copy + Debug>(words: &mut SplitWhitespace, values: &[T]) -> Result<T, String> {
+	let word = words.next().ok_or_else(|| "missing name".to_owned())?;
+	values.iter().copied().find(|value| format!("{:?}", value) == word).ok_or_else(|| format!("unknown name {}", word))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		components::player_controller::PlayerController,
+		systems::{
+			cursor::Cursor,
+			gamepad::GamepadInput,
+			input::{update_input, GamepadAxis, GamepadButton, InputMap},
+			player::update_player,
+		},
+	};
+	use glutin::event::VirtualKeyCode;
+	use nalgebra::Vector3;
+	use shipyard::World;
+	use std::f32::consts::PI;
+
+	#[allow(deprecated)]
+	fn key(scancode: u32, state: ElementState, virtual_keycode: Option<VirtualKeyCode>) -> KeyboardInput {
+		KeyboardInput { scancode, state, virtual_keycode, modifiers: ModifiersState::empty() }
+	}
+
+	#[test]
+	#[allow(deprecated)]
+	fn every_event_round_trips() {
+		let device_id = unsafe { DeviceId::dummy() };
+		let modifiers = ModifiersState::empty();
+		let window_events = vec![
+			WindowEvent::Resized(PhysicalSize::new(1280, 720)),
+			WindowEvent::Focused(false),
+			WindowEvent::ReceivedCharacter('é'),
+			WindowEvent::KeyboardInput {
+				device_id,
+				input: key(17, ElementState::Pressed, Some(VirtualKeyCode::W)),
+				is_synthetic: false,
+			},
+			WindowEvent::KeyboardInput {
+				device_id,
+				input: key(99, ElementState::Released, Some(VirtualKeyCode::WebFavorites)),
+				is_synthetic: false,
+			},
+			WindowEvent::KeyboardInput { device_id, input: key(250, ElementState::Pressed, None), is_synthetic: false },
+			WindowEvent::ModifiersChanged(ModifiersState::SHIFT | ModifiersState::CTRL),
+			WindowEvent::CursorMoved { device_id, position: PhysicalPosition::new(10.25, 0.1), modifiers },
+			WindowEvent::CursorEntered { device_id },
+			WindowEvent::CursorLeft { device_id },
+			WindowEvent::MouseWheel {
+				device_id,
+				delta: MouseScrollDelta::LineDelta(0.0, -1.0),
+				phase: TouchPhase::Moved,
+				modifiers,
+			},
+			WindowEvent::MouseWheel {
+				device_id,
+				delta: MouseScrollDelta::PixelDelta((1.5, -20.125).into()),
+				phase: TouchPhase::Moved,
+				modifiers,
+			},
+			WindowEvent::MouseInput { device_id, state: ElementState::Pressed, button: MouseButton::Left, modifiers },
+			WindowEvent::MouseInput {
+				device_id,
+				state: ElementState::Released,
+				button: MouseButton::Other(7),
+				modifiers,
+			},
+		];
+		let device_events = vec![
+			DeviceEvent::MouseMotion { delta: (-3.0, 0.1) },
+			DeviceEvent::MouseWheel { delta: MouseScrollDelta::LineDelta(2.0, 0.5) },
+			DeviceEvent::Button { button: 3, state: ElementState::Pressed },
+			DeviceEvent::Key(key(30, ElementState::Released, Some(VirtualKeyCode::A))),
+		];
+		let gamepad_events = vec![
+			GamepadEvent::Connected { pad: 2, name: "Xbox Wireless Controller".to_owned() },
+			GamepadEvent::Button { pad: 2, button: GamepadButton::DPadLeft, down: true },
+			GamepadEvent::Axis { pad: 2, axis: GamepadAxis::LeftTrigger, value: 0.3 },
+			GamepadEvent::Disconnected { pad: 2 },
+		];
+
+		let mut out = format!("{}\n", HEADER).into_bytes();
+		write_frame(&mut out, Duration::from_micros(16_667), &window_events, &device_events, &gamepad_events).unwrap();
+		write_frame(&mut out, Duration::from_nanos(1), &[], &[], &[]).unwrap();
+		let frames = parse(&String::from_utf8(out).unwrap()).unwrap();
+
+		assert_eq!(frames.len(), 2);
+		assert_eq!(frames[0].delta, Duration::from_micros(16_667));
+		assert_eq!(frames[0].window_events, window_events);
+		assert_eq!(frames[0].device_events, device_events);
+		assert_eq!(frames[0].gamepad_events, gamepad_events);
+		assert_eq!(frames[1].delta, Duration::from_nanos(1));
+		assert!(frames[1].window_events.is_empty() && frames[1].device_events.is_empty());
+	}
+
+	#[test]
+	fn bad_recordings_are_errors() {
+		assert!(parse("frame 0\n").unwrap_err().starts_with("not a recording"));
+		let err = |src: &str| parse(&format!("{}\n{}", HEADER, src)).unwrap_err();
+		assert_eq!(err("key 1 pressed W"), "line 2: event before the first frame");
+		assert_eq!(err("frame 0\nkey 1 pressed NotAKey"), "line 3: unknown key NotAKey");
+		assert_eq!(err("frame 0\nmouse Left held"), "line 3: expected `pressed` or `released`, got Some(\"held\")");
+		assert_eq!(err("frame 0\nteleport"), "line 3: unknown event teleport");
+	}
+
+	/// Walks forward, turns left with the mouse, then strafes right, half a second per frame.
+	const WALK: &str = "replay 1
+frame 500000000
+key 17 pressed W
+frame 500000000
+resized 10 10
+frame 500000000
+key 17 released W
+motion -157.07963267948966 0
+frame 500000000
+key 32 pressed D
+frame 500000000
+key 32 released D
+";
+
+	#[test]
+	fn replay_drives_player() {
+		let bindings = "axis move_x = D - A\naxis move_y = W - S\n\
+		                axis look_x = MouseX * 0.01\naxis look_y = MouseY * 0.01";
+		let world = World::new();
+		world.add_unique(Application::default());
+		world.add_unique(InputMap::from_bindings(bindings).unwrap());
+		world.add_unique(PlayerController::new());
+		let mut cursor = Cursor::new();
+		cursor.grab();
+		world.add_unique(cursor);
+		world.add_unique(Duration::new(0, 0));
+		world.add_unique(Vec::<WindowEvent>::new());
+		world.add_unique(Vec::<DeviceEvent>::new());
+		let frames = parse(WALK).unwrap();
+		world.add_unique_non_send_sync(Playback { frames, played: 0, gamepads: GamepadInput::fake().1 });
+
+		let mut frames = 0;
+		loop {
+			world.run(play_frame);
+			if world.run(|app: UniqueView<Application>| app.quit) {
+				break;
+			}
+			world.run(|window_events: UniqueView<Vec<WindowEvent>>| {
+				assert!(window_events.iter().all(|event| !matches!(event, WindowEvent::Resized(_))));
+			});
+			world.run(update_input);
+			world.run(update_player);
+			frames += 1;
+		}
+		assert_eq!(frames, 5);
+
+		world.run(|player: UniqueView<PlayerController>| {
+			let cam = &player.cam;
+			// A second forward along +y, then a quarter turn left so strafing right also moves along +y
+			let pos = cam.uniform.pos;
+			assert!((pos - Vector3::new(0.0, 1.5, 0.0)).norm() < 1e-5, "{:?}", pos);
+			assert!((cam.yaw - PI / 2.0).abs() < 1e-5 && cam.pitch == 0.0, "{} {}", cam.yaw, cam.pitch);
+			let forward = cam.uniform.rot * Vector3::y();
+			assert!((forward - Vector3::new(-1.0, 0.0, 0.0)).norm() < 1e-5, "{:?}", forward);
+		});
+	}
+}
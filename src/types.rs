@@ -1 +1,10 @@
+pub mod assets;
 pub mod camera;
+pub mod debug_draw;
+pub mod file_watcher;
+pub mod font;
+pub mod frustum;
+pub mod material;
+pub mod simplify;
+pub mod text;
+pub mod ui;
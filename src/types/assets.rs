@@ -0,0 +1,120 @@
+use std::{
+	borrow::Cow,
+	env, fs,
+	path::{Path, PathBuf},
+};
+
+macro_rules! embed {
+	($($name:literal),* $(,)?) => {
+		&[$(($name, include_bytes!(concat!("../../assets/", $name)))),*]
+	};
+}
+
+/// Built into the binary so it runs without an assets directory.
+static EMBEDDED: &[(&str, &[u8])] = embed![
+	"baldman.dae",
+	"fonts/DejaVuSans.ttf",
+	"input.cfg",
+	"shaders/cull.comp",
+	"shaders/debug.frag",
+	"shaders/debug.vert",
+	"shaders/hiz.comp",
+	"shaders/ibl/brdf.comp",
+	"shaders/ibl/equirect.comp",
+	"shaders/ibl/ibl.glsl",
+	"shaders/ibl/irradiance.comp",
+	"shaders/ibl/prefilter.comp",
+	"shaders/include/camera.glsl",
+	"shaders/include/cubemap.glsl",
+	"shaders/include/environment.glsl",
+	"shaders/include/lights.glsl",
+	"shaders/include/material.glsl",
+	"shaders/include/quat.glsl",
+	"shaders/include/shadows.glsl",
+	"shaders/post/blur.frag",
+	"shaders/post/bright.frag",
+	"shaders/post/composite.frag",
+	"shaders/post/exposure.frag",
+	"shaders/post/fullscreen.vert",
+	"shaders/post/fxaa.frag",
+	"shaders/post/grade.frag",
+	"shaders/post/post.glsl",
+	"shaders/post/tonemap.frag",
+	"shaders/shader.frag",
+	"shaders/shader.vert",
+	"shaders/shadow.frag",
+	"shaders/shadow.vert",
+	"shaders/sky.frag",
+	"shaders/sky.vert",
+	"shaders/text.frag",
+	"shaders/text.vert",
+	"shaders/ui.vert",
+	"textures/brown_eye.png",
+	"textures/middleage_lightskinned_male_diffuse.png",
+];
+
+/// Resolves asset names like `"shaders/shader.vert"`. Files in the assets directory take priority over the copies
+/// embedded in the binary.
+#[derive(Clone, Debug)]
+pub struct Assets {
+	dir: Option<PathBuf>,
+}
+impl Assets {
+	/// Uses the first assets directory found among, in order: `--assets <dir>`, `$IMMORAL_ASSETS`, `assets` next to
+	/// the executable, and in debug builds `assets` in the crate root.
+	pub fn from_env() -> Self {
+		let mut args = env::args().skip_while(|arg| arg != "--assets").skip(1);
+		let flag = args.next().map(PathBuf::from);
+		let var = env::var_os("IMMORAL_ASSETS").map(PathBuf::from);
+		let exe = env::current_exe().ok().and_then(|exe| Some(exe.parent()?.join("assets")));
+		let crate_root = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets");
+		let crate_root = Some(crate_root).filter(|_| cfg!(debug_assertions));
+
+		let dir = flag.or(var).or_else(|| exe.filter(|dir| dir.is_dir())).or_else(|| crate_root.filter(|dir| dir.is_dir()));
+		Self { dir }
+	}
+
+	/// Reads from `dir` before the embedded copies.
+	#[cfg(test)]
+	pub fn from_dir(dir: PathBuf) -> Self {
+		Self { dir: Some(dir) }
+	}
+
+	/// The file `name` is read from, if it is on disk rather than embedded.
+	pub fn path(&self, name: &str) -> Option<PathBuf> {
+		self.dir.as_ref().map(|dir| dir.join(name)).filter(|path| path.is_file())
+	}
+
+	pub fn read(&self, name: &str) -> Result<Cow<'static, [u8]>, String> {
+		if let Some(path) = self.path(name) {
+			return fs::read(&path).map(Cow::Owned).map_err(|e| format!("{}: {}", path.display(), e));
+		}
+
+		EMBEDDED
+			.iter()
+			.find(|(embedded, _)| *embedded == name)
+			.map(|&(_, data)| Cow::Borrowed(data))
+			.ok_or_else(|| format!("{}: asset not found", name))
+	}
+
+	pub fn read_string(&self, name: &str) -> Result<String, String> {
+		let data = self.read(name)?;
+		String::from_utf8(data.into_owned()).map_err(|e| format!("{}: {}", name, e))
+	}
+}
+
+/// Resolves `name` relative to the directory of the asset `base`, handling `.` and `..`.
+pub fn relative(base: &str, name: &str) -> String {
+	let mut parts: Vec<_> = base.split('/').collect();
+	parts.pop();
+	for part in name.split(|c| c == '/' || c == '\\') {
+		match part {
+			"" | "." => (),
+			".." => {
+				parts.pop();
+			},
+			part => parts.push(part),
+		}
+	}
+	parts.join("/")
+}
@@ -10,6 +10,9 @@ pub struct Camera {
 	pub z_far: f32,
 	pub fov: f32,
 	pub aspect: f32,
+	/// Maps near to depth 1 and an infinite far plane to 0, ignoring `z_far`. Needs a [0, 1] clip range and a
+	/// GREATER depth test.
+	pub reversed_z: bool,
 }
 impl Camera {
 	pub fn new() -> Self {
@@ -22,6 +25,7 @@ impl Camera {
 			z_far: 1000.0,
 			fov: 45.0,
 			aspect: 1.0,
+			reversed_z: false,
 		}
 	}
 
@@ -45,8 +49,13 @@ impl Camera {
 		let fov_tan_inv: f32 = 1.0 / (self.fov * (PI/180.0)).tan();
 		self.uniform.proj[0] = fov_tan_inv * self.aspect;
 		self.uniform.proj[1] = fov_tan_inv;
-		self.uniform.proj[2] = (self.z_far + self.z_near) / (self.z_near - self.z_far);
-		self.uniform.proj[3] = 2.0 * self.z_far * self.z_near / (self.z_near - self.z_far);
+		if self.reversed_z {
+			self.uniform.proj[2] = 0.0;
+			self.uniform.proj[3] = self.z_near;
+		} else {
+			self.uniform.proj[2] = (self.z_far + self.z_near) / (self.z_near - self.z_far);
+			self.uniform.proj[3] = 2.0 * self.z_far * self.z_near / (self.z_near - self.z_far);
+		}
 	}
 }
 
@@ -0,0 +1,121 @@
+use crate::types::{
+	camera::Camera,
+	text::{TextStyle, WorldText},
+};
+use glrs::implement_vertex;
+use nalgebra::{UnitQuaternion, Vector3, Vector4};
+use std::f32::consts::PI;
+
+/// Segments of a sphere's circles
+const CIRCLE_SEGMENTS: usize = 32;
+
+/// Vertex of a debug line
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+pub struct DebugVertex {
+	pub pos: Vector3<f32>,
+	pub color: Vector4<f32>,
+}
+implement_vertex!(DebugVertex, pos, color);
+
+/// Lines and labels any system can push to, drawn over the scene at the end of the frame and cleared after.
+pub struct DebugDraw {
+	/// Whether shapes pushed from now on are hidden behind the scene. Back to true every frame.
+	pub depth_test: bool,
+	/// Line list vertices, indexed by whether they're depth tested
+	lines: [Vec<DebugVertex>; 2],
+	texts: Vec<WorldText>,
+}
+impl DebugDraw {
+	pub fn new() -> Self {
+		Self { depth_test: true, lines: [vec![], vec![]], texts: vec![] }
+	}
+
+	pub fn clear(&mut self) {
+		self.depth_test = true;
+		self.lines[0].clear();
+		self.lines[1].clear();
+		self.texts.clear();
+	}
+
+	pub fn line(&mut self, from: Vector3<f32>, to: Vector3<f32>, color: Vector4<f32>) {
+		let lines = &mut self.lines[self.depth_test as usize];
+		lines.push(DebugVertex { pos: from, color });
+		lines.push(DebugVertex { pos: to, color });
+	}
+
+	pub fn aabb(&mut self, min: &Vector3<f32>, max: &Vector3<f32>, color: Vector4<f32>) {
+		let corner = |i: usize| {
+			Vector3::new(
+				if i & 1 == 0 { min.x } else { max.x },
+				if i & 2 == 0 { min.y } else { max.y },
+				if i & 4 == 0 { min.z } else { max.z },
+			)
+		};
+		for i in 0..8 {
+			// Each corner connects to the three that differ in one axis, counted once from the lower end
+			for axis in &[1, 2, 4] {
+				if i & axis == 0 {
+					self.line(corner(i), corner(i | axis), color);
+				}
+			}
+		}
+	}
+
+	/// Circles around each axis.
+	pub fn sphere(&mut self, center: &Vector3<f32>, radius: f32, color: Vector4<f32>) {
+		let axes = [(Vector3::x(), Vector3::y()), (Vector3::y(), Vector3::z()), (Vector3::z(), Vector3::x())];
+		for (u, v) in &axes {
+			let point = |i: usize| {
+				let angle = i as f32 / CIRCLE_SEGMENTS as f32 * 2.0 * PI;
+				center + (u * angle.cos() + v * angle.sin()) * radius
+			};
+			for i in 0..CIRCLE_SEGMENTS {
+				self.line(point(i), point(i + 1), color);
+			}
+		}
+	}
+
+	/// X, Y and Z in red, green and blue.
+	pub fn axes(&mut self, pos: &Vector3<f32>, rot: &UnitQuaternion<f32>, size: f32) {
+		self.line(*pos, pos + rot * Vector3::x() * size, Vector4::new(1.0, 0.0, 0.0, 1.0));
+		self.line(*pos, pos + rot * Vector3::y() * size, Vector4::new(0.0, 1.0, 0.0, 1.0));
+		self.line(*pos, pos + rot * Vector3::z() * size, Vector4::new(0.0, 0.0, 1.0, 1.0));
+	}
+
+	/// What `cam` sees, out to `z_far` even with reversed-Z.
+	pub fn frustum(&mut self, cam: &Camera, color: Vector4<f32>) {
+		let rot = cam.uniform.rot;
+		let (right, forward, up) = (rot * Vector3::x(), rot * Vector3::y(), rot * Vector3::z());
+		let tan_y = cam.fov.to_radians().tan();
+		let tan_x = tan_y / cam.aspect;
+		let rect = |d: f32| {
+			let (x, y) = (right * d * tan_x, up * d * tan_y);
+			let center = cam.uniform.pos + forward * d;
+			[center - x - y, center + x - y, center + x + y, center - x + y]
+		};
+		let (near, far) = (rect(cam.z_near), rect(cam.z_far));
+		for i in 0..4 {
+			self.line(near[i], near[(i + 1) % 4], color);
+			self.line(far[i], far[(i + 1) % 4], color);
+			self.line(near[i], far[i], color);
+		}
+	}
+
+	/// Label facing the camera, `height` world units per em, with its top left at `pos`.
+	pub fn text_3d(&mut self, pos: &Vector3<f32>, text: &str, height: f32, color: Vector4<f32>) {
+		let style = TextStyle::new(height, color);
+		let depth_test = self.depth_test;
+		self.texts.push(WorldText { pos: *pos, text: text.to_owned(), style, depth_test });
+	}
+
+	/// Line list of everything pushed this frame with depth testing on or off.
+	pub fn vertices(&self, depth_test: bool) -> &[DebugVertex] {
+		&self.lines[depth_test as usize]
+	}
+
+	/// Labels, drawn with the text renderer.
+	pub fn texts(&self) -> &[WorldText] {
+		&self.texts
+	}
+}
@@ -0,0 +1,47 @@
+use std::{
+	collections::HashMap,
+	fs,
+	path::{Path, PathBuf},
+	time::{Duration, Instant, SystemTime},
+};
+
+/// Polls modification times of watched files.
+pub struct FileWatcher {
+	files: HashMap<PathBuf, Option<SystemTime>>,
+	last_poll: Instant,
+	pub interval: Duration,
+}
+impl FileWatcher {
+	pub fn new() -> Self {
+		Self { files: HashMap::new(), last_poll: Instant::now(), interval: Duration::from_millis(500) }
+	}
+
+	pub fn watch(&mut self, path: impl AsRef<Path>) {
+		let path = path.as_ref();
+		if !self.files.contains_key(path) {
+			self.files.insert(path.to_owned(), modified(path));
+		}
+	}
+
+	/// Returns the files that changed since the last poll. Does nothing until `interval` has passed.
+	pub fn poll(&mut self) -> Vec<PathBuf> {
+		if self.last_poll.elapsed() < self.interval {
+			return vec![];
+		}
+		self.last_poll = Instant::now();
+
+		let mut changed = vec![];
+		for (path, time) in &mut self.files {
+			let new_time = modified(path);
+			if new_time.is_some() && new_time != *time {
+				*time = new_time;
+				changed.push(path.clone());
+			}
+		}
+		changed
+	}
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+	fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
@@ -0,0 +1,119 @@
+use crate::types::assets::Assets;
+use rusttype::{point, GlyphId, Scale};
+
+/// Glyph placed by `Font::layout`
+#[derive(Clone, Copy, Debug)]
+pub struct LaidGlyph {
+	pub id: GlyphId,
+	/// Pen position on the baseline, in pixels from the top left of the text with y down
+	pub pos: [f32; 2],
+	pub line: usize,
+}
+
+pub struct TextLayout {
+	pub glyphs: Vec<LaidGlyph>,
+	/// Width of each line without trailing whitespace
+	pub line_widths: Vec<f32>,
+	pub line_height: f32,
+}
+impl TextLayout {
+	pub fn width(&self) -> f32 {
+		self.line_widths.iter().cloned().fold(0.0, f32::max)
+	}
+
+	pub fn height(&self) -> f32 {
+		self.line_widths.len() as f32 * self.line_height
+	}
+}
+
+/// Coverage of a rasterized glyph
+pub struct GlyphBitmap {
+	/// Top left relative to the pen position, y down
+	pub offset: [i32; 2],
+	pub size: [u32; 2],
+	/// One byte per pixel, rows top to bottom
+	pub coverage: Vec<u8>,
+}
+
+/// A TTF or OTF font.
+pub struct Font {
+	font: rusttype::Font<'static>,
+}
+impl Font {
+	pub fn load(assets: &Assets, name: &str) -> Result<Self, String> {
+		let data = assets.read(name)?.into_owned();
+		let font = rusttype::Font::try_from_vec(data).ok_or_else(|| format!("{}: not a TTF or OTF font", name))?;
+		Ok(Self { font })
+	}
+
+	/// Lays out `text` at `size` pixels per em. Lines break at newlines, and at spaces before words that would end
+	/// past `wrap` pixels. Words longer than `wrap` overflow.
+	pub fn layout(&self, text: &str, size: f32, wrap: Option<f32>) -> TextLayout {
+		let scale = Scale::uniform(size);
+		let v_metrics = self.font.v_metrics(scale);
+		let line_height = v_metrics.ascent - v_metrics.descent + v_metrics.line_gap;
+
+		let mut glyphs: Vec<LaidGlyph> = vec![];
+		let mut line_widths = vec![0.0];
+		let (mut x, mut line) = (0.0, 0);
+		let mut last = None;
+		// First glyph after the last space on this line, with the pen position there and the line's width before it
+		let mut wrap_at: Option<(usize, f32, f32)> = None;
+		for c in text.chars() {
+			if c == '\n' {
+				line += 1;
+				line_widths.push(0.0);
+				x = 0.0;
+				last = None;
+				wrap_at = None;
+				continue;
+			}
+
+			let glyph = self.font.glyph(c).scaled(scale);
+			let id = glyph.id();
+			if let Some(last) = last {
+				x += self.font.pair_kerning(scale, last, id);
+			}
+			last = Some(id);
+			let advance = glyph.h_metrics().advance_width;
+			if c.is_whitespace() {
+				x += advance;
+				wrap_at = Some((glyphs.len(), x, line_widths[line]));
+				continue;
+			}
+
+			if let (Some(wrap), Some((start, start_x, width))) = (wrap, wrap_at) {
+				if x + advance > wrap {
+					// Move the word so far to the next line
+					for glyph in &mut glyphs[start..] {
+						glyph.pos[0] -= start_x;
+						glyph.line += 1;
+					}
+					line_widths[line] = width;
+					line += 1;
+					line_widths.push(0.0);
+					x -= start_x;
+					wrap_at = None;
+				}
+			}
+			glyphs.push(LaidGlyph { id, pos: [x, 0.0], line });
+			x += advance;
+			line_widths[line] = x;
+		}
+
+		for glyph in &mut glyphs {
+			glyph.pos[1] = glyph.line as f32 * line_height + v_metrics.ascent;
+		}
+		TextLayout { glyphs, line_widths, line_height }
+	}
+
+	/// Renders `id` at `size` pixels per em. Glyphs without an outline, like spaces, give `None`.
+	pub fn rasterize(&self, id: GlyphId, size: f32) -> Option<GlyphBitmap> {
+		let glyph = self.font.glyph(id).scaled(Scale::uniform(size)).positioned(point(0.0, 0.0));
+		let bounds = glyph.pixel_bounding_box()?;
+		let (width, height) = (bounds.width() as u32, bounds.height() as u32);
+		let mut coverage = vec![0; (width * height) as usize];
+		glyph.draw(|x, y, v| coverage[(y * width + x) as usize] = (v * 255.0).round() as u8);
+		Some(GlyphBitmap { offset: [bounds.min.x, bounds.min.y], size: [width, height], coverage })
+	}
+}
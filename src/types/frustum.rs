@@ -0,0 +1,202 @@
+use crate::types::camera::Camera;
+use nalgebra::{Matrix4, Point3, Vector3, Vector4};
+
+/// Axis aligned box and bounding sphere around a mesh.
+#[derive(Clone, Copy, Debug)]
+pub struct Bounds {
+	pub min: Vector3<f32>,
+	pub max: Vector3<f32>,
+	pub center: Vector3<f32>,
+	pub radius: f32,
+}
+impl Bounds {
+	/// Empty point set gives a zero sized box at the origin.
+	pub fn from_points<'a>(points: impl Iterator<Item = &'a Vector3<f32>> + Clone) -> Self {
+		let inf = Vector3::repeat(f32::INFINITY);
+		let (min, max) = points.clone().fold((inf, -inf), |(min, max), p| (min.inf(p), max.sup(p)));
+		if min.x > max.x {
+			return Self { min: Vector3::zeros(), max: Vector3::zeros(), center: Vector3::zeros(), radius: 0.0 };
+		}
+		let center = (min + max) / 2.0;
+		let radius = points.map(|p| (p - center).norm()).fold(0.0, f32::max);
+		Self { min, max, center, radius }
+	}
+
+	/// Box around the transformed box, and the sphere moved and grown by the largest scale.
+	pub fn transformed(&self, transform: &Matrix4<f32>) -> Self {
+		let corners: Vec<_> = (0..8)
+			.map(|i| {
+				let pick = |axis: usize| if i >> axis & 1 == 0 { self.min[axis] } else { self.max[axis] };
+				transform.transform_point(&Point3::new(pick(0), pick(1), pick(2))).coords
+			})
+			.collect();
+		let Bounds { min, max, .. } = Self::from_points(corners.iter());
+		let center = transform.transform_point(&Point3::from(self.center)).coords;
+		let scale = (0..3).map(|axis| transform.column(axis).xyz().norm()).fold(0.0, f32::max);
+		Self { min, max, center, radius: self.radius * scale }
+	}
+}
+
+/// Planes bounding what a camera sees, in world space with normals pointing inwards.
+#[derive(Clone, Copy, Debug)]
+pub struct Frustum {
+	/// xyz is the normal, w the distance along it from the origin negated
+	planes: [Vector4<f32>; 6],
+	/// Reversed-Z cameras have no far plane
+	count: usize,
+}
+impl Frustum {
+	pub fn new(cam: &Camera) -> Self {
+		let rot = cam.uniform.rot;
+		let pos = cam.uniform.pos;
+		let (right, forward, up) = (rot * Vector3::x(), rot * Vector3::y(), rot * Vector3::z());
+		let tan_y = cam.fov.to_radians().tan();
+		let tan_x = tan_y / cam.aspect;
+
+		let plane = |normal: Vector3<f32>, point: Vector3<f32>| {
+			let normal = normal.normalize();
+			Vector4::new(normal.x, normal.y, normal.z, -normal.dot(&point))
+		};
+		let planes = [
+			plane(right + forward * tan_x, pos),
+			plane(-right + forward * tan_x, pos),
+			plane(up + forward * tan_y, pos),
+			plane(-up + forward * tan_y, pos),
+			plane(forward, pos + forward * cam.z_near),
+			plane(-forward, pos + forward * cam.z_far),
+		];
+		Self { planes, count: if cam.reversed_z { 5 } else { 6 } }
+	}
+
+	pub fn planes(&self) -> &[Vector4<f32>] {
+		&self.planes[..self.count]
+	}
+
+	pub fn contains_sphere(&self, center: &Vector3<f32>, radius: f32) -> bool {
+		self.planes().iter().all(|p| p.xyz().dot(center) + p.w >= -radius)
+	}
+
+	/// Conservative: boxes near a corner of the frustum can pass without being visible.
+	pub fn contains_box(&self, min: &Vector3<f32>, max: &Vector3<f32>) -> bool {
+		self.planes().iter().all(|p| {
+			// The corner furthest along the normal
+			let corner = Vector3::new(
+				if p.x >= 0.0 { max.x } else { min.x },
+				if p.y >= 0.0 { max.y } else { min.y },
+				if p.z >= 0.0 { max.z } else { min.z },
+			);
+			p.xyz().dot(&corner) + p.w >= 0.0
+		})
+	}
+
+	/// Sphere first since it's cheaper, then the tighter box.
+	pub fn intersects(&self, bounds: &Bounds) -> bool {
+		self.contains_sphere(&bounds.center, bounds.radius) && self.contains_box(&bounds.min, &bounds.max)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// At the origin looking down +y. With a 45 degree fov and square aspect the side planes are at |x| = y and
+	/// |z| = y, near at y = 0.1 and far at y = 1000.
+	fn frustum(reversed_z: bool) -> Frustum {
+		let mut cam = Camera::new();
+		cam.reversed_z = reversed_z;
+		cam.update();
+		Frustum::new(&cam)
+	}
+
+	fn aabb(min: [f32; 3], max: [f32; 3]) -> Bounds {
+		Bounds::from_points([Vector3::from(min), Vector3::from(max)].iter())
+	}
+
+	#[test]
+	fn box_inside() {
+		assert!(frustum(false).intersects(&aabb([-1.0, 9.0, -1.0], [1.0, 11.0, 1.0])));
+	}
+
+	#[test]
+	fn box_outside_each_plane() {
+		let frustum = frustum(false);
+		let boxes = [
+			("left", aabb([-103.0, 9.0, -1.0], [-101.0, 11.0, 1.0])),
+			("right", aabb([101.0, 9.0, -1.0], [103.0, 11.0, 1.0])),
+			("bottom", aabb([-1.0, 9.0, -103.0], [1.0, 11.0, -101.0])),
+			("top", aabb([-1.0, 9.0, 101.0], [1.0, 11.0, 103.0])),
+			("near", aabb([-1.0, -3.0, -1.0], [1.0, -1.0, 1.0])),
+			("far", aabb([-1.0, 1001.0, -1.0], [1.0, 1003.0, 1.0])),
+		];
+		for (plane, bounds) in &boxes {
+			assert!(!frustum.intersects(bounds), "box past the {} plane wasn't culled", plane);
+		}
+	}
+
+	#[test]
+	fn box_straddling_each_plane() {
+		let frustum = frustum(false);
+		let boxes = [
+			("left", aabb([-11.0, 9.0, -1.0], [-9.0, 11.0, 1.0])),
+			("right", aabb([9.0, 9.0, -1.0], [11.0, 11.0, 1.0])),
+			("bottom", aabb([-1.0, 9.0, -11.0], [1.0, 11.0, -9.0])),
+			("top", aabb([-1.0, 9.0, 9.0], [1.0, 11.0, 11.0])),
+			("near", aabb([-0.01, -1.0, -0.01], [0.01, 1.0, 0.01])),
+			("far", aabb([-1.0, 999.0, -1.0], [1.0, 1001.0, 1.0])),
+		];
+		for (plane, bounds) in &boxes {
+			assert!(frustum.intersects(bounds), "box across the {} plane was culled", plane);
+		}
+	}
+
+	#[test]
+	fn sphere_on_plane() {
+		let frustum = frustum(false);
+		let on_plane = Vector3::new(-10.0, 10.0, 0.0);
+		assert!(frustum.contains_sphere(&on_plane, 0.0));
+
+		// Half a unit outside the left plane
+		let outward = Vector3::new(-1.0, -1.0, 0.0).normalize();
+		let outside = on_plane + outward * 0.5;
+		assert!(frustum.contains_sphere(&outside, 1.0));
+		assert!(!frustum.contains_sphere(&outside, 0.25));
+	}
+
+	#[test]
+	fn reversed_z_has_no_far_plane() {
+		let distant = aabb([-1.0, 5000.0, -1.0], [1.0, 5002.0, 1.0]);
+		let (normal, reversed) = (frustum(false), frustum(true));
+		assert_eq!(normal.planes().len(), 6);
+		assert_eq!(reversed.planes().len(), 5);
+		assert!(!normal.intersects(&distant));
+		assert!(reversed.intersects(&distant));
+
+		// The other planes match
+		let behind = aabb([-1.0, -3.0, -1.0], [1.0, -1.0, 1.0]);
+		assert!(!reversed.intersects(&behind));
+		assert_eq!(normal.planes()[..5], reversed.planes()[..]);
+	}
+
+	#[test]
+	fn transformed_bounds() {
+		let bounds = aabb([-1.0, -1.0, -1.0], [1.0, 1.0, 1.0]);
+		let moved = bounds.transformed(&Matrix4::new_translation(&Vector3::new(0.0, 10.0, 0.0)));
+		assert_eq!(moved.min, Vector3::new(-1.0, 9.0, -1.0));
+		assert_eq!(moved.max, Vector3::new(1.0, 11.0, 1.0));
+		assert_eq!(moved.center, Vector3::new(0.0, 10.0, 0.0));
+		assert_eq!(moved.radius, bounds.radius);
+
+		// An eighth of a turn about z widens the box in x and y, the sphere only grows with the scale
+		let rotation = Matrix4::new_rotation(Vector3::z() * std::f32::consts::FRAC_PI_4);
+		let turned = bounds.transformed(&(rotation * Matrix4::new_nonuniform_scaling(&Vector3::repeat(2.0))));
+		assert!((turned.max - Vector3::new(2.0 * 2f32.sqrt(), 2.0 * 2f32.sqrt(), 2.0)).norm() < 1e-5);
+		assert!((turned.min + turned.max).norm() < 1e-5);
+		assert!((turned.radius - bounds.radius * 2.0).abs() < 1e-5);
+
+		// Culled behind the camera, visible in front of it
+		let frustum = frustum(false);
+		let behind = Matrix4::new_translation(&Vector3::new(0.0, -10.0, 0.0));
+		assert!(!frustum.intersects(&bounds.transformed(&behind)));
+		assert!(frustum.intersects(&moved));
+	}
+}
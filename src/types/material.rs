@@ -0,0 +1,247 @@
+use crate::types::assets::relative;
+use assimp_sys::{
+	aiGetMaterialColor, aiGetMaterialFloatArray, aiGetMaterialTexture, AiColor4D, AiMaterial, AiReturn, AiString,
+	AiTextureType,
+};
+use image::RgbaImage;
+use nalgebra::{Vector3, Vector4};
+use std::{ffi::CStr, ptr, slice, str};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AlphaMode {
+	Opaque,
+	/// Discards fragments with alpha below the cutoff
+	Mask(f32),
+	Blend,
+}
+
+/// Metallic-roughness PBR material. Maps are texture asset names.
+#[derive(Clone, Debug)]
+pub struct Material {
+	pub base_color: Vector4<f32>,
+	pub metallic: f32,
+	pub roughness: f32,
+	pub emissive: Vector3<f32>,
+	pub normal_scale: f32,
+	pub occlusion_strength: f32,
+	pub alpha_mode: AlphaMode,
+	pub base_color_map: Option<String>,
+	/// Roughness in green and metallic in blue, as in glTF
+	pub metallic_roughness_map: Option<String>,
+	pub normal_map: Option<String>,
+	pub emissive_map: Option<String>,
+	pub occlusion_map: Option<String>,
+}
+impl Material {
+	/// Reads the material from assimp properties. This version of assimp has no PBR keys, so diffuse, opacity and
+	/// shininess are converted, and the metallic-roughness and occlusion maps come from the slots assimp's glTF
+	/// importer uses for them (unknown and lightmap). Map names are resolved relative to the asset `model`.
+	pub fn from_assimp(mat: &AiMaterial, model: &str) -> Self {
+		let diffuse = color(mat, b"$clr.diffuse\0").unwrap_or(AiColor4D { r: 1.0, g: 1.0, b: 1.0, a: 1.0 });
+		let opacity = float(mat, b"$mat.opacity\0").unwrap_or(1.0);
+		let emissive = color(mat, b"$clr.emissive\0").unwrap_or(AiColor4D { r: 0.0, g: 0.0, b: 0.0, a: 0.0 });
+		// Beckmann-style conversion from a Phong exponent
+		let roughness = float(mat, b"$mat.shininess\0").map_or(1.0, |s| (2.0 / (s.max(0.0) + 2.0)).sqrt());
+		let bump_scale = float(mat, b"$mat.bumpscaling\0").unwrap_or(1.0);
+
+		let map = |ty| texture(mat, ty).map(|path| relative(model, &path));
+		let opacity_map = map(AiTextureType::Opacity);
+		let alpha_mode = if opacity < 1.0 || opacity_map.is_some() { AlphaMode::Blend } else { AlphaMode::Opaque };
+
+		Self {
+			base_color: Vector4::new(diffuse.r, diffuse.g, diffuse.b, diffuse.a * opacity),
+			metallic: 0.0,
+			roughness,
+			emissive: Vector3::new(emissive.r, emissive.g, emissive.b),
+			normal_scale: bump_scale,
+			occlusion_strength: 1.0,
+			alpha_mode,
+			base_color_map: map(AiTextureType::Diffuse),
+			metallic_roughness_map: map(AiTextureType::Unknown),
+			normal_map: map(AiTextureType::Normals),
+			emissive_map: map(AiTextureType::Emissive),
+			occlusion_map: map(AiTextureType::Lightmap),
+		}
+	}
+
+	/// Switches opaque materials to alpha testing or blending if `base_color_map` has transparent texels. Over a tenth
+	/// of the texture partly transparent blends; less than that with some fully transparent texels is a cutout, like
+	/// foliage. A few anti-aliased edge texels alone leave it opaque.
+	pub fn detect_alpha(&mut self, base_color_map: &RgbaImage) {
+		if self.alpha_mode != AlphaMode::Opaque {
+			return;
+		}
+		let (mut clear, mut partial) = (0usize, 0usize);
+		for px in base_color_map.pixels() {
+			match px[3] {
+				0..=15 => clear += 1,
+				16..=239 => partial += 1,
+				_ => (),
+			}
+		}
+		let (width, height) = base_color_map.dimensions();
+		if partial * 10 > width as usize * height as usize {
+			self.alpha_mode = AlphaMode::Blend;
+		} else if clear > 0 {
+			self.alpha_mode = AlphaMode::Mask(0.5);
+		}
+	}
+
+	/// Every map, and whether it holds sRGB colors rather than linear data.
+	pub fn maps(&self) -> impl Iterator<Item = (&String, bool)> {
+		let color = self.base_color_map.iter().chain(&self.emissive_map).map(|map| (map, true));
+		let data = self.metallic_roughness_map.iter().chain(&self.normal_map).chain(&self.occlusion_map);
+		color.chain(data.map(|map| (map, false)))
+	}
+
+	/// `layer` gives the texture array layer of a map, in the sRGB array if the flag is set.
+	pub fn uniform(&self, layer: impl Fn(&str, bool) -> i32) -> MaterialUniform {
+		let color = |map: &Option<String>| map.as_ref().map_or(-1, |name| layer(name, true));
+		let map = |map: &Option<String>| map.as_ref().map_or(-1, |name| layer(name, false));
+		let (alpha_mode, alpha_cutoff) = match self.alpha_mode {
+			AlphaMode::Opaque => (0, 0.0),
+			AlphaMode::Mask(cutoff) => (1, cutoff),
+			AlphaMode::Blend => (2, 0.0),
+		};
+		MaterialUniform {
+			base_color: self.base_color,
+			emissive: Vector4::new(self.emissive.x, self.emissive.y, self.emissive.z, 0.0),
+			metallic: self.metallic,
+			roughness: self.roughness,
+			normal_scale: self.normal_scale,
+			occlusion_strength: self.occlusion_strength,
+			alpha_cutoff,
+			alpha_mode,
+			base_color_map: color(&self.base_color_map),
+			metallic_roughness_map: map(&self.metallic_roughness_map),
+			normal_map: map(&self.normal_map),
+			emissive_map: color(&self.emissive_map),
+			occlusion_map: map(&self.occlusion_map),
+			_pad: 0,
+		}
+	}
+}
+impl Default for Material {
+	fn default() -> Self {
+		Self {
+			base_color: Vector4::new(1.0, 1.0, 1.0, 1.0),
+			metallic: 0.0,
+			roughness: 1.0,
+			emissive: Vector3::zeros(),
+			normal_scale: 1.0,
+			occlusion_strength: 1.0,
+			alpha_mode: AlphaMode::Opaque,
+			base_color_map: None,
+			metallic_roughness_map: None,
+			normal_map: None,
+			emissive_map: None,
+			occlusion_map: None,
+		}
+	}
+}
+
+/// std430 layout of a material in the materials storage buffer. Maps are texture array layers, or -1. Base color and
+/// emissive maps are in the sRGB array.
+#[allow(unused)]
+#[derive(Clone, Copy, Debug, Default)]
+#[repr(C)]
+pub struct MaterialUniform {
+	base_color: Vector4<f32>,
+	emissive: Vector4<f32>,
+	metallic: f32,
+	roughness: f32,
+	normal_scale: f32,
+	occlusion_strength: f32,
+	alpha_cutoff: f32,
+	/// 0 opaque, 1 mask, 2 blend
+	alpha_mode: i32,
+	base_color_map: i32,
+	metallic_roughness_map: i32,
+	normal_map: i32,
+	emissive_map: i32,
+	occlusion_map: i32,
+	_pad: i32,
+}
+
+fn color(mat: &AiMaterial, key: &[u8]) -> Option<AiColor4D> {
+	let key = CStr::from_bytes_with_nul(key).unwrap();
+	let mut color = AiColor4D { r: 0.0, g: 0.0, b: 0.0, a: 0.0 };
+	let ret = unsafe { aiGetMaterialColor(mat, key.as_ptr(), 0, 0, &mut color) };
+	Some(color).filter(|_| ret == AiReturn::Success)
+}
+
+fn float(mat: &AiMaterial, key: &[u8]) -> Option<f32> {
+	let key = CStr::from_bytes_with_nul(key).unwrap();
+	let mut val = 0.0f32;
+	let mut max = 1u32;
+	let ret = unsafe { aiGetMaterialFloatArray(mat, key.as_ptr(), 0, 0, &mut val, &mut max) };
+	Some(val).filter(|_| ret == AiReturn::Success && max == 1)
+}
+
+fn texture(mat: &AiMaterial, ty: AiTextureType) -> Option<String> {
+	let mut path = AiString::default();
+	let ret = unsafe {
+		aiGetMaterialTexture(
+			mat,
+			ty,
+			0,
+			&mut path,
+			ptr::null(),
+			ptr::null_mut(),
+			ptr::null_mut(),
+			ptr::null_mut(),
+			ptr::null_mut(),
+			ptr::null_mut(),
+		)
+	};
+	let path = unsafe { str::from_utf8_unchecked(slice::from_raw_parts(path.data.as_ptr(), path.length)) };
+	Some(path.to_owned()).filter(|path| ret == AiReturn::Success && !path.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use image::Rgba;
+
+	/// Opaque white `size` by `size` texture with the first `clear` texels transparent and the next `partial` half
+	/// transparent.
+	fn texture(size: u32, clear: u32, partial: u32) -> RgbaImage {
+		RgbaImage::from_fn(size, size, |x, y| {
+			let i = y * size + x;
+			let alpha = if i < clear { 0 } else if i < clear + partial { 128 } else { 255 };
+			Rgba([255, 255, 255, alpha])
+		})
+	}
+
+	fn detect(img: &RgbaImage) -> AlphaMode {
+		let mut material = Material::default();
+		material.detect_alpha(img);
+		material.alpha_mode
+	}
+
+	#[test]
+	fn opaque_textures_stay_opaque() {
+		assert_eq!(detect(&texture(16, 0, 0)), AlphaMode::Opaque);
+		// A few anti-aliased edge texels
+		assert_eq!(detect(&texture(16, 0, 3)), AlphaMode::Opaque);
+	}
+
+	#[test]
+	fn cutouts_are_masked() {
+		assert_eq!(detect(&texture(16, 100, 0)), AlphaMode::Mask(0.5));
+		assert_eq!(detect(&texture(16, 100, 20)), AlphaMode::Mask(0.5));
+	}
+
+	#[test]
+	fn translucent_textures_blend() {
+		assert_eq!(detect(&texture(16, 0, 30)), AlphaMode::Blend);
+		assert_eq!(detect(&texture(16, 100, 30)), AlphaMode::Blend);
+	}
+
+	#[test]
+	fn explicit_modes_are_kept() {
+		let mut material = Material { alpha_mode: AlphaMode::Mask(0.3), ..Material::default() };
+		material.detect_alpha(&texture(16, 0, 200));
+		assert_eq!(material.alpha_mode, AlphaMode::Mask(0.3));
+	}
+}
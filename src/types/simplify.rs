@@ -0,0 +1,128 @@
+use nalgebra::Vector3;
+use std::collections::HashMap;
+
+/// Result of simplifying a mesh. Vertices are a subset of the original ones, so their other attributes carry over.
+pub struct Simplified {
+	/// Original index of each kept vertex
+	pub vertices: Vec<usize>,
+	/// Triangles indexing into `vertices`
+	pub indices: Vec<u16>,
+}
+
+/// Reduces a triangle mesh to about `ratio` of its triangles by vertex clustering: vertices in the same grid cell
+/// merge into the one nearest the cell's average, and triangles that collapse are dropped. The grid is searched for
+/// the coarsest resolution that keeps at least `ratio` of the triangles.
+pub fn simplify(positions: &[Vector3<f32>], indices: &[u16], ratio: f32) -> Simplified {
+	let target = (indices.len() / 3) as f32 * ratio;
+	let inf = Vector3::repeat(f32::INFINITY);
+	let (min, max) = positions.iter().fold((inf, -inf), |(min, max), p| (min.inf(p), max.sup(p)));
+	let extent = (max - min).max();
+	if positions.is_empty() || extent <= 0.0 {
+		return Simplified { vertices: (0..positions.len()).collect(), indices: indices.to_vec() };
+	}
+
+	let (mut lo, mut hi) = (1u32, 1024u32);
+	let mut best = cluster(positions, indices, min, extent / hi as f32);
+	while lo < hi {
+		let cells = (lo + hi) / 2;
+		let result = cluster(positions, indices, min, extent / cells as f32);
+		if (result.indices.len() / 3) as f32 >= target {
+			best = result;
+			hi = cells;
+		} else {
+			lo = cells + 1;
+		}
+	}
+	best
+}
+
+fn cluster(positions: &[Vector3<f32>], indices: &[u16], min: Vector3<f32>, cell: f32) -> Simplified {
+	let key = |p: &Vector3<f32>| {
+		let c = (p - min) / cell;
+		(c.x as i32, c.y as i32, c.z as i32)
+	};
+
+	let mut sums: HashMap<_, (Vector3<f32>, u32)> = HashMap::new();
+	for p in positions {
+		let sum = sums.entry(key(p)).or_insert((Vector3::zeros(), 0));
+		sum.0 += p;
+		sum.1 += 1;
+	}
+
+	// Representative of each cell, as (original index, distance from the cell average)
+	let mut reps: HashMap<_, (usize, f32)> = HashMap::new();
+	for (i, p) in positions.iter().enumerate() {
+		let k = key(p);
+		let (sum, count) = sums[&k];
+		let dist = (p - sum / count as f32).norm_squared();
+		let rep = reps.entry(k).or_insert((i, dist));
+		if dist < rep.1 {
+			*rep = (i, dist);
+		}
+	}
+
+	let mut vertices = vec![];
+	let mut remap = HashMap::new();
+	let mut out = vec![];
+	for tri in indices.chunks_exact(3) {
+		let tri: Vec<_> = tri.iter().map(|&i| reps[&key(&positions[i as usize])].0).collect();
+		if tri[0] == tri[1] || tri[1] == tri[2] || tri[0] == tri[2] {
+			continue;
+		}
+		for &i in &tri {
+			let new = *remap.entry(i).or_insert_with(|| {
+				vertices.push(i);
+				vertices.len() - 1
+			});
+			out.push(new as u16);
+		}
+	}
+	Simplified { vertices, indices: out }
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Flat `n` by `n` grid of quads, two triangles each.
+	fn grid(n: u16) -> (Vec<Vector3<f32>>, Vec<u16>) {
+		let positions = (0..=n).flat_map(|y| (0..=n).map(move |x| Vector3::new(x as f32, y as f32, 0.0))).collect();
+		let mut indices = vec![];
+		for y in 0..n {
+			for x in 0..n {
+				let i = y * (n + 1) + x;
+				indices.extend_from_slice(&[i, i + 1, i + n + 2, i, i + n + 2, i + n + 1]);
+			}
+		}
+		(positions, indices)
+	}
+
+	#[test]
+	fn keeps_at_least_the_ratio() {
+		let (positions, indices) = grid(16);
+		for &ratio in &[0.75, 0.5, 0.25] {
+			let simplified = simplify(&positions, &indices, ratio);
+			let triangles = simplified.indices.len() / 3;
+			assert!(triangles as f32 >= 512.0 * ratio, "{} triangles at ratio {}", triangles, ratio);
+			assert!(triangles < 512, "nothing removed at ratio {}", ratio);
+			assert!(simplified.indices.iter().all(|&i| (i as usize) < simplified.vertices.len()));
+			assert!(simplified.vertices.iter().all(|&i| i < positions.len()));
+		}
+	}
+
+	#[test]
+	fn full_ratio_keeps_everything() {
+		let (positions, indices) = grid(16);
+		let simplified = simplify(&positions, &indices, 1.0);
+		assert_eq!(simplified.indices.len(), indices.len());
+		assert_eq!(simplified.vertices.len(), positions.len());
+	}
+
+	#[test]
+	fn degenerate_meshes_are_copied() {
+		let positions = vec![Vector3::new(1.0, 2.0, 3.0); 3];
+		let simplified = simplify(&positions, &[0, 1, 2], 0.5);
+		assert_eq!(simplified.vertices, [0, 1, 2]);
+		assert_eq!(simplified.indices, [0, 1, 2]);
+	}
+}
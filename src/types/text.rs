@@ -0,0 +1,75 @@
+use nalgebra::{Vector3, Vector4};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Align {
+	Left,
+	Center,
+	Right,
+}
+impl Align {
+	/// Fraction of a line's width it's moved left by
+	pub fn factor(self) -> f32 {
+		match self {
+			Align::Left => 0.0,
+			Align::Center => 0.5,
+			Align::Right => 1.0,
+		}
+	}
+}
+
+#[derive(Clone, Debug)]
+pub struct TextStyle {
+	/// Pixels per em on screen, world units per em in the world
+	pub size: f32,
+	pub color: Vector4<f32>,
+	/// Width lines wrap at, in the same units as `size`
+	pub wrap: Option<f32>,
+	/// How lines line up with the text's position
+	pub align: Align,
+}
+impl TextStyle {
+	pub fn new(size: f32, color: Vector4<f32>) -> Self {
+		Self { size, color, wrap: None, align: Align::Left }
+	}
+}
+
+pub struct ScreenText {
+	/// Window pixels from the top left
+	pub pos: [f32; 2],
+	pub text: String,
+	pub style: TextStyle,
+}
+
+pub struct WorldText {
+	pub pos: Vector3<f32>,
+	pub text: String,
+	pub style: TextStyle,
+	pub depth_test: bool,
+}
+
+/// Text in the loaded TTF font any system can push to, drawn at the end of the frame and cleared after. Screen text
+/// goes over the post-processed image, so it's for HUDs and subtitles. World text faces the camera.
+pub struct TextDraw {
+	pub screen: Vec<ScreenText>,
+	pub world: Vec<WorldText>,
+}
+impl TextDraw {
+	pub fn new() -> Self {
+		Self { screen: vec![], world: vec![] }
+	}
+
+	pub fn clear(&mut self) {
+		self.screen.clear();
+		self.world.clear();
+	}
+
+	/// `pos` is the top of the text, at its left, center or right edge depending on `style.align`.
+	pub fn screen(&mut self, pos: [f32; 2], text: &str, style: &TextStyle) {
+		self.screen.push(ScreenText { pos, text: text.to_owned(), style: style.clone() });
+	}
+
+	/// Like `screen`, at a point in the world. Hidden behind the scene.
+	pub fn world(&mut self, pos: &Vector3<f32>, text: &str, style: &TextStyle) {
+		self.world.push(WorldText { pos: *pos, text: text.to_owned(), style: style.clone(), depth_test: true });
+	}
+}
@@ -0,0 +1,414 @@
+use crate::types::debug_draw::DebugVertex;
+use glutin::event::{ElementState, MouseButton, MouseScrollDelta, WindowEvent};
+use nalgebra::{Vector2, Vector3, Vector4};
+use std::{
+	collections::{hash_map::DefaultHasher, HashMap, HashSet},
+	hash::{Hash, Hasher},
+};
+
+/// Height of text in pixels
+const TEXT_HEIGHT: f32 = 10.0;
+const CHAR_WIDTH: f32 = TEXT_HEIGHT * 0.75;
+const ROW_HEIGHT: f32 = 18.0;
+const PADDING: f32 = 6.0;
+const WINDOW_WIDTH: f32 = 280.0;
+
+const BACKGROUND: [f32; 4] = [0.08, 0.08, 0.1, 0.85];
+const TITLE: [f32; 4] = [0.2, 0.25, 0.4, 0.95];
+const WIDGET: [f32; 4] = [0.2, 0.2, 0.25, 1.0];
+const HOVERED: [f32; 4] = [0.3, 0.3, 0.4, 1.0];
+const ACCENT: [f32; 4] = [0.4, 0.55, 0.9, 1.0];
+const TEXT: [f32; 4] = [0.9, 0.9, 0.9, 1.0];
+
+/// Ends of each segment of the text font in a 1 by 2 cell, as x0, y0, x1, y1
+const FONT_SEGMENTS: [[f32; 4]; 14] = [
+	[0.0, 2.0, 1.0, 2.0], // a: top
+	[1.0, 2.0, 1.0, 1.0], // b: upper right
+	[1.0, 1.0, 1.0, 0.0], // c: lower right
+	[0.0, 0.0, 1.0, 0.0], // d: bottom
+	[0.0, 0.0, 0.0, 1.0], // e: lower left
+	[0.0, 1.0, 0.0, 2.0], // f: upper left
+	[0.0, 1.0, 0.5, 1.0], // g: middle left
+	[0.5, 1.0, 1.0, 1.0], // h: middle right
+	[0.0, 2.0, 0.5, 1.0], // i: upper left diagonal
+	[0.5, 2.0, 0.5, 1.0], // j: upper center
+	[1.0, 2.0, 0.5, 1.0], // k: upper right diagonal
+	[0.0, 0.0, 0.5, 1.0], // l: lower left diagonal
+	[0.5, 0.0, 0.5, 1.0], // m: lower center
+	[1.0, 0.0, 0.5, 1.0], // n: lower right diagonal
+];
+
+/// Immediate-mode widgets drawn as colored triangles and lines in window pixels. Widgets are identified by their
+/// label within their window or section, so labels there should be unique.
+pub struct Ui {
+	size: Vector2<f32>,
+	mouse: Vector2<f32>,
+	mouse_down: bool,
+	mouse_pressed: bool,
+	/// Pixels scrolled this frame, positive up
+	scroll: f32,
+	/// Widget being dragged or pressed
+	active: Option<u64>,
+	/// Mouse position relative to the window being dragged when it was grabbed
+	grab: Vector2<f32>,
+	/// Top left of each window by title, kept between frames so they can be moved
+	windows: HashMap<String, Vector2<f32>>,
+	/// Sections that are expanded
+	open: HashSet<u64>,
+	/// Left edge of the window being laid out
+	window: Option<f32>,
+	/// Mixed into widget ids, so sections can reuse labels
+	scope: u64,
+	/// Where the next widget goes
+	cursor: Vector2<f32>,
+	/// Covered by a window last frame
+	wants_mouse: bool,
+	/// Window rectangles this frame
+	covered: Vec<(Vector2<f32>, Vector2<f32>)>,
+	triangles: Vec<DebugVertex>,
+	lines: Vec<DebugVertex>,
+}
+impl Ui {
+	pub fn new() -> Self {
+		Self {
+			size: Vector2::new(1.0, 1.0),
+			mouse: Vector2::new(-1.0, -1.0),
+			mouse_down: false,
+			mouse_pressed: false,
+			scroll: 0.0,
+			active: None,
+			grab: Vector2::zeros(),
+			windows: HashMap::new(),
+			open: HashSet::new(),
+			window: None,
+			scope: 0,
+			cursor: Vector2::zeros(),
+			wants_mouse: false,
+			covered: vec![],
+			triangles: vec![],
+			lines: vec![],
+		}
+	}
+
+	/// Takes this frame's input and clears last frame's widgets.
+	pub fn begin_frame(&mut self, events: &[WindowEvent], size: [u32; 2]) {
+		self.size = Vector2::new(size[0] as f32, size[1] as f32);
+		self.mouse_pressed = false;
+		self.scroll = 0.0;
+		for event in events {
+			match event {
+				WindowEvent::CursorMoved { position, .. } => {
+					self.mouse = Vector2::new(position.x as f32, position.y as f32);
+				},
+				WindowEvent::CursorLeft { .. } => self.mouse = Vector2::new(-1.0, -1.0),
+				WindowEvent::MouseInput { state, button: MouseButton::Left, .. } => {
+					self.mouse_down = *state == ElementState::Pressed;
+					self.mouse_pressed |= self.mouse_down;
+				},
+				WindowEvent::MouseWheel { delta, .. } => {
+					self.scroll += match delta {
+						MouseScrollDelta::LineDelta(_, y) => y * ROW_HEIGHT,
+						MouseScrollDelta::PixelDelta(pos) => pos.y as f32,
+					};
+				},
+				_ => (),
+			}
+		}
+		if !self.mouse_down {
+			self.active = None;
+		}
+
+		self.wants_mouse = self.active.is_some() || self.covered.iter().any(|&(min, max)| self.hovered(min, max));
+		self.covered.clear();
+		self.triangles.clear();
+		self.lines.clear();
+	}
+
+	/// Whether the mouse is over the UI or dragging a widget, so other systems should ignore it.
+	pub fn wants_mouse(&self) -> bool {
+		self.wants_mouse
+	}
+
+	/// Window pixels, y down
+	pub fn size(&self) -> [f32; 2] {
+		[self.size.x, self.size.y]
+	}
+
+	/// Triangle list of the widgets' backgrounds, in window pixels.
+	pub fn triangles(&self) -> &[DebugVertex] {
+		&self.triangles
+	}
+
+	/// Line list of the widgets' text and outlines, in window pixels.
+	pub fn lines(&self) -> &[DebugVertex] {
+		&self.lines
+	}
+
+	/// Lays out `contents` in a window that can be dragged by its title bar. `pos` is where it first appears.
+	pub fn window(&mut self, title: &str, pos: [f32; 2], contents: impl FnOnce(&mut Self)) {
+		let id = hash(&(title, "window"));
+		let mut top_left = *self.windows.entry(title.to_owned()).or_insert_with(|| Vector2::new(pos[0], pos[1]));
+		let title_max = top_left + Vector2::new(WINDOW_WIDTH, ROW_HEIGHT);
+		if self.clicked(id, top_left, title_max) {
+			self.grab = self.mouse - top_left;
+		} else if self.active == Some(id) {
+			top_left = self.mouse - self.grab;
+		}
+		top_left = top_left.sup(&Vector2::zeros()).inf(&(self.size - Vector2::new(WINDOW_WIDTH, ROW_HEIGHT)));
+		self.windows.insert(title.to_owned(), top_left);
+
+		// The background goes in once the height is known, before the contents so it's drawn under them
+		let background = self.triangles.len();
+		self.rect(top_left, top_left + Vector2::new(WINDOW_WIDTH, ROW_HEIGHT), TITLE);
+		self.text(top_left + Vector2::new(PADDING, (ROW_HEIGHT - TEXT_HEIGHT) / 2.0), title, TEXT);
+
+		self.window = Some(top_left.x);
+		self.scope = id;
+		self.cursor = top_left + Vector2::new(PADDING, ROW_HEIGHT + PADDING);
+		contents(self);
+		self.window = None;
+
+		let bottom = Vector2::new(top_left.x + WINDOW_WIDTH, self.cursor.y + PADDING);
+		let mut quad = vec![];
+		push_rect(&mut quad, top_left, bottom, BACKGROUND);
+		self.triangles.splice(background..background, quad);
+		self.covered.push((top_left, bottom));
+	}
+
+	pub fn label(&mut self, text: &str) {
+		let pos = self.cursor + Vector2::new(0.0, (ROW_HEIGHT - TEXT_HEIGHT) / 2.0);
+		self.text(pos, text, TEXT);
+		self.cursor.y += ROW_HEIGHT + text.matches('\n').count() as f32 * TEXT_HEIGHT * 1.25;
+	}
+
+	/// Returns true when clicked.
+	pub fn button(&mut self, text: &str) -> bool {
+		let id = self.id(text);
+		let min = self.cursor;
+		let max = min + Vector2::new(text_width(text) + PADDING * 2.0, ROW_HEIGHT);
+		let clicked = self.clicked(id, min, max);
+		let color = if self.hovered(min, max) { HOVERED } else { WIDGET };
+		self.rect(min, max, color);
+		self.text(min + Vector2::new(PADDING, (ROW_HEIGHT - TEXT_HEIGHT) / 2.0), text, TEXT);
+		self.next_row();
+		clicked
+	}
+
+	/// Returns true when toggled.
+	pub fn checkbox(&mut self, text: &str, value: &mut bool) -> bool {
+		let id = self.id(text);
+		let min = self.cursor;
+		let max = min + Vector2::new(self.width(), ROW_HEIGHT);
+		let clicked = self.clicked(id, min, max);
+		if clicked {
+			*value = !*value;
+		}
+		let box_max = min + Vector2::repeat(ROW_HEIGHT - 4.0);
+		self.rect(min, box_max, if self.hovered(min, max) { HOVERED } else { WIDGET });
+		if *value {
+			self.rect(min + Vector2::repeat(3.0), box_max - Vector2::repeat(3.0), ACCENT);
+		}
+		self.text(min + Vector2::new(ROW_HEIGHT + 2.0, (ROW_HEIGHT - TEXT_HEIGHT) / 2.0 - 2.0), text, TEXT);
+		self.next_row();
+		clicked
+	}
+
+	/// Drag or scroll over the bar to change `value` between `min` and `max`. Returns true when changed.
+	pub fn slider(&mut self, text: &str, value: &mut f32, min: f32, max: f32) -> bool {
+		let id = self.id(text);
+		let lo = self.cursor;
+		let hi = lo + Vector2::new(self.width(), ROW_HEIGHT);
+		let old = *value;
+		if self.clicked(id, lo, hi) || self.active == Some(id) {
+			let t = ((self.mouse.x - lo.x) / (hi.x - lo.x)).max(0.0).min(1.0);
+			*value = min + (max - min) * t;
+		} else if self.hovered(lo, hi) && self.scroll != 0.0 {
+			*value = (*value + self.scroll.signum() * (max - min) / 100.0).max(min).min(max);
+		}
+
+		self.rect(lo, hi, if self.hovered(lo, hi) { HOVERED } else { WIDGET });
+		let t = ((*value - min) / (max - min)).max(0.0).min(1.0);
+		self.rect(lo, Vector2::new(lo.x + (hi.x - lo.x) * t, hi.y), ACCENT);
+		let label = format!("{}: {:.3}", text, value);
+		self.text(lo + Vector2::new(PADDING, (ROW_HEIGHT - TEXT_HEIGHT) / 2.0), &label, TEXT);
+		self.next_row();
+		*value != old
+	}
+
+	/// Line graph of `values` from 0 at the bottom to `max` at the top.
+	pub fn plot(&mut self, values: &[f32], max: f32) {
+		let min = self.cursor;
+		let size = Vector2::new(self.width(), ROW_HEIGHT * 3.0);
+		self.rect(min, min + size, WIDGET);
+		let point = |i: usize| {
+			let x = i as f32 / (values.len() - 1) as f32;
+			let y = (values[i] / max).max(0.0).min(1.0);
+			Vector3::new(min.x + x * size.x, min.y + (1.0 - y) * size.y, 0.0)
+		};
+		for i in 1..values.len() {
+			self.lines.push(DebugVertex { pos: point(i - 1), color: Vector4::from(ACCENT) });
+			self.lines.push(DebugVertex { pos: point(i), color: Vector4::from(ACCENT) });
+		}
+		self.cursor.y += size.y + 2.0;
+	}
+
+	/// Header that expands `contents` when clicked. Starts closed.
+	pub fn collapsing(&mut self, text: &str, contents: impl FnOnce(&mut Self)) {
+		let id = self.id(text);
+		let min = self.cursor;
+		let max = min + Vector2::new(self.width(), ROW_HEIGHT);
+		if self.clicked(id, min, max) && !self.open.remove(&id) {
+			self.open.insert(id);
+		}
+		let open = self.open.contains(&id);
+		self.rect(min, max, if self.hovered(min, max) { HOVERED } else { WIDGET });
+		let header = format!("{} {}", if open { "-" } else { "+" }, text);
+		self.text(min + Vector2::new(PADDING, (ROW_HEIGHT - TEXT_HEIGHT) / 2.0), &header, TEXT);
+		self.next_row();
+		if open {
+			let scope = self.scope;
+			self.scope = id;
+			self.cursor.x += PADDING * 2.0;
+			contents(self);
+			self.cursor.x -= PADDING * 2.0;
+			self.scope = scope;
+		}
+	}
+
+	fn id(&self, label: &str) -> u64 {
+		hash(&(self.scope, label))
+	}
+
+	/// Room left in the window from the cursor
+	fn width(&self) -> f32 {
+		let left = self.window.unwrap_or(0.0);
+		left + WINDOW_WIDTH - PADDING - self.cursor.x
+	}
+
+	fn next_row(&mut self) {
+		self.cursor.y += ROW_HEIGHT + 2.0;
+	}
+
+	fn hovered(&self, min: Vector2<f32>, max: Vector2<f32>) -> bool {
+		let mouse = self.mouse;
+		mouse.x >= min.x && mouse.y >= min.y && mouse.x < max.x && mouse.y < max.y
+	}
+
+	/// Becomes active when pressed, and reports a click when pressed while nothing else is active. Windows use this
+	/// to start dragging, so it fires on press rather than release.
+	fn clicked(&mut self, id: u64, min: Vector2<f32>, max: Vector2<f32>) -> bool {
+		if self.mouse_pressed && self.active.is_none() && self.hovered(min, max) {
+			self.active = Some(id);
+			true
+		} else {
+			false
+		}
+	}
+
+	fn rect(&mut self, min: Vector2<f32>, max: Vector2<f32>, color: [f32; 4]) {
+		push_rect(&mut self.triangles, min, max, color);
+	}
+
+	/// `pos` is the top left of the first line.
+	fn text(&mut self, pos: Vector2<f32>, text: &str, color: [f32; 4]) {
+		let scale = TEXT_HEIGHT / 2.0;
+		let lines = &mut self.lines;
+		segment_text(text, |[x, y]| {
+			let pos = Vector3::new(pos.x + x * scale, pos.y + (2.0 - y) * scale, 0.0);
+			lines.push(DebugVertex { pos, color: Vector4::from(color) });
+		});
+	}
+}
+
+fn push_rect(triangles: &mut Vec<DebugVertex>, min: Vector2<f32>, max: Vector2<f32>, color: [f32; 4]) {
+	let color = Vector4::from(color);
+	let corners = [[min.x, min.y], [max.x, min.y], [max.x, max.y], [min.x, min.y], [max.x, max.y], [min.x, max.y]];
+	triangles.extend(corners.iter().map(|&[x, y]| DebugVertex { pos: Vector3::new(x, y, 0.0), color }));
+}
+
+fn text_width(text: &str) -> f32 {
+	text.lines().map(|line| line.chars().count()).max().unwrap_or(0) as f32 * CHAR_WIDTH
+}
+
+fn hash(value: &impl Hash) -> u64 {
+	let mut hasher = DefaultHasher::new();
+	value.hash(&mut hasher);
+	hasher.finish()
+}
+
+/// Line list of `text` in the segment font, in units of half a line's height with y up. Characters are 1.5 wide and
+/// lines 2.5 apart, starting at the origin and going down.
+fn segment_text(text: &str, mut vertex: impl FnMut([f32; 2])) {
+	let (mut col, mut row) = (0.0, 0.0);
+	for c in text.chars() {
+		if c == '\n' {
+			col = 0.0;
+			row += 1.0;
+			continue;
+		}
+		let (x, y) = (col * 1.5, -row * 2.5);
+		for &[x0, y0, x1, y1] in glyph(c).bytes().map(|seg| &FONT_SEGMENTS[(seg - b'a') as usize]) {
+			vertex([x + x0, y + y0]);
+			vertex([x + x1, y + y1]);
+		}
+		col += 1.0;
+	}
+}
+
+/// Segments lit for `c`, as letters indexing `FONT_SEGMENTS`. Unknown characters are blank.
+fn glyph(c: char) -> &'static str {
+	match c.to_ascii_uppercase() {
+		'0' => "abcdefkl",
+		'1' => "bck",
+		'2' => "abdegh",
+		'3' => "abcdh",
+		'4' => "bcfgh",
+		'5' => "acdfgh",
+		'6' => "acdefgh",
+		'7' => "abc",
+		'8' => "abcdefgh",
+		'9' => "abcdfgh",
+		'A' => "abcefgh",
+		'B' => "abcdhjm",
+		'C' => "adef",
+		'D' => "abcdjm",
+		'E' => "adefg",
+		'F' => "aefg",
+		'G' => "acdefh",
+		'H' => "bcefgh",
+		'I' => "adjm",
+		'J' => "bcde",
+		'K' => "efgkn",
+		'L' => "def",
+		'M' => "bcefik",
+		'N' => "bcefin",
+		'O' => "abcdef",
+		'P' => "abefgh",
+		'Q' => "abcdefn",
+		'R' => "abefghn",
+		'S' => "acdfgh",
+		'T' => "ajm",
+		'U' => "bcdef",
+		'V' => "efkl",
+		'W' => "bcefln",
+		'X' => "ikln",
+		'Y' => "ikm",
+		'Z' => "adkl",
+		'-' => "gh",
+		'+' => "ghjm",
+		'=' => "dgh",
+		'_' => "d",
+		'/' => "kl",
+		'\\' => "in",
+		'*' => "ghijklmn",
+		'(' | '<' => "kn",
+		')' | '>' => "il",
+		'[' => "adef",
+		']' => "abcd",
+		'\'' => "j",
+		'"' => "bj",
+		'|' | ':' => "jm",
+		',' | '.' => "l",
+		_ => "",
+	}
+}